@@ -0,0 +1,37 @@
+//! Pretends to be one or more picos, sending a slowly drifting reading for each location.
+//!
+//! cargo run --example simulator -- http://localhost:8080 basement garage
+
+use std::thread::sleep;
+use std::time::Duration;
+use temp_client::TempClient;
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let base_url = args
+        .next()
+        .unwrap_or_else(|| "http://localhost:8080".to_string());
+    let locations = args.collect::<Vec<_>>();
+    let locations = if locations.is_empty() {
+        vec!["simulated".to_string()]
+    } else {
+        locations
+    };
+
+    let client = TempClient::new(base_url);
+
+    for tick in 0u32.. {
+        for (idx, location) in locations.iter().enumerate() {
+            let phase = (tick as f32 / 30.0) + idx as f32;
+            let temperature = 20.0 + 3.0 * phase.sin();
+            let humidity = 45.0 + 10.0 * phase.cos();
+
+            match client.send_reading(location, temperature, humidity) {
+                Ok(()) => println!("{}: {:.1} C, {:.1} %", location, temperature, humidity),
+                Err(err) => eprintln!("{}: {}", location, err),
+            }
+        }
+
+        sleep(Duration::from_secs(60));
+    }
+}
@@ -0,0 +1,65 @@
+//! Renames, merges, archives and deletes locations on a running temp_server.
+//!
+//! The server url is read from TEMP_SERVER_URL, defaulting to http://localhost:8080, and the
+//! admin token from TEMP_SERVER_ADMIN_TOKEN.
+//!
+//! cargo run --bin temp_admin -- rename kitchn kitchen
+
+use std::process::ExitCode;
+use temp_client::TempClient;
+
+const USAGE: &str = "usage: temp_admin <command>
+    rename <location> <new name>
+    merge <location> <into location>
+    archive <location>
+    restore <location>
+    delete <location>
+    archived";
+
+fn main() -> ExitCode {
+    let base_url =
+        std::env::var("TEMP_SERVER_URL").unwrap_or_else(|_| "http://localhost:8080".to_string());
+    let mut client = TempClient::new(base_url);
+    if let Ok(token) = std::env::var("TEMP_SERVER_ADMIN_TOKEN") {
+        client = client.with_admin_token(token);
+    }
+
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+
+    let result = match args.as_slice() {
+        ["rename", location, to] => client
+            .rename_location(location, to)
+            .map(|()| format!("Renamed {} to {}", location, to)),
+        ["merge", location, into] => client
+            .merge_location(location, into)
+            .map(|()| format!("Merged {} into {}", location, into)),
+        ["archive", location] => client
+            .archive_location(location)
+            .map(|()| format!("Archived {}", location)),
+        ["restore", location] => client
+            .restore_location(location)
+            .map(|()| format!("Restored {}", location)),
+        ["delete", location] => client
+            .delete_location(location)
+            .map(|()| format!("Deleted {}", location)),
+        ["archived"] => client
+            .archived_locations()
+            .map(|locations| locations.join("\n")),
+        _ => {
+            eprintln!("{}", USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match result {
+        Ok(message) => {
+            println!("{}", message);
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,526 @@
+//! A small blocking client for the temp_server http api, for scripts and sensor simulators.
+//!
+//! Every endpoint used here is listed in [`ENDPOINTS`], and the tests check both those paths and
+//! the response types against the OpenAPI document the server generates.
+
+mod types;
+
+pub use types::*;
+
+use chrono::{DateTime, FixedOffset, NaiveDate};
+use serde::de::DeserializeOwned;
+use ureq::{Agent, RequestBuilder};
+
+pub type Result<T> = std::result::Result<T, ureq::Error>;
+
+/// Every (method, path template) this client calls, matching the paths in the OpenAPI document.
+pub const ENDPOINTS: &[(&str, &str)] = &[
+    ("get", "/reading/{location}/{temperature}/{humidity}"),
+    ("get", "/api/v1/latest"),
+    ("get", "/api/v1/locations/{location}/latest"),
+    ("get", "/api/v1/health"),
+    ("get", "/api/v1/locations/{location}/health"),
+    ("get", "/api/v1/locations/{location}/metadata"),
+    ("put", "/api/v1/locations/{location}/metadata"),
+    ("post", "/api/v1/admin/locations/{location}/rename"),
+    ("post", "/api/v1/admin/locations/{location}/merge"),
+    ("post", "/api/v1/admin/locations/{location}/archive"),
+    ("post", "/api/v1/admin/locations/{location}/restore"),
+    ("delete", "/api/v1/admin/locations/{location}"),
+    ("get", "/api/v1/admin/archive"),
+    ("get", "/api/v1/alerts"),
+    ("get", "/api/v1/notifications/deliveries"),
+    ("get", "/api/v1/locations/{location}/readings"),
+    ("get", "/api/v1/locations/{location}/export.csv"),
+    ("get", "/api/v1/locations/{location}/aggregate"),
+    ("get", "/api/v1/locations/{location}/statistics"),
+    ("get", "/api/v1/locations/{location}/outages"),
+    ("get", "/api/v1/compare"),
+    ("get", "/plot/{location}"),
+    ("get", "/api/openapi.json"),
+];
+
+/// Query for [`TempClient::aggregate`], left out fields use the server defaults.
+#[derive(Debug, Clone, Default)]
+pub struct AggregateQuery {
+    pub from: Option<DateTime<FixedOffset>>,
+    pub to: Option<DateTime<FixedOffset>>,
+    /// Bucket width such as "5m", "1h" or "1d"
+    pub bucket: String,
+    pub aggregates: Vec<String>,
+    pub metrics: Vec<String>,
+}
+
+/// Query for [`TempClient::compare`], left out fields use the server defaults.
+#[derive(Debug, Clone, Default)]
+pub struct CompareQuery {
+    pub locations: Vec<String>,
+    pub from: Option<DateTime<FixedOffset>>,
+    pub to: Option<DateTime<FixedOffset>>,
+    /// How far back from `to` to compare, such as "24h" or "7d"
+    pub last: Option<String>,
+    pub step: Option<String>,
+    pub metric: Option<String>,
+}
+
+pub struct TempClient {
+    base_url: String,
+    agent: Agent,
+    admin_token: Option<String>,
+}
+
+/// Percent encodes a location so it can be used as a single path segment.
+fn encode_segment(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn with_range<B>(
+    request: RequestBuilder<B>,
+    from: Option<DateTime<FixedOffset>>,
+    to: Option<DateTime<FixedOffset>>,
+) -> RequestBuilder<B> {
+    let request = match from {
+        None => request,
+        Some(from) => request.query("from", from.to_rfc3339()),
+    };
+    match to {
+        None => request,
+        Some(to) => request.query("to", to.to_rfc3339()),
+    }
+}
+
+impl TempClient {
+    /// `base_url` is the server root, e.g. "http://localhost:8080"
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            agent: Agent::new_with_defaults(),
+            admin_token: None,
+        }
+    }
+
+    /// Sets the token the admin methods send, the server's TEMP_SERVER_ADMIN_TOKEN.
+    pub fn with_admin_token(mut self, token: impl Into<String>) -> Self {
+        self.admin_token = Some(token.into());
+        self
+    }
+
+    fn get(&self, path: &str) -> RequestBuilder<ureq::typestate::WithoutBody> {
+        self.agent.get(format!("{}{}", self.base_url, path))
+    }
+
+    fn admin<B>(&self, request: RequestBuilder<B>) -> RequestBuilder<B> {
+        match &self.admin_token {
+            None => request,
+            Some(token) => request.header("Authorization", format!("Bearer {}", token)),
+        }
+    }
+
+    fn admin_location_url(&self, location: &str, rest: &str) -> String {
+        format!(
+            "{}/api/v1/admin/locations/{}{}",
+            self.base_url,
+            encode_segment(location),
+            rest
+        )
+    }
+
+    fn location_path(location: &str, rest: &str) -> String {
+        format!("/api/v1/locations/{}/{}", encode_segment(location), rest)
+    }
+
+    fn json<T: DeserializeOwned>(request: RequestBuilder<ureq::typestate::WithoutBody>) -> Result<T> {
+        request.call()?.body_mut().read_json()
+    }
+
+    /// Sends a reading the same way a pico does, with the temperature in Celsius.
+    pub fn send_reading(&self, location: &str, temperature: f32, humidity: f32) -> Result<()> {
+        self.get(&format!(
+            "/reading/{}/{}/{}",
+            encode_segment(location),
+            temperature,
+            humidity
+        ))
+        .call()?;
+        Ok(())
+    }
+
+    pub fn latest(&self) -> Result<Vec<LatestReading>> {
+        Self::json(self.get("/api/v1/latest"))
+    }
+
+    pub fn location_latest(&self, location: &str) -> Result<LatestReading> {
+        Self::json(self.get(&Self::location_path(location, "latest")))
+    }
+
+    /// Whether every location is reporting as often as it should.
+    pub fn health(&self) -> Result<Vec<LocationHealth>> {
+        Self::json(self.get("/api/v1/health"))
+    }
+
+    pub fn location_health(&self, location: &str) -> Result<LocationHealth> {
+        Self::json(self.get(&Self::location_path(location, "health")))
+    }
+
+    pub fn metadata(&self, location: &str) -> Result<LocationMetadata> {
+        Self::json(self.get(&Self::location_path(location, "metadata")))
+    }
+
+    /// Replaces the metadata of a location, returning it as the server stored it.
+    /// Needs the admin token, see [`TempClient::with_admin_token`].
+    pub fn set_metadata(
+        &self,
+        location: &str,
+        metadata: &LocationMetadata,
+    ) -> Result<LocationMetadata> {
+        self.admin(self.agent.put(format!(
+            "{}{}",
+            self.base_url,
+            Self::location_path(location, "metadata")
+        )))
+        .send_json(metadata)?
+            .body_mut()
+            .read_json()
+    }
+
+    pub fn readings(
+        &self,
+        location: &str,
+        from: Option<DateTime<FixedOffset>>,
+        to: Option<DateTime<FixedOffset>>,
+    ) -> Result<Vec<ReadingWithMetrics>> {
+        Self::json(with_range(
+            self.get(&Self::location_path(location, "readings")),
+            from,
+            to,
+        ))
+    }
+
+    pub fn export_csv(
+        &self,
+        location: &str,
+        from: Option<DateTime<FixedOffset>>,
+        to: Option<DateTime<FixedOffset>>,
+    ) -> Result<String> {
+        with_range(
+            self.get(&Self::location_path(location, "export.csv")),
+            from,
+            to,
+        )
+        .call()?
+        .body_mut()
+        .read_to_string()
+    }
+
+    pub fn aggregate(&self, location: &str, query: &AggregateQuery) -> Result<AggregateResponse> {
+        let mut request = with_range(
+            self.get(&Self::location_path(location, "aggregate")),
+            query.from,
+            query.to,
+        )
+        .query("bucket", &query.bucket);
+
+        if !query.aggregates.is_empty() {
+            request = request.query("aggregates", query.aggregates.join(","));
+        }
+        if !query.metrics.is_empty() {
+            request = request.query("metrics", query.metrics.join(","));
+        }
+
+        Self::json(request)
+    }
+
+    pub fn statistics(
+        &self,
+        location: &str,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    ) -> Result<StatisticsResponse> {
+        let mut request = self.get(&Self::location_path(location, "statistics"));
+        if let Some(from) = from {
+            request = request.query("from", from.to_string());
+        }
+        if let Some(to) = to {
+            request = request.query("to", to.to_string());
+        }
+
+        Self::json(request)
+    }
+
+    /// Times the location did not report, `last` is how far back to look such as "30d",
+    /// the server looks back a week without it.
+    pub fn outages(&self, location: &str, last: Option<&str>) -> Result<OutagesResponse> {
+        let mut request = self.get(&Self::location_path(location, "outages"));
+        if let Some(last) = last {
+            request = request.query("last", last);
+        }
+
+        Self::json(request)
+    }
+
+    pub fn compare(&self, query: &CompareQuery) -> Result<Comparison> {
+        let mut request = with_range(self.get("/api/v1/compare"), query.from, query.to)
+            .query("locations", query.locations.join(","));
+        if let Some(last) = &query.last {
+            request = request.query("last", last);
+        }
+        if let Some(step) = &query.step {
+            request = request.query("step", step);
+        }
+        if let Some(metric) = &query.metric {
+            request = request.query("metric", metric);
+        }
+
+        Self::json(request)
+    }
+
+    /// The svg plot of a location, `series` is a list of metric names and may be empty.
+    /// `last` is how far back to plot such as "7d", the server plots the last day without it.
+    pub fn plot_svg(&self, location: &str, series: &[&str], last: Option<&str>) -> Result<String> {
+        let mut request = self.get(&format!("/plot/{}", encode_segment(location)));
+        if !series.is_empty() {
+            request = request.query("series", series.join(","));
+        }
+        if let Some(last) = last {
+            request = request.query("last", last);
+        }
+
+        request.call()?.body_mut().read_to_string()
+    }
+
+    /// A png plot of a location, `width` and `height` are in pixels.
+    pub fn plot_png(
+        &self,
+        location: &str,
+        series: &[&str],
+        last: Option<&str>,
+        width: u32,
+        height: u32,
+    ) -> Result<Vec<u8>> {
+        let mut request = self
+            .get(&format!("/plot/{}", encode_segment(location)))
+            .query("format", "png")
+            .query("width", width.to_string())
+            .query("height", height.to_string());
+        if !series.is_empty() {
+            request = request.query("series", series.join(","));
+        }
+        if let Some(last) = last {
+            request = request.query("last", last);
+        }
+
+        request.call()?.body_mut().read_to_vec()
+    }
+
+    /// Gives a location a new name, keeping its readings and metadata.
+    pub fn rename_location(&self, location: &str, to: &str) -> Result<()> {
+        self.admin(self.agent.post(self.admin_location_url(location, "/rename")))
+            .send_json(serde_json::json!({ "to": to }))?;
+        Ok(())
+    }
+
+    /// Moves every reading of `location` into `into` and deletes `location`.
+    pub fn merge_location(&self, location: &str, into: &str) -> Result<()> {
+        self.admin(self.agent.post(self.admin_location_url(location, "/merge")))
+            .send_json(serde_json::json!({ "into": into }))?;
+        Ok(())
+    }
+
+    /// Hides a location, keeping its readings so it can be restored.
+    pub fn archive_location(&self, location: &str) -> Result<()> {
+        self.admin(self.agent.post(self.admin_location_url(location, "/archive")))
+            .send_empty()?;
+        Ok(())
+    }
+
+    pub fn restore_location(&self, location: &str) -> Result<()> {
+        self.admin(self.agent.post(self.admin_location_url(location, "/restore")))
+            .send_empty()?;
+        Ok(())
+    }
+
+    /// Deletes a location and its readings, or its archived copy if it is not in use.
+    pub fn delete_location(&self, location: &str) -> Result<()> {
+        self.admin(self.agent.delete(self.admin_location_url(location, "")))
+            .call()?;
+        Ok(())
+    }
+
+    pub fn archived_locations(&self) -> Result<Vec<String>> {
+        Self::json(self.admin(self.get("/api/v1/admin/archive")))
+    }
+
+    /// Alerts firing now and the latest `limit` alert events, the server lists 50 without a limit.
+    pub fn alerts(&self, limit: Option<usize>) -> Result<AlertsResponse> {
+        let mut request = self.get("/api/v1/alerts");
+        if let Some(limit) = limit {
+            request = request.query("limit", limit.to_string());
+        }
+
+        Self::json(request)
+    }
+
+    /// The latest `limit` notifications sent to the webhooks and by email, newest first, the server lists 50
+    /// without a limit.
+    pub fn deliveries(&self, limit: Option<usize>) -> Result<Vec<Delivery>> {
+        let mut request = self.get("/api/v1/notifications/deliveries");
+        if let Some(limit) = limit {
+            request = request.query("limit", limit.to_string());
+        }
+
+        Self::json(request)
+    }
+
+    /// The OpenAPI document served by the server.
+    pub fn openapi(&self) -> Result<serde_json::Value> {
+        Self::json(self.get("/api/openapi.json"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Serialize;
+    use serde_json::Value;
+    use std::collections::BTreeSet;
+
+    fn openapi() -> Value {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../temp_server/openapi.json");
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    /// Every property name of a schema, following `$ref` and `allOf` the way serde flatten is documented.
+    fn schema_properties(openapi: &Value, schema: &Value) -> BTreeSet<String> {
+        if let Some(reference) = schema["$ref"].as_str() {
+            let name = reference.trim_start_matches("#/components/schemas/");
+            return schema_properties(openapi, &openapi["components"]["schemas"][name]);
+        }
+
+        let mut properties = schema["properties"]
+            .as_object()
+            .map(|properties| properties.keys().cloned().collect::<BTreeSet<_>>())
+            .unwrap_or_default();
+
+        for part in schema["allOf"].as_array().into_iter().flatten() {
+            properties.extend(schema_properties(openapi, part));
+        }
+
+        properties
+    }
+
+    fn assert_matches_schema<T: Serialize + Default>(name: &str) {
+        let openapi = openapi();
+        let schema = &openapi["components"]["schemas"][name];
+        assert!(!schema.is_null(), "{} is not in openapi.json", name);
+
+        let fields = serde_json::to_value(T::default())
+            .unwrap()
+            .as_object()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect::<BTreeSet<_>>();
+
+        assert_eq!(fields, schema_properties(&openapi, schema), "{}", name);
+    }
+
+    #[test]
+    fn endpoints_exist_in_openapi() {
+        let openapi = openapi();
+
+        for (method, path) in ENDPOINTS {
+            assert!(
+                openapi["paths"][path][method].is_object(),
+                "{} {} is not in openapi.json",
+                method,
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn types_match_openapi() {
+        assert_matches_schema::<Reading>("Reading");
+        assert_matches_schema::<DerivedMetrics>("DerivedMetrics");
+        assert_matches_schema::<ReadingWithMetrics>("ReadingWithMetrics");
+        assert_matches_schema::<LatestReading>("LatestReading");
+        assert_matches_schema::<LocationHealth>("LocationHealth");
+        assert_matches_schema::<LocationMetadata>("LocationMetadata");
+        assert_matches_schema::<AlertEvent>("AlertEvent");
+        assert_matches_schema::<AlertsResponse>("AlertsResponse");
+        assert_matches_schema::<Delivery>("Delivery");
+        assert_matches_schema::<AggregateRow>("AggregateRow");
+        assert_matches_schema::<AggregateResponse>("AggregateResponse");
+        assert_matches_schema::<Extreme>("Extreme");
+        assert_matches_schema::<DailySummary>("DailySummary");
+        assert_matches_schema::<HourRecord>("HourRecord");
+        assert_matches_schema::<Records>("Records");
+        assert_matches_schema::<StatisticsResponse>("StatisticsResponse");
+        assert_matches_schema::<Outage>("Outage");
+        assert_matches_schema::<OutagesResponse>("OutagesResponse");
+        assert_matches_schema::<ComparisonSeries>("ComparisonSeries");
+        assert_matches_schema::<Comparison>("Comparison");
+    }
+
+    #[test]
+    fn health_states_match_openapi() {
+        let openapi = openapi();
+        let states = [SensorHealth::Ok, SensorHealth::Late, SensorHealth::Missing]
+            .map(|health| serde_json::to_value(health).unwrap());
+        assert_eq!(
+            openapi["components"]["schemas"]["SensorHealth"]["enum"],
+            Value::from(states.to_vec())
+        );
+    }
+
+    #[test]
+    fn temperature_units_match_openapi() {
+        let openapi = openapi();
+        let units = [TemperatureUnit::Fahrenheit, TemperatureUnit::Celsius]
+            .map(|unit| serde_json::to_value(unit).unwrap());
+        assert_eq!(
+            openapi["components"]["schemas"]["TemperatureUnit"]["enum"],
+            Value::from(units.to_vec())
+        );
+    }
+
+    #[test]
+    fn alert_states_match_openapi() {
+        let openapi = openapi();
+        let states = [AlertState::Firing, AlertState::Resolved]
+            .map(|state| serde_json::to_value(state).unwrap());
+        assert_eq!(
+            openapi["components"]["schemas"]["AlertState"]["enum"],
+            Value::from(states.to_vec())
+        );
+    }
+
+    #[test]
+    fn notification_kinds_match_openapi() {
+        let openapi = openapi();
+        let kinds = [
+            NotificationKind::Alert,
+            NotificationKind::Health,
+            NotificationKind::Digest,
+        ]
+        .map(|kind| serde_json::to_value(kind).unwrap());
+        assert_eq!(
+            openapi["components"]["schemas"]["NotificationKind"]["enum"],
+            Value::from(kinds.to_vec())
+        );
+    }
+
+    #[test]
+    fn encodes_path_segments() {
+        assert_eq!(encode_segment("living room"), "living%20room");
+        assert_eq!(encode_segment("garage_2"), "garage_2");
+    }
+}
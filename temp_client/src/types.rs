@@ -0,0 +1,212 @@
+//! Response types of the temp_server api, these mirror the schemas in `temp_server/openapi.json`.
+
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Reading {
+    pub location: String,
+    pub temperature: f32,
+    pub humidity: f32,
+    pub reading_time: DateTime<FixedOffset>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DerivedMetrics {
+    pub dew_point: f32,
+    pub absolute_humidity: f32,
+    pub heat_index: f32,
+    pub humidex: f32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReadingWithMetrics {
+    #[serde(flatten)]
+    pub reading: Reading,
+    #[serde(flatten)]
+    pub derived: DerivedMetrics,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LatestReading {
+    pub location: String,
+    pub reading: Option<Reading>,
+    pub age_seconds: Option<i64>,
+    pub stale: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SensorHealth {
+    Ok,
+    Late,
+    #[default]
+    Missing,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LocationHealth {
+    pub location: String,
+    pub health: SensorHealth,
+    pub last_seen: Option<DateTime<FixedOffset>>,
+    pub age_seconds: Option<i64>,
+    pub late_after_seconds: i64,
+    pub missing_after_seconds: i64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TemperatureUnit {
+    #[default]
+    Fahrenheit,
+    Celsius,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LocationMetadata {
+    pub display_name: Option<String>,
+    pub description: Option<String>,
+    pub group: Option<String>,
+    pub tags: Vec<String>,
+    pub reading_period_seconds: Option<u32>,
+    pub unit: Option<TemperatureUnit>,
+    /// As #rrggbb
+    pub color: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertState {
+    #[default]
+    Firing,
+    Resolved,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AlertEvent {
+    pub rule: String,
+    pub location: String,
+    pub state: AlertState,
+    pub time: DateTime<FixedOffset>,
+    pub metric: String,
+    /// In the unit of the rule
+    pub value: f32,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AlertsResponse {
+    pub firing: Vec<AlertEvent>,
+    /// Newest first
+    pub events: Vec<AlertEvent>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    #[default]
+    Alert,
+    Health,
+    Digest,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Delivery {
+    pub notifier: String,
+    pub kind: NotificationKind,
+    pub message: String,
+    pub time: DateTime<FixedOffset>,
+    pub attempts: u32,
+    pub delivered: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AggregateRow {
+    pub bucket_start: DateTime<FixedOffset>,
+    pub bucket_end: DateTime<FixedOffset>,
+    pub count: usize,
+    /// Keyed by metric then aggregate, e.g. `values["temperature"]["mean"]`
+    pub values: BTreeMap<String, BTreeMap<String, f64>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AggregateResponse {
+    pub location: String,
+    pub bucket_seconds: i64,
+    pub rows: Vec<AggregateRow>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Extreme {
+    pub value: f32,
+    pub time: DateTime<FixedOffset>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DailySummary {
+    pub date: NaiveDate,
+    pub count: usize,
+    pub temperature_high: Extreme,
+    pub temperature_low: Extreme,
+    pub temperature_mean: f32,
+    pub humidity_high: Extreme,
+    pub humidity_low: Extreme,
+    pub humidity_mean: f32,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct HourRecord {
+    pub hour_start: NaiveDateTime,
+    pub mean: f32,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Records {
+    pub highest_temperature: Option<Extreme>,
+    pub lowest_temperature: Option<Extreme>,
+    pub highest_humidity: Option<Extreme>,
+    pub lowest_humidity: Option<Extreme>,
+    pub hottest_day: Option<DailySummary>,
+    pub coldest_day: Option<DailySummary>,
+    pub most_humid_hour: Option<HourRecord>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StatisticsResponse {
+    pub location: String,
+    pub records: Records,
+    pub days: Vec<DailySummary>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Outage {
+    pub start: DateTime<FixedOffset>,
+    /// None while the sensor has not reported again
+    pub end: Option<DateTime<FixedOffset>>,
+    pub duration_seconds: i64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OutagesResponse {
+    pub location: String,
+    pub reading_period_seconds: i64,
+    pub max_gap_seconds: i64,
+    pub outages: Vec<Outage>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ComparisonSeries {
+    pub location: String,
+    pub values: Vec<Option<f64>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Comparison {
+    pub metric: String,
+    pub step_seconds: i64,
+    pub times: Vec<DateTime<FixedOffset>>,
+    pub series: Vec<ComparisonSeries>,
+}
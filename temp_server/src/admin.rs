@@ -0,0 +1,363 @@
+use crate::location::{validate_name, Location};
+use crate::notifier::Notification;
+use crate::reading::Reading;
+use crate::state::{LocationInfo, TemperatureServerState};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+use tracing::{error, info};
+
+/// Why an admin operation was refused or failed.
+#[derive(Debug)]
+pub enum AdminError {
+    UnknownLocation(Location),
+    /// The operation would overwrite another location
+    Conflict(String),
+    InvalidName(String),
+    Io(std::io::Error),
+}
+
+impl Display for AdminError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AdminError::UnknownLocation(location) => write!(f, "Unknown location: {}", location),
+            AdminError::Conflict(err) | AdminError::InvalidName(err) => write!(f, "{}", err),
+            AdminError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<std::io::Error> for AdminError {
+    fn from(err: std::io::Error) -> Self {
+        AdminError::Io(err)
+    }
+}
+
+type Locations = HashMap<Location, LocationInfo>;
+
+/// Opens the csv file of a location again after an operation on it failed part way,
+/// so the location keeps working.
+fn reopen(state: &TemperatureServerState, locations: &mut Locations, location: &Location) {
+    match LocationInfo::open(&state.location_path(location), location) {
+        Ok(location_info) => {
+            locations.insert(location.clone(), location_info);
+        }
+        Err(err) => error!("Error reopening {}: {}", location, err),
+    }
+}
+
+/// Checks the name of a location given to the admin api, before it is turned into a path that
+/// could otherwise lead out of the log folder.
+fn check_name(location: &Location) -> Result<(), AdminError> {
+    validate_name(location.as_str()).map_err(AdminError::InvalidName)
+}
+
+fn check_new_name(
+    state: &TemperatureServerState,
+    locations: &Locations,
+    location: &Location,
+) -> Result<(), AdminError> {
+    check_name(location)?;
+    if locations.contains_key(location) || state.location_path(location).exists() {
+        return Err(AdminError::Conflict(format!(
+            "Location already exists: {}",
+            location
+        )));
+    }
+    Ok(())
+}
+
+/// Where the csv file of a location in use is.
+fn current_path(locations: &Locations, location: &Location) -> Result<PathBuf, AdminError> {
+    match locations.get(location) {
+        None => Err(AdminError::UnknownLocation(location.clone())),
+        Some(location_info) => Ok(location_info.path().to_path_buf()),
+    }
+}
+
+/// Resolves the alerts of a location that is going away, and lets the notifiers know.
+async fn resolve_alerts(state: &TemperatureServerState, location: &Location, reason: &str) {
+    let events = state
+        .alerts
+        .lock()
+        .await
+        .remove_location(location, reason)
+        .await;
+    for event in events {
+        // there being no notifiers is not an error
+        let _ = state.notifications.send(Notification::Alert(event));
+    }
+}
+
+async fn read_all(
+    locations: &mut Locations,
+    location: &Location,
+) -> Result<Vec<Reading>, AdminError> {
+    match locations.get_mut(location) {
+        None => Err(AdminError::UnknownLocation(location.clone())),
+        Some(location_info) => Ok(location_info.read_readings(location).await?),
+    }
+}
+
+/// Gives a location a new name, keeping its readings and metadata.
+pub async fn rename_location(
+    state: &TemperatureServerState,
+    from: &Location,
+    to: &Location,
+) -> Result<(), AdminError> {
+    check_name(from)?;
+    {
+        let mut lock = state.file_buf_list.lock().await;
+        let from_path = current_path(&lock, from)?;
+        check_new_name(state, &lock, to)?;
+
+        // closes the file, which some platforms need before it can be renamed
+        lock.remove(from);
+        if let Err(err) = fs::rename(from_path, state.location_path(to)).await {
+            reopen(state, &mut lock, from);
+            return Err(err.into());
+        }
+        reopen(state, &mut lock, to);
+    }
+
+    let group = {
+        let mut metadata = state.metadata.lock().await;
+        metadata.rename(from, to).await?;
+        metadata.get(to).group
+    };
+    // the alerts just move over, so the notifiers aren't told about them
+    state
+        .alerts
+        .lock()
+        .await
+        .rename_location(from, to, group.as_deref())
+        .await;
+    info!("Renamed {} to {}", from, to);
+    Ok(())
+}
+
+/// Adds every reading of `from` to `into` in time order, then deletes `from`.
+/// `into` keeps its own metadata. Lines of either file that don't parse are dropped.
+pub async fn merge_location(
+    state: &TemperatureServerState,
+    from: &Location,
+    into: &Location,
+) -> Result<(), AdminError> {
+    check_name(from)?;
+    check_name(into)?;
+    if from == into {
+        return Err(AdminError::Conflict(format!(
+            "Can't merge {} into itself",
+            from
+        )));
+    }
+
+    {
+        let mut lock = state.file_buf_list.lock().await;
+        let from_path = current_path(&lock, from)?;
+        let into_path = current_path(&lock, into)?;
+        let from_readings = read_all(&mut lock, from).await?;
+        let into_readings = read_all(&mut lock, into).await?;
+        let merged = merge_readings(into_readings, from_readings);
+
+        // the merged file is written beside the real one and moved over it once complete,
+        // the name has no .csv in it so a half written one is never loaded as a location
+        let merging_path = state.log_folder.join(format!("{}.merging", into));
+        let mut file = fs::File::create(&merging_path).await?;
+        file.write_all("Date,Time,Temperature,Humidity\n".as_bytes())
+            .await?;
+        for reading in &merged {
+            file.write_all(reading.format_to_file().as_bytes()).await?;
+        }
+        file.sync_all().await?;
+        drop(file);
+
+        lock.remove(into);
+        if let Err(err) = fs::rename(&merging_path, into_path).await {
+            reopen(state, &mut lock, into);
+            return Err(err.into());
+        }
+        reopen(state, &mut lock, into);
+
+        lock.remove(from);
+        if let Err(err) = fs::remove_file(from_path).await {
+            reopen(state, &mut lock, from);
+            return Err(err.into());
+        }
+    }
+
+    resolve_alerts(state, from, &format!("merged into {}", into)).await;
+    state.metadata.lock().await.remove(from).await?;
+    info!("Merged {} into {}", from, into);
+    Ok(())
+}
+
+/// Merges two lists of readings that are each in time order, readings of `first` go first
+/// when both have one at the same time.
+fn merge_readings(first: Vec<Reading>, second: Vec<Reading>) -> Vec<Reading> {
+    let mut merged = Vec::with_capacity(first.len() + second.len());
+    let mut first = first.into_iter().peekable();
+    let mut second = second.into_iter().peekable();
+
+    loop {
+        let take_first = match (first.peek(), second.peek()) {
+            (None, None) => break,
+            (Some(_), None) => true,
+            (None, Some(_)) => false,
+            (Some(a), Some(b)) => a.reading_time() <= b.reading_time(),
+        };
+        merged.extend(if take_first {
+            first.next()
+        } else {
+            second.next()
+        });
+    }
+
+    merged
+}
+
+/// Moves a location out of the way, it stops being listed but its file is kept to be restored.
+pub async fn archive_location(
+    state: &TemperatureServerState,
+    location: &Location,
+) -> Result<(), AdminError> {
+    check_name(location)?;
+    let mut lock = state.file_buf_list.lock().await;
+    let path = current_path(&lock, location)?;
+
+    let archived_path = state.archive_folder().join(location.path());
+    if archived_path.exists() {
+        return Err(AdminError::Conflict(format!(
+            "{} is already archived, delete or restore the archived copy first",
+            location
+        )));
+    }
+    fs::create_dir_all(state.archive_folder()).await?;
+
+    lock.remove(location);
+    if let Err(err) = fs::rename(path, archived_path).await {
+        reopen(state, &mut lock, location);
+        return Err(err.into());
+    }
+    drop(lock);
+
+    resolve_alerts(state, location, "archived").await;
+    info!("Archived {}", location);
+    Ok(())
+}
+
+/// Brings an archived location back.
+pub async fn restore_location(
+    state: &TemperatureServerState,
+    location: &Location,
+) -> Result<(), AdminError> {
+    check_name(location)?;
+    let mut lock = state.file_buf_list.lock().await;
+
+    let archived_path = state.archive_folder().join(location.path());
+    if !archived_path.exists() {
+        return Err(AdminError::UnknownLocation(location.clone()));
+    }
+    if lock.contains_key(location) {
+        return Err(AdminError::Conflict(format!(
+            "{} has reported since it was archived, merge or rename it first",
+            location
+        )));
+    }
+
+    fs::rename(archived_path, state.location_path(location)).await?;
+    reopen(state, &mut lock, location);
+
+    info!("Restored {}", location);
+    Ok(())
+}
+
+/// Deletes a location and all of its readings, or its archived copy when it is not in use.
+pub async fn delete_location(
+    state: &TemperatureServerState,
+    location: &Location,
+) -> Result<(), AdminError> {
+    check_name(location)?;
+    {
+        let mut lock = state.file_buf_list.lock().await;
+
+        if let Some(location_info) = lock.remove(location) {
+            if let Err(err) = fs::remove_file(location_info.path()).await {
+                reopen(state, &mut lock, location);
+                return Err(err.into());
+            }
+        } else {
+            let archived_path = state.archive_folder().join(location.path());
+            if !archived_path.exists() {
+                return Err(AdminError::UnknownLocation(location.clone()));
+            }
+            fs::remove_file(archived_path).await?;
+        }
+    }
+
+    resolve_alerts(state, location, "deleted").await;
+    state.metadata.lock().await.remove(location).await?;
+    info!("Deleted {}", location);
+    Ok(())
+}
+
+/// Every archived location, by name.
+pub async fn archived_locations(
+    state: &TemperatureServerState,
+) -> std::io::Result<Vec<Location>> {
+    let mut entries = match fs::read_dir(state.archive_folder()).await {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err),
+    };
+
+    let mut locations = vec![];
+    while let Some(entry) = entries.next_entry().await? {
+        if let Some(name) = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.strip_suffix(".csv"))
+        {
+            locations.push(Location::from(name));
+        }
+    }
+    locations.sort();
+    Ok(locations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Local, TimeZone};
+
+    #[test]
+    fn merged_readings_are_in_time_order() {
+        let reading = |location: &str, minute: u32| {
+            Reading::new(
+                location.into(),
+                70.0,
+                40.0,
+                Local.with_ymd_and_hms(2025, 1, 1, 0, minute, 0).unwrap(),
+            )
+        };
+
+        let merged = merge_readings(
+            vec![reading("a", 0), reading("a", 2), reading("a", 4)],
+            vec![reading("b", 1), reading("b", 2), reading("b", 5)],
+        );
+
+        let order = merged
+            .iter()
+            .map(|reading| {
+                format!(
+                    "{}{}",
+                    reading.location(),
+                    reading.reading_time().format("%M")
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(order, ["a00", "b01", "a02", "b02", "a04", "b05"]);
+    }
+}
@@ -0,0 +1,505 @@
+use crate::admin::{
+    archive_location, archived_locations, delete_location, merge_location, rename_location,
+    restore_location, AdminError,
+};
+use crate::location::Location;
+use crate::state::TemperatureServerState;
+use actix_web::http::header::AUTHORIZATION;
+use actix_web::http::StatusCode;
+use actix_web::{
+    delete, get, post, web, HttpRequest, HttpResponse, HttpResponseBuilder, Responder,
+};
+use serde::Deserialize;
+use tracing::error;
+use utoipa::ToSchema;
+
+#[derive(Deserialize, ToSchema)]
+pub struct RenameRequest {
+    /// The new name of the location
+    to: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct MergeRequest {
+    /// The location that gets the readings
+    into: String,
+}
+
+/// Checks the request has the admin token, the response to send back instead if it doesn't.
+pub(crate) fn reject_without_admin_token(
+    req: &HttpRequest,
+    state: &TemperatureServerState,
+) -> Option<HttpResponse> {
+    let Some(token) = state.admin_token.as_deref() else {
+        return Some(
+            HttpResponseBuilder::new(StatusCode::FORBIDDEN)
+                .body("The admin api is turned off, set TEMP_SERVER_ADMIN_TOKEN to turn it on"),
+        );
+    };
+
+    let given = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    if given.is_some_and(|given| same_token(given.as_bytes(), token.as_bytes())) {
+        None
+    } else {
+        Some(
+            HttpResponseBuilder::new(StatusCode::UNAUTHORIZED).body("Missing or wrong admin token"),
+        )
+    }
+}
+
+/// Compares every byte whatever the first difference is, so how long a wrong guess takes to be
+/// turned down doesn't give away how much of it was right.
+fn same_token(given: &[u8], token: &[u8]) -> bool {
+    given.len() == token.len()
+        && given
+            .iter()
+            .zip(token)
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+fn admin_response(result: Result<(), AdminError>) -> HttpResponse {
+    match result {
+        Ok(()) => HttpResponseBuilder::new(StatusCode::NO_CONTENT).finish(),
+        Err(err @ AdminError::UnknownLocation(_)) => {
+            HttpResponseBuilder::new(StatusCode::NOT_FOUND).body(err.to_string())
+        }
+        Err(err @ AdminError::Conflict(_)) => {
+            HttpResponseBuilder::new(StatusCode::CONFLICT).body(err.to_string())
+        }
+        Err(err @ AdminError::InvalidName(_)) => {
+            HttpResponseBuilder::new(StatusCode::BAD_REQUEST).body(err.to_string())
+        }
+        Err(AdminError::Io(err)) => {
+            error!("Error changing locations: {}", err);
+            HttpResponseBuilder::new(StatusCode::INTERNAL_SERVER_ERROR).finish()
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "admin",
+    params(("location" = String, Path, description = "Location name")),
+    request_body = RenameRequest,
+    security(("admin_token" = [])),
+    responses(
+        (status = 204, description = "Location renamed, with its readings and metadata"),
+        (status = 400, description = "Invalid location name"),
+        (status = 401, description = "Missing or wrong admin token"),
+        (status = 403, description = "The admin api is turned off"),
+        (status = 404, description = "Unknown location"),
+        (status = 409, description = "A location with the new name already exists"),
+    )
+)]
+#[post("/api/v1/admin/locations/{location}/rename")]
+pub async fn rename_location_handler(
+    req: HttpRequest,
+    location: web::Path<String>,
+    body: web::Json<RenameRequest>,
+    state: web::Data<TemperatureServerState>,
+) -> impl Responder {
+    if let Some(response) = reject_without_admin_token(&req, &state) {
+        return response;
+    }
+
+    let from: Location = location.as_str().into();
+    let to: Location = body.into_inner().to.into();
+    admin_response(rename_location(&state, &from, &to).await)
+}
+
+#[utoipa::path(
+    tag = "admin",
+    params(("location" = String, Path, description = "Location whose readings are moved, it is deleted afterwards")),
+    request_body = MergeRequest,
+    security(("admin_token" = [])),
+    responses(
+        (status = 204, description = "Readings of both locations are now in one, in time order"),
+        (status = 400, description = "Invalid location name"),
+        (status = 401, description = "Missing or wrong admin token"),
+        (status = 403, description = "The admin api is turned off"),
+        (status = 404, description = "Unknown location"),
+        (status = 409, description = "Both locations are the same"),
+    )
+)]
+#[post("/api/v1/admin/locations/{location}/merge")]
+pub async fn merge_location_handler(
+    req: HttpRequest,
+    location: web::Path<String>,
+    body: web::Json<MergeRequest>,
+    state: web::Data<TemperatureServerState>,
+) -> impl Responder {
+    if let Some(response) = reject_without_admin_token(&req, &state) {
+        return response;
+    }
+
+    let from: Location = location.as_str().into();
+    let into: Location = body.into_inner().into.into();
+    admin_response(merge_location(&state, &from, &into).await)
+}
+
+#[utoipa::path(
+    tag = "admin",
+    params(("location" = String, Path, description = "Location name")),
+    security(("admin_token" = [])),
+    responses(
+        (status = 204, description = "Location hidden, its readings are kept to be restored"),
+        (status = 400, description = "Invalid location name"),
+        (status = 401, description = "Missing or wrong admin token"),
+        (status = 403, description = "The admin api is turned off"),
+        (status = 404, description = "Unknown location"),
+        (status = 409, description = "The location is already archived"),
+    )
+)]
+#[post("/api/v1/admin/locations/{location}/archive")]
+pub async fn archive_location_handler(
+    req: HttpRequest,
+    location: web::Path<String>,
+    state: web::Data<TemperatureServerState>,
+) -> impl Responder {
+    if let Some(response) = reject_without_admin_token(&req, &state) {
+        return response;
+    }
+
+    admin_response(archive_location(&state, &location.as_str().into()).await)
+}
+
+#[utoipa::path(
+    tag = "admin",
+    params(("location" = String, Path, description = "Archived location name")),
+    security(("admin_token" = [])),
+    responses(
+        (status = 204, description = "Location restored from the archive"),
+        (status = 400, description = "Invalid location name"),
+        (status = 401, description = "Missing or wrong admin token"),
+        (status = 403, description = "The admin api is turned off"),
+        (status = 404, description = "No archived location with that name"),
+        (status = 409, description = "The location has reported since it was archived"),
+    )
+)]
+#[post("/api/v1/admin/locations/{location}/restore")]
+pub async fn restore_location_handler(
+    req: HttpRequest,
+    location: web::Path<String>,
+    state: web::Data<TemperatureServerState>,
+) -> impl Responder {
+    if let Some(response) = reject_without_admin_token(&req, &state) {
+        return response;
+    }
+
+    admin_response(restore_location(&state, &location.as_str().into()).await)
+}
+
+#[utoipa::path(
+    tag = "admin",
+    params(("location" = String, Path, description = "Location name")),
+    security(("admin_token" = [])),
+    responses(
+        (status = 204, description = "Location deleted with all of its readings, or its archived copy if it is not in use"),
+        (status = 400, description = "Invalid location name"),
+        (status = 401, description = "Missing or wrong admin token"),
+        (status = 403, description = "The admin api is turned off"),
+        (status = 404, description = "Unknown location"),
+    )
+)]
+#[delete("/api/v1/admin/locations/{location}")]
+pub async fn delete_location_handler(
+    req: HttpRequest,
+    location: web::Path<String>,
+    state: web::Data<TemperatureServerState>,
+) -> impl Responder {
+    if let Some(response) = reject_without_admin_token(&req, &state) {
+        return response;
+    }
+
+    admin_response(delete_location(&state, &location.as_str().into()).await)
+}
+
+#[utoipa::path(
+    tag = "admin",
+    security(("admin_token" = [])),
+    responses(
+        (status = 200, description = "Names of the archived locations", body = Vec<Location>),
+        (status = 401, description = "Missing or wrong admin token"),
+        (status = 403, description = "The admin api is turned off"),
+    )
+)]
+#[get("/api/v1/admin/archive")]
+pub async fn archived_locations_handler(
+    req: HttpRequest,
+    state: web::Data<TemperatureServerState>,
+) -> impl Responder {
+    if let Some(response) = reject_without_admin_token(&req, &state) {
+        return response;
+    }
+
+    match archived_locations(&state).await {
+        Ok(locations) => HttpResponseBuilder::new(StatusCode::OK).json(locations),
+        Err(err) => {
+            error!("Error listing archived locations: {}", err);
+            HttpResponseBuilder::new(StatusCode::INTERNAL_SERVER_ERROR).finish()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::LocationMetadata;
+    use crate::test_support::{readings, TestFolder};
+    use actix_web::body::MessageBody;
+    use actix_web::dev::{ServiceFactory, ServiceRequest, ServiceResponse};
+    use actix_web::test::{call_and_read_body_json, call_service, init_service, TestRequest};
+    use actix_web::App;
+    use chrono::{Duration, Local};
+    use std::fs;
+
+    const TOKEN: &str = "secret";
+
+    fn app(
+        state: web::Data<TemperatureServerState>,
+    ) -> App<
+        impl ServiceFactory<
+            ServiceRequest,
+            Config = (),
+            Response = ServiceResponse<impl MessageBody>,
+            Error = actix_web::Error,
+            InitError = (),
+        >,
+    > {
+        App::new()
+            .app_data(state)
+            .service(rename_location_handler)
+            .service(merge_location_handler)
+            .service(archive_location_handler)
+            .service(restore_location_handler)
+            .service(delete_location_handler)
+            .service(archived_locations_handler)
+    }
+
+    fn admin(request: TestRequest, uri: &str) -> TestRequest {
+        request
+            .uri(uri)
+            .insert_header((AUTHORIZATION, format!("Bearer {}", TOKEN)))
+    }
+
+    fn locations(state: &TemperatureServerState) -> Vec<String> {
+        let mut locations = state
+            .file_buf_list
+            .try_lock()
+            .unwrap()
+            .keys()
+            .map(|location| location.to_string())
+            .collect::<Vec<_>>();
+        locations.sort();
+        locations
+    }
+
+    #[actix_web::test]
+    async fn locations_are_renamed_with_their_metadata() {
+        let folder = TestFolder::new("admin-rename");
+        folder.write_location("attic", &readings("attic", Local::now(), 3));
+        folder.write_location("garage", &readings("garage", Local::now(), 3));
+        let state = folder.admin_state(TOKEN);
+        let display_name = LocationMetadata {
+            display_name: Some("Attic".to_string()),
+            ..Default::default()
+        };
+        state
+            .metadata
+            .lock()
+            .await
+            .set(&"attic".into(), display_name.clone())
+            .await
+            .unwrap();
+        let app = init_service(app(state.clone())).await;
+        let rename = |from: &str, to: &str| {
+            admin(
+                TestRequest::post().set_json(serde_json::json!({ "to": to })),
+                &format!("/api/v1/admin/locations/{}/rename", from),
+            )
+            .to_request()
+        };
+
+        let resp = call_service(&app, rename("attic", "loft")).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        assert_eq!(locations(&state), ["garage", "loft"]);
+        assert!(!folder.location_path("attic").exists());
+        assert!(folder.location_path("loft").exists());
+        assert_eq!(
+            state.metadata.lock().await.get(&"loft".into()),
+            display_name
+        );
+
+        for (from, to, status) in [
+            ("loft", "garage", StatusCode::CONFLICT),
+            ("attic", "cellar", StatusCode::NOT_FOUND),
+            ("loft", ".hidden", StatusCode::BAD_REQUEST),
+        ] {
+            let resp = call_service(&app, rename(from, to)).await;
+            assert_eq!(resp.status(), status, "{} to {}", from, to);
+        }
+        assert_eq!(locations(&state), ["garage", "loft"]);
+    }
+
+    #[actix_web::test]
+    async fn merged_locations_keep_every_reading() {
+        let folder = TestFolder::new("admin-merge");
+        let now = Local::now();
+        folder.write_location("attic", &readings("attic", now, 3));
+        folder.write_location("loft", &readings("loft", now - Duration::seconds(30), 3));
+        let state = folder.admin_state(TOKEN);
+        let app = init_service(app(state.clone())).await;
+        let merge = |from: &str, into: &str| {
+            admin(
+                TestRequest::post().set_json(serde_json::json!({ "into": into })),
+                &format!("/api/v1/admin/locations/{}/merge", from),
+            )
+            .to_request()
+        };
+
+        let resp = call_service(&app, merge("loft", "loft")).await;
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+        let resp = call_service(&app, merge("loft", "attic")).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        assert_eq!(locations(&state), ["attic"]);
+        assert!(!folder.location_path("loft").exists());
+
+        let merged = state
+            .get_readings_between(&"attic".into(), None, None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(merged.len(), 6);
+        assert!(merged
+            .windows(2)
+            .all(|pair| pair[0].reading_time() < pair[1].reading_time()));
+
+        let resp = call_service(&app, merge("loft", "attic")).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn archived_locations_can_be_restored_or_deleted() {
+        let folder = TestFolder::new("admin-archive");
+        folder.write_location("attic", &readings("attic", Local::now(), 3));
+        folder.write_location("garage", &readings("garage", Local::now(), 3));
+        let state = folder.admin_state(TOKEN);
+        let app = init_service(app(state.clone())).await;
+        let post = |uri: &str| admin(TestRequest::post(), uri).to_request();
+        let archived = || {
+            call_and_read_body_json::<_, _, Vec<String>>(
+                &app,
+                admin(TestRequest::get(), "/api/v1/admin/archive").to_request(),
+            )
+        };
+        let archived_path = state.archive_folder().join("attic.csv");
+
+        let resp = call_service(&app, post("/api/v1/admin/locations/attic/archive")).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        assert_eq!(locations(&state), ["garage"]);
+        assert!(archived_path.exists());
+        assert_eq!(archived().await, ["attic"]);
+
+        let resp = call_service(&app, post("/api/v1/admin/locations/attic/restore")).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        assert_eq!(locations(&state), ["attic", "garage"]);
+        assert!(!archived_path.exists());
+        assert!(archived().await.is_empty());
+
+        let resp = call_service(&app, post("/api/v1/admin/locations/attic/restore")).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        // deleting an archived location deletes the archived copy
+        call_service(&app, post("/api/v1/admin/locations/attic/archive")).await;
+        let delete = |location: &str| {
+            admin(
+                TestRequest::delete(),
+                &format!("/api/v1/admin/locations/{}", location),
+            )
+            .to_request()
+        };
+        let resp = call_service(&app, delete("attic")).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        assert!(!archived_path.exists());
+
+        let resp = call_service(&app, delete("garage")).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        assert!(locations(&state).is_empty());
+        assert!(!folder.location_path("garage").exists());
+
+        let resp = call_service(&app, delete("garage")).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn names_leading_out_of_the_log_folder_are_rejected() {
+        let folder = TestFolder::new("admin-names");
+        folder.write_location("attic", &readings("attic", Local::now(), 3));
+        // the location would be this file if the name were used as it is
+        let victim = format!("victim-{}", std::process::id());
+        let victim_path = std::env::temp_dir().join(format!("{}.csv", victim));
+        fs::write(&victim_path, "Date,Time,Temperature,Humidity\n").unwrap();
+        let state = folder.admin_state(TOKEN);
+        let app = init_service(app(state.clone())).await;
+
+        // out of the log folder, and out of the archive folder in it
+        for name in [format!("..%2F{}", victim), format!("..%2F..%2F{}", victim)] {
+            let location = format!("/api/v1/admin/locations/{}", name);
+            for request in [
+                admin(TestRequest::delete(), &location),
+                admin(TestRequest::post(), &format!("{}/archive", location)),
+                admin(TestRequest::post(), &format!("{}/restore", location)),
+                admin(
+                    TestRequest::post().set_json(serde_json::json!({ "into": "attic" })),
+                    &format!("{}/merge", location),
+                ),
+                admin(
+                    TestRequest::post().set_json(serde_json::json!({ "to": "loft" })),
+                    &format!("{}/rename", location),
+                ),
+            ] {
+                let request = request.to_request();
+                let uri = request.uri().to_string();
+                let resp = call_service(&app, request).await;
+                assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{}", uri);
+            }
+        }
+
+        let exists = victim_path.exists();
+        let _ = fs::remove_file(&victim_path);
+        assert!(exists);
+        assert_eq!(locations(&state), ["attic"]);
+    }
+
+    #[actix_web::test]
+    async fn the_admin_api_needs_the_token() {
+        let folder = TestFolder::new("admin-token");
+        folder.write_location("attic", &readings("attic", Local::now(), 3));
+
+        let turned_on = init_service(app(folder.admin_state(TOKEN))).await;
+        let resp = call_service(
+            &turned_on,
+            TestRequest::delete()
+                .uri("/api/v1/admin/locations/attic")
+                .insert_header((AUTHORIZATION, "Bearer wrong"))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+        let turned_off = init_service(app(folder.state())).await;
+        let resp = call_service(
+            &turned_off,
+            admin(TestRequest::delete(), "/api/v1/admin/locations/attic").to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        assert!(folder.location_path("attic").exists());
+    }
+}
@@ -0,0 +1,365 @@
+use crate::metric::Metric;
+use crate::reading::Reading;
+use crate::time_range::parse_duration;
+use chrono::{DateTime, Duration, Local, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use utoipa::ToSchema;
+
+/// Width of a single aggregation bucket, parsed from strings like "30s", "5m", "1h", "1d" or "1w".
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+#[serde(try_from = "String")]
+pub struct BucketWidth(Duration);
+
+impl BucketWidth {
+    pub fn from_seconds(seconds: i64) -> Self {
+        Self(Duration::seconds(seconds.max(1)))
+    }
+
+    /// The smallest whole-minute bucket width that fits `span` into at most `max_buckets` buckets.
+    pub fn for_span(span: Duration, max_buckets: usize) -> Self {
+        let max_buckets = max_buckets.max(1) as i64;
+        let minutes = (span.num_seconds() + 60 * max_buckets - 1) / (60 * max_buckets);
+        Self::from_seconds(minutes.max(1) * 60)
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.0
+    }
+
+    pub fn num_seconds(&self) -> i64 {
+        self.0.num_seconds()
+    }
+
+    /// The start of the bucket that `time` falls in. Buckets are aligned on local wall clock time,
+    /// so daily buckets start at local midnight.
+    pub fn bucket_start(&self, time: DateTime<Local>) -> DateTime<Local> {
+        let local_seconds = time.naive_local().and_utc().timestamp();
+        let start = local_seconds - local_seconds.rem_euclid(self.num_seconds());
+
+        DateTime::from_timestamp(start, 0)
+            .and_then(|start| Local.from_local_datetime(&start.naive_utc()).earliest())
+            .unwrap_or(time)
+    }
+
+    /// The end of the bucket starting at `start`, which is the start of the next one.
+    pub fn bucket_end(&self, start: DateTime<Local>) -> DateTime<Local> {
+        // step one and a half buckets and snap back, so days that are 23 or 25 hours long
+        // because of daylight savings still end on the next midnight
+        let next = self.bucket_start(start + self.0 + self.0 / 2);
+        if next > start {
+            next
+        } else {
+            start + self.0
+        }
+    }
+
+    /// Every bucket start from the bucket containing `from` up to `to`, used to line several
+    /// series up on the same time axis.
+    pub fn grid(&self, from: DateTime<Local>, to: DateTime<Local>) -> Vec<DateTime<Local>> {
+        let mut grid = vec![];
+        let mut current = self.bucket_start(from);
+
+        while current < to {
+            grid.push(current);
+            current = self.bucket_end(current);
+        }
+
+        grid
+    }
+}
+
+impl FromStr for BucketWidth {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_duration(s).map(Self)
+    }
+}
+
+impl TryFrom<String> for BucketWidth {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl Display for BucketWidth {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}s", self.num_seconds())
+    }
+}
+
+#[derive(Clone, Copy, Hash, Eq, PartialEq, Ord, PartialOrd, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Aggregate {
+    Min,
+    Max,
+    Mean,
+    Median,
+    StdDev,
+    Count,
+}
+
+impl Aggregate {
+    pub const ALL: [Aggregate; 6] = [
+        Aggregate::Min,
+        Aggregate::Max,
+        Aggregate::Mean,
+        Aggregate::Median,
+        Aggregate::StdDev,
+        Aggregate::Count,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Aggregate::Min => "min",
+            Aggregate::Max => "max",
+            Aggregate::Mean => "mean",
+            Aggregate::Median => "median",
+            Aggregate::StdDev => "std_dev",
+            Aggregate::Count => "count",
+        }
+    }
+
+    /// Parses a comma separated list of aggregates such as "min,max,mean"
+    pub fn parse_list(list: &str) -> Result<Vec<Aggregate>, String> {
+        list.split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(Aggregate::from_str)
+            .collect()
+    }
+
+    /// Computes this aggregate over `values`, which must not be empty.
+    fn compute(&self, values: &[f64]) -> f64 {
+        let count = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / count;
+
+        match self {
+            Aggregate::Min => values.iter().copied().fold(f64::INFINITY, f64::min),
+            Aggregate::Max => values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            Aggregate::Mean => mean,
+            Aggregate::Median => {
+                let mut sorted = values.to_vec();
+                sorted.sort_by(|a, b| a.total_cmp(b));
+                let middle = sorted.len() / 2;
+                if sorted.len().is_multiple_of(2) {
+                    (sorted[middle - 1] + sorted[middle]) / 2.0
+                } else {
+                    sorted[middle]
+                }
+            }
+            Aggregate::StdDev => {
+                let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / count;
+                variance.sqrt()
+            }
+            Aggregate::Count => count,
+        }
+    }
+}
+
+impl FromStr for Aggregate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            // accept the more common spelling as well
+            "stddev" => Ok(Aggregate::StdDev),
+            _ => Aggregate::ALL
+                .into_iter()
+                .find(|aggregate| aggregate.as_str() == s)
+                .ok_or_else(|| format!("Unknown aggregate: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AggregateRow {
+    pub bucket_start: DateTime<Local>,
+    pub bucket_end: DateTime<Local>,
+    pub count: usize,
+    /// Each requested aggregate, keyed by metric then aggregate
+    #[schema(value_type = BTreeMap<String, BTreeMap<String, f64>>)]
+    pub values: BTreeMap<Metric, BTreeMap<Aggregate, f64>>,
+}
+
+impl AggregateRow {
+    pub fn get(&self, metric: Metric, aggregate: Aggregate) -> Option<f64> {
+        self.values
+            .get(&metric)
+            .and_then(|aggregates| aggregates.get(&aggregate))
+            .copied()
+    }
+}
+
+/// Looks up one aggregate for every time in `grid`, None where a bucket had no readings.
+pub fn align_to_grid(
+    rows: &[AggregateRow],
+    grid: &[DateTime<Local>],
+    metric: Metric,
+    aggregate: Aggregate,
+) -> Vec<Option<f64>> {
+    let by_start = rows
+        .iter()
+        .map(|row| (row.bucket_start, row))
+        .collect::<BTreeMap<_, _>>();
+
+    grid.iter()
+        .map(|time| by_start.get(time).and_then(|row| row.get(metric, aggregate)))
+        .collect()
+}
+
+/// Groups `readings` into buckets of `width` and computes each aggregate for each metric.
+/// Only buckets that contain at least one reading produce a row, rows are in time order.
+pub fn aggregate(
+    readings: &[Reading],
+    width: BucketWidth,
+    metrics: &[Metric],
+    aggregates: &[Aggregate],
+) -> Vec<AggregateRow> {
+    let mut buckets: BTreeMap<DateTime<Local>, Vec<&Reading>> = BTreeMap::new();
+
+    for reading in readings {
+        buckets
+            .entry(width.bucket_start(reading.reading_time()))
+            .or_default()
+            .push(reading);
+    }
+
+    buckets
+        .into_iter()
+        .map(|(bucket_start, bucket)| {
+            let values = metrics
+                .iter()
+                .map(|metric| {
+                    let metric_values = bucket
+                        .iter()
+                        .map(|reading| metric.value(reading) as f64)
+                        .collect::<Vec<f64>>();
+
+                    let computed = aggregates
+                        .iter()
+                        .map(|aggregate| (*aggregate, aggregate.compute(&metric_values)))
+                        .collect();
+
+                    (*metric, computed)
+                })
+                .collect();
+
+            AggregateRow {
+                bucket_start,
+                bucket_end: width.bucket_end(bucket_start),
+                count: bucket.len(),
+                values,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(hour: u32, minute: u32, second: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(2025, 1, 15, hour, minute, second)
+            .unwrap()
+    }
+
+    #[test]
+    fn median_takes_the_middle_value() {
+        assert_eq!(Aggregate::Median.compute(&[5.0, 1.0, 3.0]), 3.0);
+        assert_eq!(Aggregate::Median.compute(&[4.0, 1.0, 3.0, 2.0]), 2.5);
+        assert_eq!(Aggregate::Median.compute(&[7.0]), 7.0);
+    }
+
+    #[test]
+    fn std_dev_is_the_population_deviation() {
+        let values = [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0];
+        assert_eq!(Aggregate::Mean.compute(&values), 5.0);
+        assert_eq!(Aggregate::StdDev.compute(&values), 2.0);
+        assert_eq!(Aggregate::StdDev.compute(&[3.0]), 0.0);
+        assert_eq!(Aggregate::Count.compute(&values), 8.0);
+    }
+
+    #[test]
+    fn buckets_start_on_wall_clock_boundaries() {
+        let five_minutes: BucketWidth = "5m".parse().unwrap();
+        assert_eq!(five_minutes.bucket_start(time(13, 7, 42)), time(13, 5, 0));
+        assert_eq!(five_minutes.bucket_start(time(13, 5, 0)), time(13, 5, 0));
+
+        let hour: BucketWidth = "1h".parse().unwrap();
+        assert_eq!(hour.bucket_start(time(13, 59, 59)), time(13, 0, 0));
+
+        let day: BucketWidth = "1d".parse().unwrap();
+        assert_eq!(day.bucket_start(time(13, 7, 42)), time(0, 0, 0));
+    }
+
+    #[test]
+    fn daily_buckets_end_at_the_next_midnight() {
+        let day: BucketWidth = "1d".parse().unwrap();
+        let midnight = |month: u32, day: u32| {
+            Local
+                .with_ymd_and_hms(2025, month, day, 0, 0, 0)
+                .earliest()
+                .unwrap()
+        };
+
+        // every day of the year, including the ones daylight savings makes 23 or 25 hours long
+        let grid = day.grid(midnight(1, 1), midnight(12, 31));
+        assert_eq!(grid.len(), 364);
+        for (start, next) in grid.iter().zip(grid.iter().skip(1)) {
+            assert_eq!(day.bucket_end(*start), *next);
+            assert_eq!(next.time(), midnight(1, 1).time());
+        }
+        assert_eq!(day.bucket_end(midnight(12, 30)), midnight(12, 31));
+    }
+
+    #[test]
+    fn span_picks_the_smallest_whole_minute_width() {
+        // a day in at most 1000 buckets needs 1.44 minute buckets, rounded up to 2
+        assert_eq!(
+            BucketWidth::for_span(Duration::days(1), 1000).num_seconds(),
+            120
+        );
+        assert_eq!(
+            BucketWidth::for_span(Duration::hours(1), 1000).num_seconds(),
+            60
+        );
+        assert_eq!(
+            BucketWidth::for_span(Duration::days(7), 168).num_seconds(),
+            3600
+        );
+        assert_eq!(BucketWidth::for_span(Duration::zero(), 0).num_seconds(), 60);
+    }
+
+    #[test]
+    fn readings_are_grouped_into_their_buckets() {
+        let readings =
+            [(0, 60.0), (4, 62.0), (5, 70.0), (14, 80.0)].map(|(minute, temperature)| {
+                Reading::new("test".into(), temperature, 50.0, time(12, minute, 0))
+            });
+        let rows = aggregate(
+            &readings,
+            "5m".parse().unwrap(),
+            &[Metric::Temperature],
+            &[Aggregate::Mean, Aggregate::Max],
+        );
+
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0].bucket_start, time(12, 0, 0));
+        assert_eq!(rows[0].bucket_end, time(12, 5, 0));
+        assert_eq!(rows[0].count, 2);
+        assert_eq!(
+            rows[0].get(Metric::Temperature, Aggregate::Mean),
+            Some(61.0)
+        );
+        assert_eq!(rows[2].bucket_start, time(12, 10, 0));
+        assert_eq!(rows[2].get(Metric::Temperature, Aggregate::Max), Some(80.0));
+        assert_eq!(rows[2].get(Metric::Humidity, Aggregate::Max), None);
+    }
+}
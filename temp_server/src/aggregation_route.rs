@@ -0,0 +1,94 @@
+use crate::aggregation::{aggregate, Aggregate, AggregateRow, BucketWidth};
+use crate::location::Location;
+use crate::metric::Metric;
+use crate::state::TemperatureServerState;
+use actix_web::http::StatusCode;
+use actix_web::{get, web, HttpResponseBuilder, Responder};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use utoipa::{IntoParams, ToSchema};
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AggregateQuery {
+    /// Start of the range, inclusive
+    from: Option<DateTime<Local>>,
+    /// End of the range, exclusive
+    to: Option<DateTime<Local>>,
+    /// Bucket width such as 5m, 1h or 1d
+    #[param(value_type = String, example = "1h")]
+    bucket: BucketWidth,
+    /// Comma separated aggregates, defaults to all of them
+    #[param(example = "min,max,mean")]
+    aggregates: Option<String>,
+    /// Comma separated metrics, defaults to temperature and humidity
+    #[param(example = "temperature,humidity")]
+    metrics: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AggregateResponse {
+    location: Location,
+    bucket_seconds: i64,
+    rows: Vec<AggregateRow>,
+}
+
+#[utoipa::path(
+    tag = "statistics",
+    params(
+        ("location" = String, Path, description = "Location name"),
+        AggregateQuery,
+    ),
+    responses(
+        (status = 200, description = "One row per bucket that has readings", body = AggregateResponse),
+        (status = 400, description = "Unknown aggregate, metric or bucket width"),
+        (status = 404, description = "Unknown location"),
+    )
+)]
+#[get("/api/v1/locations/{location}/aggregate")]
+pub async fn aggregate_handler(
+    location: web::Path<String>,
+    query: web::Query<AggregateQuery>,
+    state: web::Data<TemperatureServerState>,
+) -> impl Responder {
+    let location: Location = location.as_str().into();
+    let query = query.into_inner();
+
+    let aggregates = match query.aggregates.as_deref().map(Aggregate::parse_list) {
+        None => Ok(Aggregate::ALL.to_vec()),
+        Some(list) => list,
+    };
+    let metrics = match query.metrics.as_deref().map(Metric::parse_list) {
+        None => Ok(Metric::MEASURED.to_vec()),
+        Some(list) => list,
+    };
+
+    let (aggregates, metrics) = match (aggregates, metrics) {
+        (Ok(aggregates), Ok(metrics)) => (aggregates, metrics),
+        (Err(err), _) | (_, Err(err)) => {
+            return HttpResponseBuilder::new(StatusCode::BAD_REQUEST).body(err);
+        }
+    };
+
+    let readings = match state
+        .get_readings_between(&location, query.from, query.to)
+        .await
+    {
+        Ok(Some(readings)) => readings,
+        Ok(None) => {
+            return HttpResponseBuilder::new(StatusCode::NOT_FOUND)
+                .body(format!("Unknown location: {}", location));
+        }
+        Err(err) => {
+            error!("Error reading location {}: {}", location, err);
+            return HttpResponseBuilder::new(StatusCode::INTERNAL_SERVER_ERROR).finish();
+        }
+    };
+
+    HttpResponseBuilder::new(StatusCode::OK).json(AggregateResponse {
+        location,
+        bucket_seconds: query.bucket.num_seconds(),
+        rows: aggregate(&readings, query.bucket, &metrics, &aggregates),
+    })
+}
@@ -0,0 +1,502 @@
+use crate::location::Location;
+use crate::metric::Metric;
+use crate::reading::Reading;
+use crate::rules::Rule;
+use chrono::{DateTime, Duration, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::path::PathBuf;
+use tokio::io::AsyncWriteExt;
+use tracing::{error, info, warn};
+use utoipa::ToSchema;
+
+// How many of the latest alert events are kept in memory to be listed
+const ALERT_HISTORY_CAPACITY: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertState {
+    Firing,
+    Resolved,
+}
+
+/// A rule starting or stopping firing for a location.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct AlertEvent {
+    pub rule: String,
+    pub location: Location,
+    pub state: AlertState,
+    pub time: DateTime<Local>,
+    pub metric: Metric,
+    /// The value of the reading that changed the state, in the unit of the rule
+    pub value: f32,
+    pub message: String,
+}
+
+/// Where a rule is at for one location.
+#[derive(Debug, Default)]
+struct RuleState {
+    // when the condition started holding, in the current stretch of it holding
+    pending_since: Option<DateTime<Local>>,
+    // the event that started the alert, while it is firing
+    firing: Option<AlertEvent>,
+}
+
+/// Evaluates the alert rules against readings as they arrive. Every event is appended to a
+/// json lines file, which is replayed at startup so firing alerts stay firing across restarts.
+pub struct AlertEngine {
+    rules: Vec<Rule>,
+    log_path: PathBuf,
+    // keyed by rule name and location
+    states: HashMap<(String, Location), RuleState>,
+    // oldest first
+    history: VecDeque<AlertEvent>,
+    // the latest readings of each location, oldest first, for rules that look back at them
+    recent: HashMap<Location, VecDeque<Reading>>,
+    // how far back any rule looks
+    window: Duration,
+}
+
+impl AlertEngine {
+    pub fn load(rules: Vec<Rule>, log_path: PathBuf) -> Self {
+        let mut engine = Self {
+            window: rules
+                .iter()
+                .map(|rule| rule.condition.window())
+                .max()
+                .unwrap_or_else(Duration::zero),
+            rules,
+            log_path,
+            states: HashMap::new(),
+            history: VecDeque::new(),
+            recent: HashMap::new(),
+        };
+
+        let contents = match fs::read_to_string(&engine.log_path) {
+            Ok(contents) => contents,
+            Err(_) => return engine,
+        };
+
+        for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+            match serde_json::from_str::<AlertEvent>(line) {
+                Ok(event) => engine.remember(event),
+                Err(err) => warn!("Bad line in {}: {}", engine.log_path.display(), err),
+            }
+        }
+
+        // alerts of rules that have since been removed from the config can never resolve
+        let rules = &engine.rules;
+        engine
+            .states
+            .retain(|(rule, _), _| rules.iter().any(|known| known.name == *rule));
+
+        info!(
+            "Loaded {} alert events, {} firing",
+            engine.history.len(),
+            engine.firing().len()
+        );
+        engine
+    }
+
+    fn remember(&mut self, event: AlertEvent) {
+        let state = self
+            .states
+            .entry((event.rule.clone(), event.location.clone()))
+            .or_default();
+        state.firing = (event.state == AlertState::Firing).then(|| event.clone());
+
+        self.history.push_back(event);
+        while self.history.len() > ALERT_HISTORY_CAPACITY {
+            self.history.pop_front();
+        }
+    }
+
+    /// How far back the rules look at earlier readings, None when none of them do.
+    pub fn window(&self) -> Option<Duration> {
+        (self.window > Duration::zero()).then_some(self.window)
+    }
+
+    /// Keeps a reading for the rules that look back at earlier readings, dropping those that are
+    /// too old for any rule. One reading from before the window is kept, so a rule can tell the
+    /// window is covered.
+    pub fn remember_reading(&mut self, reading: Reading) {
+        if self.window().is_none() {
+            return;
+        }
+
+        let since = reading.reading_time() - self.window;
+        let recent = self.recent.entry(reading.location()).or_default();
+        recent.push_back(reading);
+        while recent
+            .get(1)
+            .is_some_and(|reading| reading.reading_time() <= since)
+        {
+            recent.pop_front();
+        }
+    }
+
+    /// The event that started each alert that is firing now, oldest first.
+    pub fn firing(&self) -> Vec<AlertEvent> {
+        let mut firing = self
+            .states
+            .values()
+            .filter_map(|state| state.firing.clone())
+            .collect::<Vec<_>>();
+        firing.sort_by_key(|event| event.time);
+        firing
+    }
+
+    /// The latest alert events, newest first.
+    pub fn history(&self, limit: usize) -> Vec<AlertEvent> {
+        self.history.iter().rev().take(limit).cloned().collect()
+    }
+
+    /// Checks every rule that applies to the location of a new reading, appending the alerts that
+    /// started or stopped firing because of it to the alert log.
+    pub async fn process(&mut self, reading: &Reading, group: Option<&str>) -> Vec<AlertEvent> {
+        let events = self.evaluate(reading, group);
+        self.log(events).await
+    }
+
+    /// Resolves every alert of a location that is going away, `reason` says where to,
+    /// such as "archived". The resolved alerts are appended to the alert log.
+    pub async fn remove_location(
+        &mut self,
+        location: &Location,
+        reason: &str,
+    ) -> Vec<AlertEvent> {
+        let events = self.remove(location, reason, Local::now());
+        self.log(events).await
+    }
+
+    /// Moves the alerts of a location to its new name, see [`AlertEngine::rename`].
+    pub async fn rename_location(
+        &mut self,
+        from: &Location,
+        to: &Location,
+        group: Option<&str>,
+    ) -> Vec<AlertEvent> {
+        let events = self.rename(from, to, group, Local::now());
+        self.log(events).await
+    }
+
+    async fn log(&self, events: Vec<AlertEvent>) -> Vec<AlertEvent> {
+        if let Err(err) = self.append_to_log(&events).await {
+            error!(
+                "Error writing alert log {}: {}",
+                self.log_path.display(),
+                err
+            );
+        }
+        events
+    }
+
+    fn remove(
+        &mut self,
+        location: &Location,
+        reason: &str,
+        time: DateTime<Local>,
+    ) -> Vec<AlertEvent> {
+        let events = self
+            .states
+            .iter()
+            .filter(|((_, state_location), _)| state_location == location)
+            .filter_map(|(_, state)| state.firing.as_ref())
+            .map(|firing| AlertEvent {
+                state: AlertState::Resolved,
+                time,
+                message: format!("{}: resolved, {} was {}", firing.rule, location, reason),
+                ..firing.clone()
+            })
+            .collect::<Vec<_>>();
+
+        for event in &events {
+            info!("Alert {:?}: {}", event.state, event.message);
+            self.remember(event.clone());
+        }
+        self.states.retain(|(_, state_location), _| state_location != location);
+        self.recent.remove(location);
+
+        events
+    }
+
+    /// Alerts firing under the old name are resolved there and fire again under the new name,
+    /// keeping when they started. Those of rules that don't cover the new name just resolve.
+    fn rename(
+        &mut self,
+        from: &Location,
+        to: &Location,
+        group: Option<&str>,
+        time: DateTime<Local>,
+    ) -> Vec<AlertEvent> {
+        let rules = self
+            .states
+            .keys()
+            .filter(|(_, location)| location == from)
+            .map(|(rule, _)| rule.clone())
+            .collect::<Vec<_>>();
+
+        let mut events = vec![];
+        let mut moved = vec![];
+        for rule in rules {
+            let Some(state) = self.states.remove(&(rule.clone(), from.clone())) else {
+                continue;
+            };
+            let applies = self
+                .rules
+                .iter()
+                .any(|known| known.name == rule && known.applies_to(to, group));
+
+            if let Some(firing) = state.firing {
+                events.push(AlertEvent {
+                    state: AlertState::Resolved,
+                    time,
+                    message: format!("{}: resolved, {} was renamed to {}", rule, from, to),
+                    ..firing.clone()
+                });
+                if applies {
+                    events.push(AlertEvent {
+                        location: to.clone(),
+                        message: format!("{}: still firing, {} was renamed to {}", rule, from, to),
+                        ..firing
+                    });
+                }
+            }
+            if applies {
+                moved.push((rule, state.pending_since));
+            }
+        }
+
+        for event in &events {
+            info!("Alert {:?}: {}", event.state, event.message);
+            self.remember(event.clone());
+        }
+        self.states.retain(|(_, location), _| location != from);
+        for (rule, pending_since) in moved {
+            self.states
+                .entry((rule, to.clone()))
+                .or_default()
+                .pending_since = pending_since;
+        }
+        if let Some(recent) = self.recent.remove(from) {
+            self.recent.insert(to.clone(), recent);
+        }
+
+        events
+    }
+
+    /// Checks every rule that applies to the location of a new reading, returning the alerts that
+    /// started or stopped firing because of it.
+    fn evaluate(&mut self, reading: &Reading, group: Option<&str>) -> Vec<AlertEvent> {
+        let location = reading.location();
+        let time = reading.reading_time();
+        let mut events = vec![];
+
+        for rule in self
+            .rules
+            .iter()
+            .filter(|rule| rule.applies_to(&location, group))
+        {
+            let value = rule.convert(rule.metric.value(reading));
+            let unit = rule.unit_symbol();
+            let recent = self
+                .recent
+                .get(&location)
+                .into_iter()
+                .flatten()
+                .map(|earlier| {
+                    (
+                        earlier.reading_time(),
+                        rule.convert(rule.metric.value(earlier)),
+                    )
+                })
+                .collect::<Vec<_>>();
+            let state = self
+                .states
+                .entry((rule.name.clone(), location.clone()))
+                .or_default();
+
+            let event = |state: AlertState, message: String| AlertEvent {
+                rule: rule.name.clone(),
+                location: location.clone(),
+                state,
+                time,
+                metric: rule.metric,
+                value,
+                message,
+            };
+
+            if rule.condition.matches(value, time, &recent) {
+                let since = *state.pending_since.get_or_insert(time);
+                if state.firing.is_none() && time - since >= rule.hold_for() {
+                    events.push(event(
+                        AlertState::Firing,
+                        format!(
+                            "{}: {} at {} is {:.1} {}, {}",
+                            rule.name,
+                            rule.metric.display_name(),
+                            location,
+                            value,
+                            unit,
+                            rule.condition.describe(unit)
+                        ),
+                    ));
+                }
+            } else {
+                state.pending_since = None;
+                if state.firing.is_some() {
+                    events.push(event(
+                        AlertState::Resolved,
+                        format!(
+                            "{}: resolved, {} at {} is {:.1} {}",
+                            rule.name,
+                            rule.metric.display_name(),
+                            location,
+                            value,
+                            unit
+                        ),
+                    ));
+                }
+            }
+        }
+
+        for event in &events {
+            info!("Alert {:?}: {}", event.state, event.message);
+            self.remember(event.clone());
+        }
+        self.remember_reading(reading.clone());
+
+        events
+    }
+
+    async fn append_to_log(&self, events: &[AlertEvent]) -> std::io::Result<()> {
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        let mut lines = String::new();
+        for event in events {
+            lines.push_str(&serde_json::to_string(event)?);
+            lines.push('\n');
+        }
+
+        tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.log_path)
+            .await?
+            .write_all(lines.as_bytes())
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alerts_fire_after_holding_and_resolve() {
+        let rule: Rule = toml::from_str(
+            "name = 'humid'\nmetric = 'humidity'\ncondition = { above = 60.0 }\nfor_minutes = 5",
+        )
+        .unwrap();
+        let log_path = std::env::temp_dir().join(format!("alerts-{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&log_path);
+        let mut engine = AlertEngine::load(vec![rule.clone()], log_path.clone());
+
+        let start = Local::now();
+        let reading = |minutes: i64, humidity: f32| {
+            Reading::new(
+                "closet".into(),
+                70.0,
+                humidity,
+                start + Duration::minutes(minutes),
+            )
+        };
+
+        assert!(engine.evaluate(&reading(0, 65.0), None).is_empty());
+        assert!(engine.evaluate(&reading(4, 65.0), None).is_empty());
+        let fired = engine.evaluate(&reading(5, 65.0), None);
+        assert_eq!(fired.len(), 1);
+        assert_eq!(fired[0].state, AlertState::Firing);
+        assert!(engine.evaluate(&reading(6, 66.0), None).is_empty());
+
+        // a restart keeps the alert firing
+        fs::write(&log_path, serde_json::to_string(&fired[0]).unwrap() + "\n").unwrap();
+        let mut engine = AlertEngine::load(vec![rule], log_path.clone());
+        assert_eq!(engine.firing().len(), 1);
+        let resolved = engine.evaluate(&reading(7, 50.0), None);
+        assert_eq!(resolved[0].state, AlertState::Resolved);
+        assert!(engine.firing().is_empty());
+        assert_eq!(engine.history(10).len(), 2);
+
+        let _ = fs::remove_file(&log_path);
+    }
+
+    #[test]
+    fn alerts_follow_their_location_when_it_is_renamed_or_removed() {
+        let rules: Vec<Rule> = [("closet", "'closet', 'storage'"), ("hall", "'hall'")]
+            .iter()
+            .map(|(name, locations)| {
+                toml::from_str(&format!(
+                    "name = 'humid {}'\nlocations = [{}]\nmetric = 'humidity'\ncondition = {{ above = 60.0 }}",
+                    name, locations
+                ))
+                .unwrap()
+            })
+            .collect();
+        let log_path =
+            std::env::temp_dir().join(format!("alerts-admin-{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&log_path);
+        let mut engine = AlertEngine::load(rules, log_path.clone());
+
+        let start = Local::now();
+        let reading = |location: &str, minutes: i64, humidity: f32| {
+            Reading::new(
+                location.into(),
+                70.0,
+                humidity,
+                start + Duration::minutes(minutes),
+            )
+        };
+
+        let fired = engine.evaluate(&reading("closet", 0, 65.0), None);
+        assert_eq!(fired.len(), 1);
+
+        // the closet rule covers storage too, so the alert moves over and keeps its start
+        let moved = engine.rename(&"closet".into(), &"storage".into(), None, start);
+        assert_eq!(moved.len(), 2);
+        assert_eq!(moved[0].state, AlertState::Resolved);
+        assert_eq!(moved[0].location, "closet".into());
+        let firing = engine.firing();
+        assert_eq!(firing.len(), 1);
+        assert_eq!(firing[0].location, "storage".into());
+        assert_eq!(firing[0].time, fired[0].time);
+        assert!(engine
+            .evaluate(&reading("storage", 1, 66.0), None)
+            .is_empty());
+        let resolved = engine.evaluate(&reading("storage", 2, 50.0), None);
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(resolved[0].state, AlertState::Resolved);
+
+        // no rule covers the attic, so the alert just resolves
+        engine.evaluate(&reading("hall", 3, 65.0), None);
+        let moved = engine.rename(&"hall".into(), &"attic".into(), None, start);
+        assert_eq!(moved.len(), 1);
+        assert_eq!(moved[0].state, AlertState::Resolved);
+        assert!(engine.firing().is_empty());
+
+        engine.evaluate(&reading("storage", 4, 65.0), None);
+        let removed = engine.remove(&"storage".into(), "archived", start);
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].state, AlertState::Resolved);
+        assert!(removed[0].message.contains("storage was archived"));
+        assert!(engine.firing().is_empty());
+        assert!(engine
+            .remove(&"storage".into(), "deleted", start)
+            .is_empty());
+
+        let _ = fs::remove_file(&log_path);
+    }
+}
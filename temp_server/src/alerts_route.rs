@@ -0,0 +1,44 @@
+use crate::alerts::AlertEvent;
+use crate::state::TemperatureServerState;
+use actix_web::http::StatusCode;
+use actix_web::{get, web, HttpResponseBuilder, Responder};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+// How many events are listed when the request does not say
+const DEFAULT_EVENT_LIMIT: usize = 50;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AlertsQuery {
+    /// How many of the latest events to list, defaults to 50
+    limit: Option<usize>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AlertsResponse {
+    /// The event that started each alert that is firing now, oldest first
+    firing: Vec<AlertEvent>,
+    /// The latest alert events, newest first
+    events: Vec<AlertEvent>,
+}
+
+#[utoipa::path(
+    tag = "alerts",
+    params(AlertsQuery),
+    responses(
+        (status = 200, description = "Alerts firing now and the latest alert events", body = AlertsResponse),
+    )
+)]
+#[get("/api/v1/alerts")]
+pub async fn alerts_handler(
+    query: web::Query<AlertsQuery>,
+    state: web::Data<TemperatureServerState>,
+) -> impl Responder {
+    let alerts = state.alerts.lock().await;
+
+    HttpResponseBuilder::new(StatusCode::OK).json(AlertsResponse {
+        firing: alerts.firing(),
+        events: alerts.history(query.limit.unwrap_or(DEFAULT_EVENT_LIMIT)),
+    })
+}
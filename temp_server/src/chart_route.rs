@@ -0,0 +1,132 @@
+use crate::location::Location;
+use crate::page::render_page;
+use crate::state::TemperatureServerState;
+use actix_web::http::StatusCode;
+use actix_web::{get, web, HttpResponseBuilder, Responder};
+use askama::Template;
+
+// The chart itself is drawn by /static/chart.js, which reads the location from the url
+#[derive(Template)]
+#[template(path = "chart.html")]
+struct ChartTemplate<'a> {
+    location: &'a str,
+}
+
+#[utoipa::path(
+    tag = "pages",
+    params(("location" = String, Path, description = "Location name")),
+    responses(
+        (status = 200, description = "Interactive chart of the location, drawn in the browser from the aggregate api", content_type = "text/html", body = String),
+        (status = 404, description = "Unknown location"),
+    )
+)]
+#[get("/location/{location}/chart")]
+pub async fn chart_page_handler(
+    location: web::Path<String>,
+    state: web::Data<TemperatureServerState>,
+) -> impl Responder {
+    let location: Location = location.as_str().into();
+
+    if !state.file_buf_list.lock().await.contains_key(&location) {
+        return HttpResponseBuilder::new(StatusCode::NOT_FOUND)
+            .body(format!("Unknown location: {}", location));
+    }
+
+    render_page(&ChartTemplate {
+        location: location.as_str(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aggregation_route::aggregate_handler;
+    use crate::static_route::static_file_handler;
+    use crate::test_support::{readings, TestFolder};
+    use actix_web::http::header;
+    use actix_web::test::{
+        call_and_read_body_json, call_service, init_service, read_body, TestRequest,
+    };
+    use actix_web::App;
+    use chrono::{Duration, Local};
+
+    #[actix_web::test]
+    async fn chart_page_loads_its_script_and_data() {
+        let folder = TestFolder::new("chart-page");
+        let now = Local::now();
+        folder.write_location("living room", &readings("living room", now, 10));
+        let app = init_service(
+            App::new()
+                .app_data(folder.state())
+                .service(chart_page_handler)
+                .service(static_file_handler)
+                .service(aggregate_handler),
+        )
+        .await;
+
+        let resp = call_service(
+            &app,
+            TestRequest::get()
+                .uri("/location/living%20room/chart")
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let page = read_body(resp).await;
+        let page = std::str::from_utf8(&page).unwrap();
+        assert!(page.contains(r#"<canvas id="chart">"#));
+        assert!(page.contains(r#"<script src="/static/chart.js">"#));
+        assert!(page.contains(r#"href="/plot/living%20room""#));
+
+        let resp = call_service(
+            &app,
+            TestRequest::get().uri("/location/attic/chart").to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        // the script is served from the binary, no cdn involved
+        let resp = call_service(
+            &app,
+            TestRequest::get().uri("/static/chart.js").to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/javascript; charset=utf-8"
+        );
+        let script = read_body(resp).await;
+        assert!(std::str::from_utf8(&script)
+            .unwrap()
+            .contains("/aggregate?"));
+        let resp = call_service(
+            &app,
+            TestRequest::get().uri("/static/chart.ts").to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        // the request the script makes for the data it draws
+        let from = (now - Duration::hours(1))
+            .to_utc()
+            .format("%Y-%m-%dT%H:%M:%S%.3fZ");
+        let to = (now + Duration::hours(1))
+            .to_utc()
+            .format("%Y-%m-%dT%H:%M:%S%.3fZ");
+        let body: serde_json::Value = call_and_read_body_json(
+            &app,
+            TestRequest::get()
+                .uri(&format!(
+                    "/api/v1/locations/living%20room/aggregate?from={}&to={}&bucket=60s&aggregates=mean&metrics=temperature,humidity,dew_point",
+                    from, to
+                ))
+                .to_request(),
+        )
+        .await;
+        let rows = body["rows"].as_array().unwrap();
+        assert_eq!(rows.len(), 10);
+        assert_eq!(rows[0]["values"]["temperature"]["mean"], 60.0);
+        assert_eq!(rows[0]["values"]["humidity"]["mean"], 50.0);
+    }
+}
@@ -0,0 +1,515 @@
+use crate::aggregation::{aggregate, align_to_grid, Aggregate, BucketWidth};
+use crate::location::Location;
+use crate::metric::Metric;
+use crate::plot::{render, Drawable, PlotFormat, PlotSize};
+use crate::plot_cache::ComparisonKey;
+use crate::plotting_route::{split_on_gaps, time_label_format};
+use crate::state::TemperatureServerState;
+use crate::time_range::resolve_range;
+use actix_web::http::header::{ACCEPT, VARY};
+use actix_web::http::StatusCode;
+use actix_web::web::Bytes;
+use actix_web::{get, web, HttpRequest, HttpResponse, HttpResponseBuilder, Responder};
+use chrono::{DateTime, Duration, Local};
+use plotters::backend::DrawingBackend;
+use plotters::chart::{ChartBuilder, SeriesLabelPosition};
+use plotters::coord::Shift;
+use plotters::drawing::{DrawingArea, DrawingAreaErrorKind};
+use plotters::element::Rectangle;
+use plotters::prelude::{Color, IntoFont, LineSeries, Palette, Palette99, BLUE, WHITE};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use utoipa::{IntoParams, ToSchema};
+
+// The most grid points used when no step is given
+const COMPARISON_POINTS: usize = 200;
+
+// A step small enough to give more grid points than this is widened, the way plot sizes are clamped
+const MAX_COMPARISON_POINTS: usize = 2000;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ComparisonQuery {
+    /// Comma separated list of locations to compare
+    #[param(example = "basement,garage")]
+    locations: String,
+    /// Start of the range, defaults to one day before `to`
+    from: Option<DateTime<Local>>,
+    /// End of the range, defaults to now
+    to: Option<DateTime<Local>>,
+    /// How far back from `to` to compare, such as 24h or 7d
+    #[param(example = "7d")]
+    last: Option<String>,
+    /// Grid spacing such as 5m or 1h, picked from the range when left out
+    #[param(value_type = Option<String>, example = "5m")]
+    step: Option<BucketWidth>,
+    /// Metric to compare, defaults to temperature
+    metric: Option<Metric>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ComparisonPlotQuery {
+    /// Image format, overrides the Accept header, defaults to svg
+    #[param(inline)]
+    format: Option<PlotFormat>,
+    /// Image width in pixels, defaults to 1000
+    width: Option<u32>,
+    /// Image height in pixels, defaults to 1000
+    height: Option<u32>,
+    /// Scales text and lines, defaults to 96
+    dpi: Option<u32>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ComparisonSeries {
+    location: Location,
+    values: Vec<Option<f64>>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Comparison {
+    metric: Metric,
+    step_seconds: i64,
+    times: Vec<DateTime<Local>>,
+    series: Vec<ComparisonSeries>,
+}
+
+/// The locations, metric and time grid a comparison query asks for, Err says what is wrong with it.
+struct ComparisonRequest {
+    locations: Vec<Location>,
+    metric: Metric,
+    from: DateTime<Local>,
+    to: DateTime<Local>,
+    step: BucketWidth,
+}
+
+impl ComparisonRequest {
+    fn new(query: &ComparisonQuery) -> Result<Self, String> {
+        let locations = query
+            .locations
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(Location::from)
+            .collect::<Vec<_>>();
+
+        if locations.is_empty() {
+            return Err("At least one location is required".to_string());
+        }
+
+        let (from, to) = resolve_range(
+            query.from,
+            query.to,
+            query.last.as_deref(),
+            Duration::days(1),
+        )?;
+
+        let smallest_step = BucketWidth::for_span(to - from, MAX_COMPARISON_POINTS);
+        let step = match query.step {
+            Some(step) if step.duration() >= smallest_step.duration() => step,
+            Some(_) => smallest_step,
+            None => BucketWidth::for_span(to - from, COMPARISON_POINTS),
+        };
+
+        // a range ending now moves with every request, snap it to whole steps so repeated
+        // requests compare the same buckets until the range moves on to the next one
+        let (from, to) = if query.to.is_none() {
+            (step.bucket_start(from), step.bucket_start(to) + step.duration())
+        } else {
+            (from, to)
+        };
+
+        Ok(Self {
+            locations,
+            metric: query.metric.unwrap_or(Metric::Temperature),
+            from,
+            to,
+            step,
+        })
+    }
+}
+
+/// Loads every requested location and lines their bucket means up on one shared time grid.
+async fn build_comparison(
+    state: &TemperatureServerState,
+    request: &ComparisonRequest,
+) -> Result<Comparison, HttpResponse> {
+    let ComparisonRequest {
+        metric,
+        from,
+        to,
+        step,
+        ..
+    } = *request;
+    let times = step.grid(from, to);
+
+    let mut series = vec![];
+    for location in &request.locations {
+        let readings = match state
+            .get_readings_between(location, Some(from), Some(to))
+            .await
+        {
+            Ok(Some(readings)) => readings,
+            Ok(None) => {
+                return Err(HttpResponseBuilder::new(StatusCode::NOT_FOUND)
+                    .body(format!("Unknown location: {}", location)));
+            }
+            Err(err) => {
+                error!("Error reading location {}: {}", location, err);
+                return Err(HttpResponseBuilder::new(StatusCode::INTERNAL_SERVER_ERROR).finish());
+            }
+        };
+
+        let rows = aggregate(&readings, step, &[metric], &[Aggregate::Mean]);
+        series.push(ComparisonSeries {
+            values: align_to_grid(&rows, &times, metric, Aggregate::Mean),
+            location: location.clone(),
+        });
+    }
+
+    Ok(Comparison {
+        metric,
+        step_seconds: step.num_seconds(),
+        times,
+        series,
+    })
+}
+
+impl Drawable for Comparison {
+    fn draw<DB: DrawingBackend>(
+        &self,
+        root: &DrawingArea<DB, Shift>,
+        size: PlotSize,
+    ) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+        let font = |font_size: u32| ("sans-serif", size.scaled(font_size)).into_font();
+
+        let values = || {
+            self.series
+                .iter()
+                .flat_map(|series| series.values.iter().flatten().map(|v| *v as f32))
+        };
+        let highest_value = values().max_by(|a, b| a.total_cmp(b)).unwrap_or(100f32);
+        let lowest_value = values().min_by(|a, b| a.total_cmp(b)).unwrap_or(0f32);
+        // leave a little room above and below the lines
+        let padding = ((highest_value - lowest_value) * 0.05).max(1.0);
+
+        let step = Duration::seconds(self.step_seconds);
+        let (start, end) = match (self.times.first(), self.times.last()) {
+            (Some(first), Some(last)) => (*first, *last + step),
+            _ => (Local::now() - Duration::hours(1), Local::now()),
+        };
+
+        root.fill(&WHITE)?;
+
+        let mut chart = ChartBuilder::on(root)
+            .caption(format!("{} Comparison", self.metric.display_name()), font(40))
+            .x_label_area_size(size.scaled(40))
+            .y_label_area_size(size.scaled(40))
+            .build_cartesian_2d(start..end, (lowest_value - padding)..(highest_value + padding))?;
+
+        let x_label_format = time_label_format(end - start);
+        chart
+            .configure_mesh()
+            .x_labels(8)
+            .x_label_formatter(&|time| time.format(x_label_format).to_string())
+            .x_label_style(font(14))
+            .y_label_style(font(14))
+            .y_desc(match self.metric.unit() {
+                "" => self.metric.display_name().to_string(),
+                unit => format!("{} ({})", self.metric.display_name(), unit),
+            })
+            .axis_desc_style(font(14))
+            .draw()?;
+
+        let legend_width = size.scaled(15) as i32;
+
+        for (idx, series) in self.series.iter().enumerate() {
+            let color = Palette99::pick(idx).to_rgba();
+
+            let points = self
+                .times
+                .iter()
+                .zip(series.values.iter())
+                .filter_map(|(time, value)| value.map(|v| (*time + step / 2, v as f32)))
+                .collect::<Vec<_>>();
+
+            // a missing grid point breaks the line instead of joining its neighbours
+            for (segment_idx, segment) in split_on_gaps(&points, step).into_iter().enumerate() {
+                let drawn = chart.draw_series(LineSeries::new(
+                    segment,
+                    color.stroke_width(size.scaled(3)),
+                ))?;
+                // only label the first segment so each location appears once in the legend
+                if segment_idx == 0 {
+                    drawn.label(series.location.as_str()).legend(move |(x, y)| {
+                        Rectangle::new([(x - legend_width, y + 1), (x, y)], color)
+                    });
+                }
+            }
+        }
+
+        chart
+            .configure_series_labels()
+            .position(SeriesLabelPosition::UpperRight)
+            .margin(size.scaled(20))
+            .legend_area_size(size.scaled(5))
+            .border_style(BLUE)
+            .background_style(BLUE.mix(0.1))
+            .label_font(font(20))
+            .draw()?;
+
+        root.present()
+    }
+}
+
+#[utoipa::path(
+    tag = "readings",
+    params(ComparisonQuery),
+    responses(
+        (status = 200, description = "Bucket means of each location on a shared time grid", body = Comparison),
+        (status = 400, description = "Bad locations, range or step"),
+        (status = 404, description = "Unknown location"),
+    )
+)]
+#[get("/api/v1/compare")]
+pub async fn comparison_handler(
+    query: web::Query<ComparisonQuery>,
+    state: web::Data<TemperatureServerState>,
+) -> impl Responder {
+    let request = match ComparisonRequest::new(&query) {
+        Ok(request) => request,
+        Err(err) => return HttpResponseBuilder::new(StatusCode::BAD_REQUEST).body(err),
+    };
+
+    match build_comparison(&state, &request).await {
+        Ok(comparison) => HttpResponseBuilder::new(StatusCode::OK).json(comparison),
+        Err(resp) => resp,
+    }
+}
+
+#[utoipa::path(
+    tag = "plots",
+    params(ComparisonQuery, ComparisonPlotQuery),
+    responses(
+        (status = 200, description = "One line per location", content(
+            (String = "image/svg+xml"),
+            (Vec<u8> = "image/png"),
+            (Vec<u8> = "image/webp"),
+        )),
+        (status = 400, description = "Bad locations, range or step"),
+        (status = 404, description = "Unknown location"),
+    )
+)]
+#[get("/compare/plot")]
+pub async fn comparison_plot_handler(
+    req: HttpRequest,
+    query: web::Query<ComparisonQuery>,
+    plot_query: web::Query<ComparisonPlotQuery>,
+    state: web::Data<TemperatureServerState>,
+) -> impl Responder {
+    let request = match ComparisonRequest::new(&query) {
+        Ok(request) => request,
+        Err(err) => return HttpResponseBuilder::new(StatusCode::BAD_REQUEST).body(err),
+    };
+
+    let mut last_writes = vec![];
+    for location in &request.locations {
+        match state.get_last_write_time(location).await {
+            Ok(Some(last_write)) => last_writes.push(last_write),
+            Ok(None) => {
+                return HttpResponseBuilder::new(StatusCode::NOT_FOUND)
+                    .body(format!("Unknown location: {}", location))
+            }
+            Err(err) => {
+                error!("Error reading plot file time: {}", err);
+                return HttpResponseBuilder::new(StatusCode::INTERNAL_SERVER_ERROR).finish();
+            }
+        }
+    }
+
+    let format = PlotFormat::negotiate(plot_query.format, &req);
+    let key = ComparisonKey {
+        locations: request.locations.clone(),
+        metric: request.metric,
+        from: request.from,
+        to: request.to,
+        step: request.step,
+        format,
+        size: PlotSize::new(plot_query.width, plot_query.height, plot_query.dpi),
+        last_writes,
+    };
+
+    let cached = state.comparison_plot_cache.lock().await.get(&key);
+    let content = match cached {
+        Some(content) => content,
+        None => {
+            let comparison = match build_comparison(&state, &request).await {
+                Ok(comparison) => comparison,
+                Err(resp) => return resp,
+            };
+
+            match render(comparison, key.size, format).await {
+                Ok(content) => {
+                    let content = Bytes::from(content);
+                    state
+                        .comparison_plot_cache
+                        .lock()
+                        .await
+                        .insert(key, content.clone());
+                    content
+                }
+                Err(err) => {
+                    error!("Error drawing comparison plot: {}", err);
+                    return HttpResponseBuilder::new(StatusCode::INTERNAL_SERVER_ERROR).finish();
+                }
+            }
+        }
+    };
+
+    HttpResponseBuilder::new(StatusCode::OK)
+        .content_type(format.content_type())
+        .insert_header((VARY, ACCEPT.as_str()))
+        .body(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{readings, TestFolder};
+    use actix_web::http::header;
+    use actix_web::test::{
+        call_and_read_body_json, call_service, init_service, read_body, TestRequest,
+    };
+    use actix_web::App;
+
+    #[actix_web::test]
+    async fn comparison_plot_is_rendered_and_cached() {
+        let folder = TestFolder::new("comparison-plot");
+        let now = Local::now();
+        folder.write_location("basement", &readings("basement", now, 30));
+        folder.write_location("garage", &readings("garage", now, 30));
+        let state = folder.state();
+        let app = init_service(
+            App::new()
+                .app_data(state.clone())
+                .service(comparison_plot_handler),
+        )
+        .await;
+
+        let resp = call_service(
+            &app,
+            TestRequest::get()
+                .uri("/compare/plot?locations=basement,garage&last=1h")
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "image/svg+xml"
+        );
+        let svg = read_body(resp).await;
+        assert!(std::str::from_utf8(&svg).unwrap().contains("garage"));
+
+        // the same plot again comes out of the cache
+        let again = call_service(
+            &app,
+            TestRequest::get()
+                .uri("/compare/plot?locations=basement,garage&last=1h")
+                .to_request(),
+        )
+        .await;
+        assert_eq!(read_body(again).await, svg);
+        assert!(state
+            .comparison_plot_cache
+            .lock()
+            .await
+            .get(&comparison_key(&state, "basement,garage").await)
+            .is_some());
+
+        let resp = call_service(
+            &app,
+            TestRequest::get()
+                .uri("/compare/plot?locations=basement&last=1h")
+                .insert_header((header::ACCEPT, "image/png"))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "image/png"
+        );
+
+        let resp = call_service(
+            &app,
+            TestRequest::get()
+                .uri("/compare/plot?locations=basement,attic")
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let resp = call_service(
+            &app,
+            TestRequest::get()
+                .uri("/compare/plot?locations=,")
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    async fn comparison_key(state: &TemperatureServerState, locations: &str) -> ComparisonKey {
+        let request = ComparisonRequest::new(&ComparisonQuery {
+            locations: locations.to_string(),
+            from: None,
+            to: None,
+            last: Some("1h".to_string()),
+            step: None,
+            metric: None,
+        })
+        .unwrap();
+
+        let mut last_writes = vec![];
+        for location in &request.locations {
+            last_writes.push(state.get_last_write_time(location).await.unwrap().unwrap());
+        }
+
+        ComparisonKey {
+            locations: request.locations,
+            metric: request.metric,
+            from: request.from,
+            to: request.to,
+            step: request.step,
+            format: PlotFormat::Svg,
+            size: PlotSize::new(None, None, None),
+            last_writes,
+        }
+    }
+
+    #[actix_web::test]
+    async fn small_steps_are_widened() {
+        let folder = TestFolder::new("comparison-step");
+        folder.write_location("basement", &readings("basement", Local::now(), 3));
+        let app = init_service(
+            App::new()
+                .app_data(folder.state())
+                .service(comparison_handler),
+        )
+        .await;
+
+        let comparison: serde_json::Value = call_and_read_body_json(
+            &app,
+            TestRequest::get()
+                .uri("/api/v1/compare?locations=basement&last=30d&step=1m")
+                .to_request(),
+        )
+        .await;
+
+        let times = comparison["times"].as_array().unwrap().len();
+        assert!(times <= MAX_COMPARISON_POINTS + 1, "{} grid points", times);
+        assert!(comparison["step_seconds"].as_i64().unwrap() > 60);
+    }
+}
@@ -0,0 +1,188 @@
+use crate::email::EmailConfig;
+use crate::location::Location;
+use crate::rules::{validate_rules, Rule};
+use crate::webhook::{validate_webhooks, WebhookConfig};
+use crate::CONFIG_PATH;
+use chrono::Duration;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::sync::LazyLock;
+use tracing::info;
+
+/// Server configuration, read once from the toml file at `CONFIG_PATH`. A missing file is the same
+/// as an empty one, but a file that does not parse stops the server from starting.
+///
+/// ```toml
+/// [locations.freezer]
+/// reading_period_seconds = 30
+/// gap_multiple = 4.0
+/// late_after_seconds = 90
+/// missing_after_seconds = 600
+///
+/// [locations.freezer.plot]
+/// temperature_range = [-20.0, 40.0]
+/// humidity_range = [0.0, 100.0]
+///
+/// [[rules]]
+/// name = "freezer too warm"
+/// locations = ["freezer"]
+/// metric = "temperature"
+/// unit = "celsius"
+/// condition = { above = -10.0 }
+/// for_minutes = 5
+///
+/// [[webhooks]]
+/// name = "ntfy"
+/// url = "https://ntfy.sh/our-sensors"
+/// template = { topic = "our-sensors", message = "{message}" }
+///
+/// [email]
+/// server = "smtp.example.com"
+/// username = "sensors@example.com"
+/// password = "..."
+/// from = "Sensors <sensors@example.com>"
+/// to = ["team@example.com"]
+/// digest_time = "07:00"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServerConfig {
+    #[serde(default)]
+    locations: HashMap<String, LocationConfig>,
+    /// Alert rules, see [`Rule`]
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+    /// Where notifications are posted, see [`WebhookConfig`]
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+    /// Where notifications and the daily digest are emailed, see [`EmailConfig`]
+    pub email: Option<EmailConfig>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LocationConfig {
+    /// How often the sensor reports, defaults to the pico firmware's 60 seconds
+    pub reading_period_seconds: Option<u32>,
+    /// How many reading periods without a reading count as an outage, defaults to 3
+    pub gap_multiple: Option<f32>,
+    /// Seconds without a reading before the sensor is shown as late, defaults to the outage gap
+    pub late_after_seconds: Option<u32>,
+    /// Seconds without a reading before the sensor is shown as missing, defaults to 10 reading periods
+    pub missing_after_seconds: Option<u32>,
+    #[serde(default)]
+    pub plot: PlotConfig,
+}
+
+impl LocationConfig {
+    pub fn reading_period(&self) -> Duration {
+        Duration::seconds(self.reading_period_seconds.unwrap_or(60) as i64)
+    }
+
+    /// The longest time between two readings that is not an outage.
+    pub fn max_gap(&self) -> Duration {
+        let multiple = self.gap_multiple.unwrap_or(3.0).max(1.0) as f64;
+        Duration::milliseconds((self.reading_period().num_milliseconds() as f64 * multiple) as i64)
+    }
+
+    /// How long since the last reading before the sensor is late.
+    pub fn late_after(&self) -> Duration {
+        self.late_after_seconds
+            .map(|seconds| Duration::seconds(seconds as i64))
+            .unwrap_or_else(|| self.max_gap())
+    }
+
+    /// How long since the last reading before the sensor is missing, never sooner than it is late.
+    pub fn missing_after(&self) -> Duration {
+        self.missing_after_seconds
+            .map(|seconds| Duration::seconds(seconds as i64))
+            .unwrap_or_else(|| self.reading_period() * 10)
+            .max(self.late_after())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PlotConfig {
+    /// Fixed range of the temperature (left) axis instead of scaling it to the data
+    pub temperature_range: Option<(f32, f32)>,
+    /// Fixed range of the humidity (right) axis instead of scaling it to the data
+    pub humidity_range: Option<(f32, f32)>,
+}
+
+/// Checks every location config, the rest of a location config is checked when it is used.
+fn validate_locations(locations: &HashMap<String, LocationConfig>) -> Result<(), String> {
+    for (location, config) in locations {
+        if config.reading_period_seconds == Some(0) {
+            return Err(format!(
+                "Location {} reading_period_seconds must be more than 0",
+                location
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+impl ServerConfig {
+    /// Parses and checks the contents of a config file.
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let config: ServerConfig = toml::from_str(contents).map_err(|err| err.to_string())?;
+        validate_locations(&config.locations)
+            .and_then(|_| validate_rules(&config.rules))
+            .and_then(|_| validate_webhooks(&config.webhooks))
+            .and_then(|_| config.email.as_ref().map_or(Ok(()), EmailConfig::validate))?;
+        Ok(config)
+    }
+
+    pub fn location(&self, location: &Location) -> LocationConfig {
+        self.locations
+            .get(location.as_str())
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+pub static CONFIG: LazyLock<ServerConfig> = LazyLock::new(|| {
+    match fs::read_to_string(CONFIG_PATH.as_path()) {
+        Ok(contents) => {
+            info!("Loaded config from {}", CONFIG_PATH.display());
+            ServerConfig::parse(&contents).unwrap_or_else(|err| {
+                panic!("Invalid config file {}: {}", CONFIG_PATH.display(), err)
+            })
+        }
+        Err(_) => {
+            info!(
+                "No config file at {}, using defaults",
+                CONFIG_PATH.display()
+            );
+            ServerConfig::default()
+        }
+    }
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reading_periods_have_to_be_positive() {
+        let config = ServerConfig::parse(
+            "[locations.freezer]\nreading_period_seconds = 30\ngap_multiple = 4.0\n",
+        )
+        .unwrap();
+        let freezer = config.location(&"freezer".into());
+        assert_eq!(freezer.reading_period(), Duration::seconds(30));
+        assert_eq!(freezer.max_gap(), Duration::seconds(120));
+        assert_eq!(
+            config.location(&"garage".into()).reading_period(),
+            Duration::seconds(60)
+        );
+
+        let err =
+            ServerConfig::parse("[locations.freezer]\nreading_period_seconds = 0\n").unwrap_err();
+        assert!(err.contains("freezer"), "{}", err);
+        assert!(ServerConfig::parse("[locations.freezer]\nreading_period_seconds = -5\n").is_err());
+    }
+}
@@ -0,0 +1,144 @@
+use crate::reading::Reading;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+// Magnus formula coefficients (Sonntag 1990), valid for roughly -45 C to 60 C
+const MAGNUS_A: f32 = 17.62;
+const MAGNUS_B: f32 = 243.12;
+
+pub fn fahrenheit_to_celsius(fahrenheit: f32) -> f32 {
+    (fahrenheit - 32.0) / 1.8
+}
+
+pub fn celsius_to_fahrenheit(celsius: f32) -> f32 {
+    (celsius * 1.8) + 32.0
+}
+
+/// Dew point in Fahrenheit, from a temperature in Fahrenheit and relative humidity in percent.
+pub fn dew_point(temperature: f32, humidity: f32) -> f32 {
+    let celsius = fahrenheit_to_celsius(temperature);
+    // ln(0) is -inf, so clamp to a tiny humidity instead
+    let gamma =
+        (humidity.max(0.01) / 100.0).ln() + (MAGNUS_A * celsius) / (MAGNUS_B + celsius);
+
+    celsius_to_fahrenheit((MAGNUS_B * gamma) / (MAGNUS_A - gamma))
+}
+
+/// Absolute humidity in grams of water vapour per cubic metre of air.
+pub fn absolute_humidity(temperature: f32, humidity: f32) -> f32 {
+    let celsius = fahrenheit_to_celsius(temperature);
+    let saturation_vapour_pressure = 6.112 * ((MAGNUS_A * celsius) / (MAGNUS_B + celsius)).exp();
+
+    (saturation_vapour_pressure * humidity * 2.1674) / (273.15 + celsius)
+}
+
+/// NOAA heat index in Fahrenheit, using the Rothfusz regression with its adjustments.
+/// Below 80 F the simpler Steadman formula is used, as the regression is not valid there.
+pub fn heat_index(temperature: f32, humidity: f32) -> f32 {
+    let (t, rh) = (temperature, humidity);
+
+    let simple = 0.5 * (t + 61.0 + ((t - 68.0) * 1.2) + (rh * 0.094));
+    if (simple + t) / 2.0 < 80.0 {
+        return simple;
+    }
+
+    let mut index = -42.379 + 2.049_015_2 * t + 10.143_331 * rh
+        - 0.224_755_4 * t * rh
+        - 0.006_837_83 * t * t
+        - 0.054_817_17 * rh * rh
+        + 0.001_228_74 * t * t * rh
+        + 0.000_852_82 * t * rh * rh
+        - 0.000_001_99 * t * t * rh * rh;
+
+    if rh < 13.0 && (80.0..=112.0).contains(&t) {
+        index -= ((13.0 - rh) / 4.0) * ((17.0 - (t - 95.0).abs()) / 17.0).sqrt();
+    } else if rh > 85.0 && (80.0..=87.0).contains(&t) {
+        index += ((rh - 85.0) / 10.0) * ((87.0 - t) / 5.0);
+    }
+
+    index
+}
+
+/// Canadian humidex, a dimensionless number on the Celsius scale.
+pub fn humidex(temperature: f32, humidity: f32) -> f32 {
+    let dew_point_kelvin = fahrenheit_to_celsius(dew_point(temperature, humidity)) + 273.15;
+    let vapour_pressure = 6.11 * (5417.753 * ((1.0 / 273.16) - (1.0 / dew_point_kelvin))).exp();
+
+    fahrenheit_to_celsius(temperature) + 0.5555 * (vapour_pressure - 10.0)
+}
+
+/// Comfort metrics computed from the temperature and humidity of a reading.
+#[derive(Debug, Clone, Copy, Serialize, ToSchema)]
+pub struct DerivedMetrics {
+    pub dew_point: f32,
+    pub absolute_humidity: f32,
+    pub heat_index: f32,
+    pub humidex: f32,
+}
+
+impl From<&Reading> for DerivedMetrics {
+    fn from(reading: &Reading) -> Self {
+        let (t, rh) = (reading.temperature(), reading.humidity());
+        Self {
+            dew_point: dew_point(t, rh),
+            absolute_humidity: absolute_humidity(t, rh),
+            heat_index: heat_index(t, rh),
+            humidex: humidex(t, rh),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32, tolerance: f32) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "expected {} within {} of {}",
+            actual,
+            tolerance,
+            expected
+        );
+    }
+
+    #[test]
+    fn dew_point_matches_known_values() {
+        // 20 C at 50 % has a dew point of 9.3 C
+        assert_close(
+            fahrenheit_to_celsius(dew_point(celsius_to_fahrenheit(20.0), 50.0)),
+            9.26,
+            0.05,
+        );
+        // saturated air is at its dew point
+        assert_close(dew_point(75.0, 100.0), 75.0, 0.01);
+        // no humidity is clamped rather than giving -inf
+        assert!(dew_point(75.0, 0.0).is_finite());
+    }
+
+    #[test]
+    fn absolute_humidity_matches_known_values() {
+        // 20 C at 50 % holds about 8.6 g/m3
+        assert_close(
+            absolute_humidity(celsius_to_fahrenheit(20.0), 50.0),
+            8.62,
+            0.05,
+        );
+        assert_eq!(absolute_humidity(celsius_to_fahrenheit(20.0), 0.0), 0.0);
+    }
+
+    #[test]
+    fn heat_index_matches_the_noaa_table() {
+        assert_close(heat_index(90.0, 60.0), 100.0, 0.5);
+        assert_close(heat_index(100.0, 40.0), 109.0, 0.5);
+        // below 80 F the simple formula stays close to the temperature
+        assert_close(heat_index(70.0, 50.0), 69.05, 0.01);
+    }
+
+    #[test]
+    fn humidex_matches_known_values() {
+        // 30 C at 70 % is a humidex of 41
+        assert_close(humidex(celsius_to_fahrenheit(30.0), 70.0), 41.2, 0.1);
+        assert_close(humidex(celsius_to_fahrenheit(30.0), 40.0), 33.9, 0.1);
+    }
+}
@@ -0,0 +1,225 @@
+use chrono::{DateTime, Duration, Local};
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+/// Points of one plotted line, in time order
+pub type TimeSeries = Vec<(DateTime<Local>, f32)>;
+
+/// How a series with more points than can be drawn is reduced.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Downsampling {
+    /// Largest-Triangle-Three-Buckets, keeps the visual shape including single spikes
+    #[default]
+    Lttb,
+    /// The lowest and highest point of each bucket, an envelope of the data
+    #[serde(alias = "minmax")]
+    MinMax,
+    /// The mean of each time bucket, smooths out noise and spikes
+    Mean,
+}
+
+/// Reduces `points` to `threshold` points with Largest-Triangle-Three-Buckets.
+/// The first and last point are always kept, and from each bucket in between the point forming
+/// the largest triangle with the previously kept point and the mean of the next bucket is kept.
+pub fn lttb(points: &[(DateTime<Local>, f32)], threshold: usize) -> TimeSeries {
+    if threshold >= points.len() || threshold < 3 {
+        return points.to_vec();
+    }
+
+    // seconds since the first point, so the areas are not computed with huge timestamps
+    let origin = points[0].0;
+    let x = |idx: usize| (points[idx].0 - origin).num_milliseconds() as f64 / 1000.0;
+    let y = |idx: usize| points[idx].1 as f64;
+
+    let bucket_size = (points.len() - 2) as f64 / (threshold - 2) as f64;
+    let bucket_start = |bucket: usize| (bucket as f64 * bucket_size) as usize + 1;
+
+    let mut sampled = Vec::with_capacity(threshold);
+    sampled.push(points[0]);
+    let mut previous = 0;
+
+    for bucket in 0..threshold - 2 {
+        let next_range = bucket_start(bucket + 1)..bucket_start(bucket + 2).min(points.len());
+        let next_len = next_range.len().max(1) as f64;
+        let (next_x, next_y) = next_range
+            .fold((0.0, 0.0), |(sum_x, sum_y), idx| (sum_x + x(idx), sum_y + y(idx)));
+        let (next_x, next_y) = (next_x / next_len, next_y / next_len);
+
+        let (prev_x, prev_y) = (x(previous), y(previous));
+        let chosen = (bucket_start(bucket)..bucket_start(bucket + 1))
+            .map(|idx| {
+                let area = ((prev_x - next_x) * (y(idx) - prev_y)
+                    - (prev_x - x(idx)) * (next_y - prev_y))
+                    .abs();
+                (idx, area)
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(idx, _)| idx)
+            .unwrap_or(previous);
+
+        sampled.push(points[chosen]);
+        previous = chosen;
+    }
+
+    sampled.push(points[points.len() - 1]);
+    sampled
+}
+
+/// Splits `points` into `buckets` equally sized buckets and keeps the lowest and highest point
+/// of each, in time order, so peaks and troughs are never lost.
+pub fn min_max(points: &[(DateTime<Local>, f32)], buckets: usize) -> TimeSeries {
+    if buckets == 0 || points.len() <= buckets * 2 {
+        return points.to_vec();
+    }
+
+    let bucket_size = points.len().div_ceil(buckets);
+
+    points
+        .chunks(bucket_size)
+        .flat_map(|bucket| {
+            let lowest = bucket
+                .iter()
+                .enumerate()
+                .min_by(|a, b| a.1 .1.total_cmp(&b.1 .1))
+                .map(|(idx, _)| idx)
+                .unwrap_or(0);
+            let highest = bucket
+                .iter()
+                .enumerate()
+                .max_by(|a, b| a.1 .1.total_cmp(&b.1 .1))
+                .map(|(idx, _)| idx)
+                .unwrap_or(0);
+
+            match lowest.cmp(&highest) {
+                std::cmp::Ordering::Less => vec![bucket[lowest], bucket[highest]],
+                std::cmp::Ordering::Equal => vec![bucket[lowest]],
+                std::cmp::Ordering::Greater => vec![bucket[highest], bucket[lowest]],
+            }
+        })
+        .collect()
+}
+
+/// The mean of each point and every point up to `window` before it.
+pub fn rolling_mean(points: &[(DateTime<Local>, f32)], window: Duration) -> TimeSeries {
+    let mut window_start = 0;
+    let mut sum = 0f64;
+
+    points
+        .iter()
+        .enumerate()
+        .map(|(idx, (time, value))| {
+            sum += *value as f64;
+            while *time - points[window_start].0 > window {
+                sum -= points[window_start].1 as f64;
+                window_start += 1;
+            }
+            (*time, (sum / (idx + 1 - window_start) as f64) as f32)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// One point a minute, with values from `value`
+    fn series(len: usize, value: impl Fn(usize) -> f32) -> TimeSeries {
+        let start = Local.with_ymd_and_hms(2025, 1, 1, 12, 0, 0).unwrap();
+        (0..len)
+            .map(|idx| (start + Duration::minutes(idx as i64), value(idx)))
+            .collect()
+    }
+
+    fn is_time_ordered(points: &TimeSeries) -> bool {
+        points.windows(2).all(|pair| pair[0].0 < pair[1].0)
+    }
+
+    #[test]
+    fn lttb_leaves_short_series_alone() {
+        let points = series(50, |idx| idx as f32);
+        assert_eq!(lttb(&points, 100), points);
+        assert_eq!(lttb(&points, 2), points);
+    }
+
+    #[test]
+    fn lttb_hits_threshold_and_keeps_the_ends() {
+        let points = series(10_000, |idx| (idx as f32 / 100.0).sin());
+        let sampled = lttb(&points, 500);
+
+        assert_eq!(sampled.len(), 500);
+        assert_eq!(sampled.first(), points.first());
+        assert_eq!(sampled.last(), points.last());
+        assert!(is_time_ordered(&sampled));
+    }
+
+    #[test]
+    fn lttb_keeps_a_single_spike_and_trough() {
+        let points = series(43_200, |idx| match idx {
+            12_345 => 95.0,
+            30_000 => -20.0,
+            _ => 70.0 + (idx as f32 / 1000.0).sin(),
+        });
+        let sampled = lttb(&points, 200);
+
+        assert!(sampled.contains(&points[12_345]));
+        assert!(sampled.contains(&points[30_000]));
+    }
+
+    #[test]
+    fn min_max_keeps_every_extreme_in_order() {
+        let points = series(100_000, |idx| match idx {
+            777 => 150.0,
+            99_000 => -40.0,
+            _ => ((idx % 97) as f32).sqrt(),
+        });
+        let sampled = min_max(&points, 250);
+
+        assert!(sampled.len() <= 500);
+        assert!(is_time_ordered(&sampled));
+        assert!(sampled.contains(&points[777]));
+        assert!(sampled.contains(&points[99_000]));
+    }
+
+    #[test]
+    fn min_max_envelope_matches_bucket_extremes() {
+        // a sawtooth with a period of 10 points, every bucket of 100 holds a full 0..=9 cycle
+        let points = series(1_000, |idx| (idx % 10) as f32);
+        let sampled = min_max(&points, 10);
+
+        assert_eq!(sampled.len(), 20);
+        for pair in sampled.chunks(2) {
+            assert_eq!(pair[0].1, 0.0);
+            assert_eq!(pair[1].1, 9.0);
+        }
+    }
+
+    #[test]
+    fn downsampling_handles_empty_series() {
+        assert!(lttb(&[], 100).is_empty());
+        assert!(min_max(&[], 100).is_empty());
+    }
+
+    #[test]
+    fn rolling_mean_averages_the_trailing_window() {
+        let points = series(10, |idx| idx as f32);
+        let averaged = rolling_mean(&points, Duration::minutes(2));
+
+        assert_eq!(averaged.len(), points.len());
+        assert_eq!(averaged[0].1, 0.0);
+        assert_eq!(averaged[1].1, 0.5);
+        // minutes 7, 8 and 9
+        assert_eq!(averaged[9].1, 8.0);
+        assert!(averaged.iter().zip(&points).all(|(a, p)| a.0 == p.0));
+    }
+
+    #[test]
+    fn min_max_can_be_written_without_the_underscore() {
+        let parse = |s: &str| serde_json::from_value::<Downsampling>(s.into()).ok();
+        assert_eq!(parse("min_max"), Some(Downsampling::MinMax));
+        assert_eq!(parse("minmax"), Some(Downsampling::MinMax));
+        assert_eq!(parse("lttb"), Some(Downsampling::Lttb));
+        assert_eq!(parse("median"), None);
+    }
+}
@@ -0,0 +1,589 @@
+use crate::metadata::TemperatureUnit;
+use crate::notifier::{queue_notifications, Notification, NotificationKind, Retries};
+use crate::outage::{find_outages, Outage};
+use crate::reading::Reading;
+use crate::state::TemperatureServerState;
+use actix_web::web;
+use chrono::{DateTime, Duration, Local, NaiveTime};
+use lettre::address::AddressError;
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+use serde::Deserialize;
+use std::fmt::Write;
+use tracing::error;
+
+// Format of `digest_time` in the config file
+const DIGEST_TIME_FORMAT: &str = "%H:%M";
+
+// How far back the daily digest looks
+const DIGEST_PERIOD_HOURS: i64 = 24;
+
+// Give up on a mail server that takes longer than this to answer
+const SMTP_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmtpSecurity {
+    /// Connect in plain text then upgrade the connection, usually on port 587
+    #[default]
+    Starttls,
+    /// Connect over TLS from the start, usually on port 465
+    Tls,
+    /// Never encrypt, only for a mail server on the same machine or a local test sink
+    None,
+}
+
+/// Where and how emails are sent, from the config file, such as
+///
+/// ```toml
+/// [email]
+/// server = "smtp.example.com"
+/// username = "sensors@example.com"
+/// password = "..."
+/// from = "Sensors <sensors@example.com>"
+/// to = ["team@example.com"]
+/// events = ["alert"]
+/// digest_time = "07:00"
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EmailConfig {
+    pub server: String,
+    /// Defaults to 587 for starttls, 465 for tls and 25 without encryption
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub security: SmtpSecurity,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+    /// Which notifications are emailed as they happen, every kind when empty
+    #[serde(default)]
+    pub events: Vec<NotificationKind>,
+    /// Local time the daily digest is sent at, as HH:MM, there is no digest without it
+    pub digest_time: Option<String>,
+    /// How many times an email is sent before giving up, defaults to 5
+    pub max_attempts: Option<u32>,
+    /// Seconds before the first retry, doubling after every failed attempt, defaults to 5
+    pub retry_after_seconds: Option<u64>,
+}
+
+impl EmailConfig {
+    pub fn wants(&self, notification: &Notification) -> bool {
+        self.events.is_empty() || self.events.contains(&notification.kind())
+    }
+
+    pub fn retries(&self) -> Retries {
+        Retries::new(self.max_attempts, self.retry_after_seconds)
+    }
+
+    pub fn digest_time(&self) -> Option<NaiveTime> {
+        self.digest_time
+            .as_deref()
+            .and_then(|time| NaiveTime::parse_from_str(time, DIGEST_TIME_FORMAT).ok())
+    }
+
+    /// Checks the addresses parse and the digest time is a time.
+    pub fn validate(&self) -> Result<(), String> {
+        self.from
+            .parse::<Mailbox>()
+            .map_err(|err| format!("Invalid email from address {:?}: {}", self.from, err))?;
+        if self.to.is_empty() {
+            return Err("Email needs at least one to address".to_string());
+        }
+        for to in &self.to {
+            to.parse::<Mailbox>()
+                .map_err(|err| format!("Invalid email to address {:?}: {}", to, err))?;
+        }
+        if self.username.is_some() != self.password.is_some() {
+            return Err("Email needs both a username and a password, or neither".to_string());
+        }
+        if self.digest_time.is_some() && self.digest_time().is_none() {
+            return Err(format!(
+                "Invalid email digest_time {:?}, it should be like 07:00",
+                self.digest_time.as_deref().unwrap_or_default()
+            ));
+        }
+        if self.events.contains(&NotificationKind::Digest) {
+            return Err("The digest is sent at digest_time, not as an event".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn transport(&self) -> Result<SmtpTransport, lettre::transport::smtp::Error> {
+        let builder = match self.security {
+            SmtpSecurity::Starttls => SmtpTransport::starttls_relay(&self.server)?,
+            SmtpSecurity::Tls => SmtpTransport::relay(&self.server)?,
+            SmtpSecurity::None => SmtpTransport::builder_dangerous(&self.server).port(25),
+        };
+        let builder = match self.port {
+            Some(port) => builder.port(port),
+            None => builder,
+        };
+        let builder = match (&self.username, &self.password) {
+            (Some(username), Some(password)) => {
+                builder.credentials(Credentials::new(username.clone(), password.clone()))
+            }
+            _ => builder,
+        };
+
+        Ok(builder.timeout(Some(SMTP_TIMEOUT)).build())
+    }
+
+    /// A plain text email to everyone in `to`.
+    pub fn message(&self, subject: &str, body: String) -> Result<Message, String> {
+        let mut builder = Message::builder()
+            .from(
+                self.from
+                    .parse()
+                    .map_err(|err: AddressError| err.to_string())?,
+            )
+            .subject(subject)
+            .header(ContentType::TEXT_PLAIN);
+        for to in &self.to {
+            builder = builder.to(to.parse().map_err(|err: AddressError| err.to_string())?);
+        }
+        builder.body(body).map_err(|err| err.to_string())
+    }
+}
+
+/// Whether sending again could work, a mail server that rejects the email will keep rejecting it.
+fn worth_retrying(err: &lettre::transport::smtp::Error) -> bool {
+    !err.is_permanent()
+}
+
+/// Sends one email, with retries, and records the delivery.
+async fn send_email(
+    config: &EmailConfig,
+    transport: &SmtpTransport,
+    state: &web::Data<TemperatureServerState>,
+    kind: NotificationKind,
+    subject: &str,
+    body: String,
+) {
+    let message = match config.message(subject, body) {
+        Ok(message) => message,
+        Err(err) => {
+            error!("Error building email {:?}: {}", subject, err);
+            return;
+        }
+    };
+
+    let transport = transport.clone();
+    let delivery = config
+        .retries()
+        .send(
+            "email",
+            kind,
+            subject,
+            move || transport.send(&message).map(|_| ()),
+            worth_retrying,
+        )
+        .await;
+    state.deliveries.lock().await.record(delivery).await;
+}
+
+/// Emails every notification the config wants, one at a time so they arrive in order.
+pub async fn run_email(config: EmailConfig, state: web::Data<TemperatureServerState>) {
+    let transport = match config.transport() {
+        Ok(transport) => transport,
+        Err(err) => {
+            error!("Error setting up email to {}: {}", config.server, err);
+            return;
+        }
+    };
+    let wanted = config.clone();
+    let mut queue = queue_notifications(&state, "email", move |notification| {
+        wanted.wants(notification)
+    });
+
+    while let Some(notification) = queue.recv().await {
+        let body = format!(
+            "{}\n\n{}\n",
+            notification.message(),
+            serde_json::to_string_pretty(&notification).unwrap_or_default()
+        );
+        send_email(
+            &config,
+            &transport,
+            &state,
+            notification.kind(),
+            notification.message(),
+            body,
+        )
+        .await;
+    }
+}
+
+/// The next time after `now` that is `time` o'clock.
+fn next_digest(now: DateTime<Local>, time: NaiveTime) -> DateTime<Local> {
+    (0..=2)
+        .filter_map(|days| {
+            (now.date_naive() + Duration::days(days))
+                .and_time(time)
+                .and_local_timezone(Local)
+                .earliest()
+        })
+        .find(|next| *next > now)
+        .unwrap_or(now + Duration::days(1))
+}
+
+/// Emails the digest every day at the configured time.
+pub async fn run_digest(config: EmailConfig, state: web::Data<TemperatureServerState>) {
+    let Some(time) = config.digest_time() else {
+        return;
+    };
+    let transport = match config.transport() {
+        Ok(transport) => transport,
+        Err(err) => {
+            error!("Error setting up email to {}: {}", config.server, err);
+            return;
+        }
+    };
+
+    loop {
+        let now = Local::now();
+        let next = next_digest(now, time);
+        tokio::time::sleep((next - now).to_std().unwrap_or_default()).await;
+
+        let digests = collect_digests(&state, Local::now()).await;
+        let subject = format!("Sensor digest for {}", next.format("%m/%d/%Y"));
+        send_email(
+            &config,
+            &transport,
+            &state,
+            NotificationKind::Digest,
+            &subject,
+            digest_body(&digests),
+        )
+        .await;
+    }
+}
+
+/// Lowest, highest and mean of a set of values.
+struct Spread {
+    min: f32,
+    max: f32,
+    mean: f32,
+}
+
+impl Spread {
+    fn new(values: impl Iterator<Item = f32> + Clone) -> Option<Self> {
+        let count = values.clone().count();
+        (count > 0).then(|| Self {
+            min: values.clone().fold(f32::INFINITY, f32::min),
+            max: values.clone().fold(f32::NEG_INFINITY, f32::max),
+            mean: values.sum::<f32>() / count as f32,
+        })
+    }
+}
+
+/// What one location did over the digest period.
+struct LocationDigest {
+    name: String,
+    unit: TemperatureUnit,
+    readings: Vec<Reading>,
+    outages: Vec<Outage>,
+}
+
+impl LocationDigest {
+    fn write(&self, body: &mut String) -> std::fmt::Result {
+        writeln!(body, "{}", self.name)?;
+
+        let temperature = Spread::new(
+            self.readings
+                .iter()
+                .map(|reading| self.unit.convert_fahrenheit(reading.temperature())),
+        );
+        let humidity = Spread::new(self.readings.iter().map(|reading| reading.humidity()));
+        match (temperature, humidity) {
+            (Some(temperature), Some(humidity)) => {
+                let unit = self.unit.symbol();
+                writeln!(
+                    body,
+                    "  Temperature: min {:.1} {}, max {:.1} {}, avg {:.1} {}",
+                    temperature.min, unit, temperature.max, unit, temperature.mean, unit
+                )?;
+                writeln!(
+                    body,
+                    "  Humidity: min {:.1} %, max {:.1} %, avg {:.1} %",
+                    humidity.min, humidity.max, humidity.mean
+                )?;
+                writeln!(body, "  Readings: {}", self.readings.len())?;
+            }
+            _ => writeln!(body, "  No readings")?,
+        }
+
+        if self.outages.is_empty() {
+            return writeln!(body, "  Outages: none");
+        }
+        writeln!(body, "  Outages:")?;
+        for outage in &self.outages {
+            let minutes = outage.duration_seconds / 60;
+            match outage.end {
+                Some(end) => writeln!(
+                    body,
+                    "    {} to {} ({}h {}m)",
+                    outage.start.format("%m/%d %I:%M %p"),
+                    end.format("%m/%d %I:%M %p"),
+                    minutes / 60,
+                    minutes % 60
+                )?,
+                None => writeln!(
+                    body,
+                    "    since {} ({}h {}m, still going)",
+                    outage.start.format("%m/%d %I:%M %p"),
+                    minutes / 60,
+                    minutes % 60
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+async fn collect_digests(
+    state: &web::Data<TemperatureServerState>,
+    now: DateTime<Local>,
+) -> Vec<LocationDigest> {
+    let from = now - Duration::hours(DIGEST_PERIOD_HOURS);
+    let metadata = state.metadata.lock().await.clone();
+    let mut locations = state
+        .file_buf_list
+        .lock()
+        .await
+        .keys()
+        .cloned()
+        .collect::<Vec<_>>();
+    locations.sort();
+
+    let mut digests = vec![];
+    for location in locations {
+        let readings = match state
+            .get_readings_between(&location, Some(from), Some(now))
+            .await
+        {
+            Ok(Some(readings)) => readings,
+            Ok(None) => continue,
+            Err(err) => {
+                error!("Error reading {} for the digest: {}", location, err);
+                continue;
+            }
+        };
+        let location_metadata = metadata.get(&location);
+
+        digests.push(LocationDigest {
+            name: location_metadata.display_name(&location).to_string(),
+            unit: location_metadata.unit(),
+            outages: find_outages(&readings, metadata.config(&location).max_gap(), from..now),
+            readings,
+        });
+    }
+
+    digests
+}
+
+fn digest_body(digests: &[LocationDigest]) -> String {
+    let mut body = format!(
+        "Readings from the last {} hours for every location.\n",
+        DIGEST_PERIOD_HOURS
+    );
+    for digest in digests {
+        body.push('\n');
+        // writing to a string can't fail
+        let _ = digest.write(&mut body);
+    }
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{readings, TestFolder};
+    use chrono::TimeZone;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread::{self, JoinHandle};
+
+    fn config(toml: &str) -> EmailConfig {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn digest_summarises_readings_and_outages() {
+        let noon = Local.with_ymd_and_hms(2025, 1, 1, 12, 0, 0).unwrap();
+        let readings = [(0, 50.0, 40.0), (1, 68.0, 50.0), (30, 59.0, 60.0)]
+            .map(|(minute, temperature, humidity)| {
+                Reading::new(
+                    "attic".into(),
+                    temperature,
+                    humidity,
+                    noon + Duration::minutes(minute),
+                )
+            })
+            .to_vec();
+        let outages = find_outages(
+            &readings,
+            Duration::minutes(3),
+            noon..noon + Duration::minutes(30),
+        );
+
+        let body = digest_body(&[
+            LocationDigest {
+                name: "Attic".to_string(),
+                unit: TemperatureUnit::Celsius,
+                readings,
+                outages,
+            },
+            LocationDigest {
+                name: "Garage".to_string(),
+                unit: TemperatureUnit::Fahrenheit,
+                readings: vec![],
+                outages: find_outages(
+                    &[],
+                    Duration::minutes(3),
+                    noon..noon + Duration::minutes(30),
+                ),
+            },
+        ]);
+
+        assert!(body.contains("Attic\n  Temperature: min 10.0 C, max 20.0 C, avg 15.0 C\n"));
+        assert!(body.contains("  Humidity: min 40.0 %, max 60.0 %, avg 50.0 %\n  Readings: 3\n"));
+        assert!(body.contains("    01/01 12:01 PM to 01/01 12:30 PM (0h 29m)\n"));
+        assert!(body.contains(
+            "Garage\n  No readings\n  Outages:\n    since 01/01 12:00 PM (0h 30m, still going)\n"
+        ));
+    }
+
+    #[actix_web::test]
+    async fn digest_lists_silent_locations_as_outages() {
+        let folder = TestFolder::new("digest");
+        let now = Local::now();
+        folder.write_location("attic", &readings("attic", now, 10));
+        folder.write_location("garage", &readings("garage", now - Duration::days(2), 10));
+
+        let digests = collect_digests(&folder.state(), now).await;
+        assert_eq!(digests.len(), 2);
+        // the attic only started reporting ten minutes ago
+        let attic = &digests[0];
+        assert_eq!(attic.readings.len(), 10);
+        assert_eq!(attic.outages.len(), 1);
+        assert_eq!(attic.outages[0].end, Some(attic.readings[0].reading_time()));
+
+        let garage = &digests[1];
+        assert!(garage.readings.is_empty());
+        assert_eq!(garage.outages.len(), 1);
+        assert_eq!(garage.outages[0].end, None);
+        assert_eq!(
+            garage.outages[0].duration_seconds,
+            Duration::hours(DIGEST_PERIOD_HOURS).num_seconds()
+        );
+        assert!(digest_body(&digests).contains("garage\n  No readings\n  Outages:\n    since "));
+    }
+
+    #[test]
+    fn digest_is_sent_at_the_next_digest_time() {
+        let time = NaiveTime::from_hms_opt(7, 0, 0).unwrap();
+        let morning = Local.with_ymd_and_hms(2025, 1, 1, 6, 0, 0).unwrap();
+        let evening = Local.with_ymd_and_hms(2025, 1, 1, 19, 0, 0).unwrap();
+
+        assert_eq!(next_digest(morning, time), morning + Duration::hours(1));
+        assert_eq!(next_digest(evening, time), evening + Duration::hours(12));
+    }
+
+    /// A mail server that accepts anything, returning the port it listens on and the commands
+    /// and message it was sent.
+    fn smtp_sink() -> (u16, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let sink = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut received = String::new();
+            writeln!(reader.get_mut(), "220 sink ready\r").unwrap();
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap() == 0 {
+                    break;
+                }
+                received.push_str(&line);
+                let reply = match line.split_whitespace().next().unwrap_or_default() {
+                    "EHLO" => "250-sink\r\n250 AUTH PLAIN LOGIN",
+                    "AUTH" => "235 authenticated",
+                    "DATA" => "354 go ahead",
+                    "QUIT" => "221 bye",
+                    "." => "250 queued",
+                    _ if received.contains("DATA\r\n") => continue,
+                    _ => "250 ok",
+                };
+                writeln!(reader.get_mut(), "{}\r", reply).unwrap();
+                if reply.starts_with("221") {
+                    break;
+                }
+            }
+            received
+        });
+        (port, sink)
+    }
+
+    #[test]
+    fn sends_to_a_local_smtp_sink() {
+        let (port, sink) = smtp_sink();
+
+        let email = config(&format!(
+            "server = '127.0.0.1'\nport = {}\nsecurity = 'none'\nfrom = 'Sensors <sensors@example.com>'\nto = ['team@example.com', 'me@example.com']",
+            port
+        ));
+        email.validate().unwrap();
+        let message = email
+            .message("garage humid", "Humidity at garage is 55.0 %".to_string())
+            .unwrap();
+        email.transport().unwrap().send(&message).unwrap();
+
+        let received = sink.join().unwrap();
+        assert!(received.contains("MAIL FROM:<sensors@example.com>"));
+        assert!(received.contains("RCPT TO:<me@example.com>"));
+        assert!(received.contains("Subject: garage humid"));
+        assert!(received.contains("Humidity at garage is 55.0 %"));
+        // there is nothing to log in with
+        assert!(!received.contains("AUTH"));
+    }
+
+    #[test]
+    fn logs_in_to_the_smtp_server() {
+        let (port, sink) = smtp_sink();
+
+        let email = config(&format!(
+            "server = '127.0.0.1'\nport = {}\nsecurity = 'none'\nusername = 'sensors'\npassword = 'secret'\nfrom = 'sensors@example.com'\nto = ['team@example.com']",
+            port
+        ));
+        email.validate().unwrap();
+        let message = email.message("digest", "No readings".to_string()).unwrap();
+        email.transport().unwrap().send(&message).unwrap();
+
+        let received = sink.join().unwrap();
+        // PLAIN sends "\0username\0password" base64 encoded
+        assert!(received.contains("AUTH PLAIN AHNlbnNvcnMAc2VjcmV0\r\n"));
+        assert!(received.find("AUTH").unwrap() < received.find("MAIL FROM").unwrap());
+    }
+
+    #[test]
+    fn config_is_checked() {
+        let base = "server = 'localhost'\nfrom = 'sensors@example.com'\nto = ['team@example.com']";
+        assert!(config(base).validate().is_ok());
+        assert!(config(&format!("{}\ndigest_time = '7am'", base))
+            .validate()
+            .is_err());
+        assert!(config(&format!("{}\nusername = 'sensors'", base))
+            .validate()
+            .is_err());
+        assert!(
+            config("server = 'localhost'\nfrom = 'sensors'\nto = ['team@example.com']")
+                .validate()
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,64 @@
+use crate::config::LocationConfig;
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// Whether a sensor is reporting as often as it is expected to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SensorHealth {
+    /// Reported within its late threshold
+    Ok,
+    /// Missed a few readings
+    Late,
+    /// Has not reported for longer than its missing threshold, or never has
+    Missing,
+}
+
+impl SensorHealth {
+    pub fn new(
+        last_seen: Option<DateTime<Local>>,
+        config: &LocationConfig,
+        now: DateTime<Local>,
+    ) -> Self {
+        match last_seen.map(|last_seen| now - last_seen) {
+            None => Self::Missing,
+            Some(age) if age > config.missing_after() => Self::Missing,
+            Some(age) if age > config.late_after() => Self::Late,
+            Some(_) => Self::Ok,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Ok => "ok",
+            Self::Late => "late",
+            Self::Missing => "missing",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn health_follows_the_location_thresholds() {
+        let config = LocationConfig {
+            reading_period_seconds: Some(30),
+            ..Default::default()
+        };
+        let now = Local::now();
+        let health = |seconds_ago: i64| {
+            SensorHealth::new(Some(now - Duration::seconds(seconds_ago)), &config, now)
+        };
+
+        assert_eq!(health(0), SensorHealth::Ok);
+        assert_eq!(health(90), SensorHealth::Ok);
+        assert_eq!(health(91), SensorHealth::Late);
+        assert_eq!(health(300), SensorHealth::Late);
+        assert_eq!(health(301), SensorHealth::Missing);
+        assert_eq!(SensorHealth::new(None, &config, now), SensorHealth::Missing);
+    }
+}
@@ -0,0 +1,88 @@
+use crate::config::LocationConfig;
+use crate::health::SensorHealth;
+use crate::location::Location;
+use crate::state::{LocationInfo, TemperatureServerState};
+use actix_web::http::StatusCode;
+use actix_web::{get, web, HttpResponseBuilder, Responder};
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+#[derive(Serialize, ToSchema)]
+pub struct LocationHealth {
+    location: Location,
+    health: SensorHealth,
+    /// When the location last reported, restored from its csv file when the server starts
+    last_seen: Option<DateTime<Local>>,
+    age_seconds: Option<i64>,
+    late_after_seconds: i64,
+    missing_after_seconds: i64,
+}
+
+impl LocationHealth {
+    pub fn new(
+        location: &Location,
+        location_info: &LocationInfo,
+        config: &LocationConfig,
+        now: DateTime<Local>,
+    ) -> Self {
+        let last_seen = location_info.get_last_modified().copied();
+
+        Self {
+            location: location.clone(),
+            health: SensorHealth::new(last_seen, config, now),
+            last_seen,
+            age_seconds: last_seen.map(|last_seen| (now - last_seen).num_seconds()),
+            late_after_seconds: config.late_after().num_seconds(),
+            missing_after_seconds: config.missing_after().num_seconds(),
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "readings",
+    responses(
+        (status = 200, description = "Whether every location is reporting on time", body = Vec<LocationHealth>),
+    )
+)]
+#[get("/api/v1/health")]
+pub async fn all_health_handler(state: web::Data<TemperatureServerState>) -> impl Responder {
+    let metadata = state.metadata.lock().await.clone();
+    let lock = state.file_buf_list.lock().await;
+    let now = Local::now();
+
+    let mut health = lock
+        .iter()
+        .map(|(location, location_info)| {
+            LocationHealth::new(location, location_info, &metadata.config(location), now)
+        })
+        .collect::<Vec<_>>();
+    health.sort_by(|a, b| a.location.cmp(&b.location));
+
+    HttpResponseBuilder::new(StatusCode::OK).json(health)
+}
+
+#[utoipa::path(
+    tag = "readings",
+    params(("location" = String, Path, description = "Location name")),
+    responses(
+        (status = 200, description = "Whether the location is reporting on time", body = LocationHealth),
+        (status = 404, description = "Unknown location"),
+    )
+)]
+#[get("/api/v1/locations/{location}/health")]
+pub async fn location_health_handler(
+    location: web::Path<String>,
+    state: web::Data<TemperatureServerState>,
+) -> impl Responder {
+    let location: Location = location.as_str().into();
+    let config = state.metadata.lock().await.config(&location);
+    let lock = state.file_buf_list.lock().await;
+
+    match lock.get(&location) {
+        None => HttpResponseBuilder::new(StatusCode::NOT_FOUND)
+            .body(format!("Unknown location: {}", location)),
+        Some(location_info) => HttpResponseBuilder::new(StatusCode::OK)
+            .json(LocationHealth::new(&location, location_info, &config, Local::now())),
+    }
+}
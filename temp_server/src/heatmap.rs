@@ -0,0 +1,203 @@
+use crate::metric::Metric;
+use crate::plot::{Drawable, PlotSize};
+use chrono::{Datelike, Duration, NaiveDate};
+use plotters::backend::DrawingBackend;
+use plotters::chart::ChartBuilder;
+use plotters::coord::Shift;
+use plotters::drawing::{DrawingArea, DrawingAreaErrorKind};
+use plotters::element::{Rectangle, Text};
+use plotters::prelude::{Color, IntoFont, RGBColor, BLACK, WHITE};
+use plotters::style::text_anchor::{HPos, Pos, VPos};
+use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
+
+// Days without any readings
+const MISSING_DAY: RGBColor = RGBColor(225, 225, 225);
+
+// Cold to hot, blue through pale yellow to red
+const TEMPERATURE_SCALE: [RGBColor; 3] = [
+    RGBColor(49, 54, 149),
+    RGBColor(255, 255, 191),
+    RGBColor(165, 0, 38),
+];
+
+// Dry to humid, pale to dark blue
+const HUMIDITY_SCALE: [RGBColor; 2] = [RGBColor(247, 251, 255), RGBColor(8, 48, 107)];
+
+// The number of steps drawn in the colour scale beside the calendar
+const SCALE_STEPS: usize = 50;
+
+const WEEKDAY_NAMES: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+
+/// Picks the colour `t` of the way along `stops`, where `t` is between 0 and 1.
+fn interpolate(stops: &[RGBColor], t: f64) -> RGBColor {
+    let t = t.clamp(0.0, 1.0) * (stops.len() - 1) as f64;
+    let idx = (t.floor() as usize).min(stops.len() - 2);
+    let fraction = t - idx as f64;
+
+    let mix =
+        |low: u8, high: u8| (low as f64 + (high as f64 - low as f64) * fraction).round() as u8;
+    let (RGBColor(r1, g1, b1), RGBColor(r2, g2, b2)) = (stops[idx], stops[idx + 1]);
+    RGBColor(mix(r1, r2), mix(g1, g2), mix(b1, b2))
+}
+
+/// A calendar of one value per day, one column per week like a GitHub contribution graph.
+pub struct Heatmap {
+    pub title: String,
+    pub metric: Metric,
+    /// Description of the values, shown beside the colour scale
+    pub description: String,
+    pub dates: RangeInclusive<NaiveDate>,
+    pub values: BTreeMap<NaiveDate, f32>,
+}
+
+impl Heatmap {
+    fn value_range(&self) -> (f32, f32) {
+        let (lowest, highest) = self
+            .values
+            .values()
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(low, high), v| {
+                (low.min(*v), high.max(*v))
+            });
+
+        if lowest > highest {
+            // nothing to draw
+            (0.0, 100.0)
+        } else if lowest == highest {
+            (lowest - 1.0, highest + 1.0)
+        } else {
+            (lowest, highest)
+        }
+    }
+
+    fn color(&self, value: f32) -> RGBColor {
+        let (lowest, highest) = self.value_range();
+        let t = (value - lowest) as f64 / (highest - lowest) as f64;
+        match self.metric {
+            Metric::Humidity | Metric::AbsoluteHumidity => interpolate(&HUMIDITY_SCALE, t),
+            _ => interpolate(&TEMPERATURE_SCALE, t),
+        }
+    }
+
+    fn days(&self) -> impl Iterator<Item = NaiveDate> + '_ {
+        self.dates
+            .start()
+            .iter_days()
+            .take_while(|date| date <= self.dates.end())
+    }
+
+    /// The week column and weekday row of a date, weeks start on sunday.
+    fn cell(&self, date: NaiveDate) -> (i64, u32) {
+        let first = *self.dates.start();
+        let first_sunday = first - Duration::days(first.weekday().num_days_from_sunday() as i64);
+        (
+            (date - first_sunday).num_days() / 7,
+            date.weekday().num_days_from_sunday(),
+        )
+    }
+}
+
+impl Drawable for Heatmap {
+    fn draw<DB: DrawingBackend>(
+        &self,
+        root: &DrawingArea<DB, Shift>,
+        size: PlotSize,
+    ) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+        let font = |font_size: u32| ("sans-serif", size.scaled(font_size)).into_font();
+
+        root.fill(&WHITE)?;
+        let titled = root.titled(&self.title, font(30))?;
+
+        let scale_width = size.scaled(110).min(size.width / 3);
+        let (calendar_area, scale_area) =
+            titled.split_horizontally(titled.dim_in_pixel().0 - scale_width);
+
+        let weeks = self.cell(*self.dates.end()).0 + 1;
+
+        // two columns on the left for weekday names and a row on top for month names,
+        // weekdays run down the rows so sunday is at the top
+        let mut calendar = ChartBuilder::on(&calendar_area)
+            .margin(size.scaled(10))
+            .build_cartesian_2d(-2f64..weeks as f64, 0f64..8f64)?;
+
+        let cells = self.days().map(|date| {
+            let (week, weekday) = self.cell(date);
+            let (x, y) = (week as f64, (6 - weekday) as f64);
+            let color = self
+                .values
+                .get(&date)
+                .map(|value| self.color(*value))
+                .unwrap_or(MISSING_DAY);
+            Rectangle::new([(x + 0.08, y + 0.08), (x + 0.92, y + 0.92)], color.filled())
+        });
+        calendar.draw_series(cells)?;
+
+        let label_style = font(14)
+            .color(&BLACK)
+            .pos(Pos::new(HPos::Left, VPos::Center));
+
+        // every other day, like the github graph, so they don't crowd small plots
+        let weekday_labels = WEEKDAY_NAMES
+            .iter()
+            .enumerate()
+            .filter(|(weekday, _)| weekday % 2 == 1)
+            .map(|(weekday, name)| {
+                Text::new(
+                    *name,
+                    (-1.8, (6 - weekday) as f64 + 0.5),
+                    label_style.clone(),
+                )
+            });
+        calendar.draw_series(weekday_labels)?;
+
+        let month_labels = self
+            .days()
+            .filter(|date| date.day() == 1 || date == self.dates.start())
+            .map(|date| {
+                Text::new(
+                    date.format("%b").to_string(),
+                    (self.cell(date).0 as f64, 7.5),
+                    label_style.clone(),
+                )
+            });
+        calendar.draw_series(month_labels)?;
+
+        let (lowest, highest) = self.value_range();
+        let step = (highest - lowest) / SCALE_STEPS as f32;
+        let mut scale = ChartBuilder::on(&scale_area)
+            .margin(size.scaled(10))
+            .y_label_area_size(size.scaled(60))
+            .build_cartesian_2d(0f32..1f32, lowest..highest)?;
+        scale
+            .configure_mesh()
+            .disable_x_axis()
+            .disable_mesh()
+            .y_labels(5)
+            .y_label_style(font(14))
+            .y_desc(&self.description)
+            .axis_desc_style(font(14))
+            .draw()?;
+        scale.draw_series((0..SCALE_STEPS).map(|idx| {
+            let low = lowest + step * idx as f32;
+            Rectangle::new(
+                [(0.0, low), (1.0, low + step)],
+                self.color(low + step / 2.0).filled(),
+            )
+        }))?;
+
+        root.present()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interpolate_hits_every_stop() {
+        assert_eq!(interpolate(&TEMPERATURE_SCALE, 0.0), TEMPERATURE_SCALE[0]);
+        assert_eq!(interpolate(&TEMPERATURE_SCALE, 0.5), TEMPERATURE_SCALE[1]);
+        assert_eq!(interpolate(&TEMPERATURE_SCALE, 1.0), TEMPERATURE_SCALE[2]);
+        assert_eq!(interpolate(&HUMIDITY_SCALE, 2.0), HUMIDITY_SCALE[1]);
+    }
+}
@@ -0,0 +1,191 @@
+use crate::aggregation::Aggregate;
+use crate::heatmap::Heatmap;
+use crate::location::Location;
+use crate::metric::Metric;
+use crate::plot::{render, PlotFormat, PlotSize};
+use crate::state::TemperatureServerState;
+use actix_web::http::header::{ACCEPT, VARY};
+use actix_web::http::StatusCode;
+use actix_web::{get, web, HttpRequest, HttpResponseBuilder, Responder};
+use chrono::{Duration, Local, NaiveDate};
+use serde::Deserialize;
+use tracing::error;
+use utoipa::IntoParams;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct HeatmapQuery {
+    /// temperature or humidity, defaults to temperature
+    #[param(inline)]
+    metric: Option<Metric>,
+    /// min, max or mean of each day, defaults to mean
+    #[param(inline)]
+    aggregate: Option<Aggregate>,
+    /// Calendar year to draw, defaults to the last 52 weeks
+    #[param(example = 2025)]
+    year: Option<i32>,
+    /// Image format, overrides the Accept header, defaults to svg
+    #[param(inline)]
+    format: Option<PlotFormat>,
+    /// Image width in pixels, defaults to 1000
+    width: Option<u32>,
+    /// Image height in pixels, defaults to 250
+    height: Option<u32>,
+    /// Scales text and lines, defaults to 96
+    dpi: Option<u32>,
+}
+
+#[utoipa::path(
+    tag = "plots",
+    params(
+        ("location" = String, Path, description = "Location name"),
+        HeatmapQuery,
+    ),
+    responses(
+        (status = 200, description = "Calendar of daily values for the location", content(
+            (String = "image/svg+xml"),
+            (Vec<u8> = "image/png"),
+            (Vec<u8> = "image/webp"),
+        )),
+        (status = 400, description = "Unsupported metric, aggregate or year"),
+        (status = 404, description = "Unknown location"),
+    )
+)]
+#[get("/heatmap/{location}")]
+pub async fn heatmap_handler(
+    req: HttpRequest,
+    location: web::Path<String>,
+    query: web::Query<HeatmapQuery>,
+    state: web::Data<TemperatureServerState>,
+) -> impl Responder {
+    let metric = query.metric.unwrap_or(Metric::Temperature);
+    let aggregate = query.aggregate.unwrap_or(Aggregate::Mean);
+    if !Metric::MEASURED.contains(&metric) {
+        return HttpResponseBuilder::new(StatusCode::BAD_REQUEST).body(format!(
+            "Heatmaps only show temperature or humidity, not {}",
+            metric
+        ));
+    }
+    if !matches!(aggregate, Aggregate::Min | Aggregate::Max | Aggregate::Mean) {
+        return HttpResponseBuilder::new(StatusCode::BAD_REQUEST).body(format!(
+            "Heatmaps only show the min, max or mean of a day, not {}",
+            aggregate.as_str()
+        ));
+    }
+
+    let dates = match query.year {
+        Some(year) => match (
+            NaiveDate::from_ymd_opt(year, 1, 1),
+            NaiveDate::from_ymd_opt(year, 12, 31),
+        ) {
+            (Some(first), Some(last)) => first..=last,
+            _ => {
+                return HttpResponseBuilder::new(StatusCode::BAD_REQUEST)
+                    .body(format!("Invalid year: {}", year))
+            }
+        },
+        None => {
+            let today = Local::now().date_naive();
+            (today - Duration::weeks(52))..=today
+        }
+    };
+
+    let location: Location = location.as_str().into();
+    let values = match state.get_statistics(&location).await {
+        Ok(Some(statistics)) => statistics
+            .days(Some(*dates.start()), Some(*dates.end()))
+            .into_iter()
+            .filter_map(|day| Some((day.date, day.value(metric, aggregate)?)))
+            .collect(),
+        Ok(None) => {
+            return HttpResponseBuilder::new(StatusCode::NOT_FOUND)
+                .body(format!("Unknown location: {}", location))
+        }
+        Err(err) => {
+            error!("Error reading statistics for {}: {}", location, err);
+            return HttpResponseBuilder::new(StatusCode::INTERNAL_SERVER_ERROR).finish();
+        }
+    };
+
+    let heatmap = Heatmap {
+        title: format!(
+            "Daily {} {} for: {}",
+            aggregate.as_str(),
+            metric.display_name().to_lowercase(),
+            location.as_str()
+        ),
+        metric,
+        description: format!("{} ({})", metric.display_name(), metric.unit()),
+        dates,
+        values,
+    };
+
+    let format = PlotFormat::negotiate(query.format, &req);
+    let size = PlotSize::new(
+        Some(query.width.unwrap_or(1000)),
+        Some(query.height.unwrap_or(250)),
+        query.dpi,
+    );
+
+    match render(heatmap, size, format).await {
+        Ok(content) => HttpResponseBuilder::new(StatusCode::OK)
+            .content_type(format.content_type())
+            .insert_header((VARY, ACCEPT.as_str()))
+            .body(content),
+        Err(err) => {
+            error!("Error drawing heatmap: {}", err);
+            HttpResponseBuilder::new(StatusCode::INTERNAL_SERVER_ERROR).finish()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{readings, TestFolder};
+    use actix_web::http::header::CONTENT_TYPE;
+    use actix_web::test::{call_service, init_service, read_body, TestRequest};
+    use actix_web::App;
+
+    #[actix_web::test]
+    async fn heatmaps_draw_the_requested_metric() {
+        let folder = TestFolder::new("heatmap-route");
+        folder.write_location("garage", &readings("garage", Local::now(), 10));
+        let app = init_service(App::new().app_data(folder.state()).service(heatmap_handler)).await;
+
+        let get = |uri: &'static str| {
+            let app = &app;
+            async move {
+                let resp = call_service(app, TestRequest::get().uri(uri).to_request()).await;
+                let status = resp.status();
+                let content_type = resp.headers().get(CONTENT_TYPE).cloned();
+                let body = String::from_utf8_lossy(&read_body(resp).await).to_string();
+                (status, content_type, body)
+            }
+        };
+
+        let (status, content_type, svg) = get("/heatmap/garage").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(content_type.unwrap(), "image/svg+xml");
+        assert!(svg.contains("Daily mean temperature for: garage"));
+        assert!(svg.contains("Temperature (F)"));
+
+        let (status, _, svg) = get("/heatmap/garage?metric=humidity&aggregate=max").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(svg.contains("Daily max humidity for: garage"));
+        assert!(svg.contains("Humidity (%)"));
+
+        let (status, content_type, _) = get("/heatmap/garage?format=png").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(content_type.unwrap(), "image/png");
+
+        for uri in [
+            "/heatmap/garage?metric=dew_point",
+            "/heatmap/garage?aggregate=median",
+            "/heatmap/garage?metric=pressure",
+        ] {
+            assert_eq!(get(uri).await.0, StatusCode::BAD_REQUEST, "{}", uri);
+        }
+        assert_eq!(get("/heatmap/attic").await.0, StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,142 @@
+use crate::config::LocationConfig;
+use crate::location::Location;
+use crate::reading::Reading;
+use crate::state::{LocationInfo, TemperatureServerState};
+use actix_web::http::StatusCode;
+use actix_web::{get, web, HttpResponseBuilder, Responder};
+use chrono::Local;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+#[derive(Serialize, ToSchema)]
+pub struct LatestReading {
+    location: Location,
+    reading: Option<Reading>,
+    age_seconds: Option<i64>,
+    /// The latest reading is older than the location's missing threshold
+    stale: bool,
+}
+
+impl LatestReading {
+    pub fn new(location: &Location, location_info: &LocationInfo, config: &LocationConfig) -> Self {
+        let reading = location_info.get_latest_reading().cloned();
+        let age_seconds = reading
+            .as_ref()
+            .map(|reading| (Local::now() - reading.reading_time()).num_seconds());
+
+        Self {
+            location: location.clone(),
+            reading,
+            age_seconds,
+            stale: age_seconds.is_none_or(|age| age > config.missing_after().num_seconds()),
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "readings",
+    responses(
+        (status = 200, description = "The latest reading of every location", body = Vec<LatestReading>),
+    )
+)]
+#[get("/api/v1/latest")]
+pub async fn all_latest_handler(state: web::Data<TemperatureServerState>) -> impl Responder {
+    let metadata = state.metadata.lock().await.clone();
+    let lock = state.file_buf_list.lock().await;
+
+    let mut latest = lock
+        .iter()
+        .map(|(location, location_info)| {
+            LatestReading::new(location, location_info, &metadata.config(location))
+        })
+        .collect::<Vec<_>>();
+    latest.sort_by(|a, b| a.location.cmp(&b.location));
+
+    HttpResponseBuilder::new(StatusCode::OK).json(latest)
+}
+
+#[utoipa::path(
+    tag = "readings",
+    params(("location" = String, Path, description = "Location name")),
+    responses(
+        (status = 200, description = "The latest reading of the location", body = LatestReading),
+        (status = 404, description = "Unknown location"),
+    )
+)]
+#[get("/api/v1/locations/{location}/latest")]
+pub async fn location_latest_handler(
+    location: web::Path<String>,
+    state: web::Data<TemperatureServerState>,
+) -> impl Responder {
+    let location: Location = location.as_str().into();
+    let config = state.metadata.lock().await.config(&location);
+    let lock = state.file_buf_list.lock().await;
+
+    match lock.get(&location) {
+        None => HttpResponseBuilder::new(StatusCode::NOT_FOUND)
+            .body(format!("Unknown location: {}", location)),
+        Some(location_info) => HttpResponseBuilder::new(StatusCode::OK)
+            .json(LatestReading::new(&location, location_info, &config)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{readings, TestFolder};
+    use actix_web::{test, App};
+    use serde_json::Value;
+
+    #[actix_web::test]
+    async fn latest_readings_are_listed_and_shown_on_the_dashboard() {
+        let folder = TestFolder::new("latest");
+        folder.write_location("garage", &readings("garage", Local::now(), 5));
+        folder.write_location(
+            "attic",
+            &readings("attic", Local::now() - chrono::Duration::days(2), 3),
+        );
+        let app = test::init_service(
+            App::new()
+                .app_data(folder.state())
+                .service(all_latest_handler)
+                .service(location_latest_handler)
+                .service(crate::main_page),
+        )
+        .await;
+
+        let latest: Value = test::call_and_read_body_json(
+            &app,
+            test::TestRequest::get().uri("/api/v1/latest").to_request(),
+        )
+        .await;
+        assert_eq!(latest[0]["location"], "attic");
+        assert_eq!(latest[0]["stale"], true);
+        assert_eq!(latest[1]["location"], "garage");
+        assert_eq!(latest[1]["reading"]["temperature"], 64.0);
+        assert_eq!(latest[1]["stale"], false);
+
+        let garage: Value = test::call_and_read_body_json(
+            &app,
+            test::TestRequest::get()
+                .uri("/api/v1/locations/garage/latest")
+                .to_request(),
+        )
+        .await;
+        assert_eq!(garage["reading"]["humidity"], 50.0);
+
+        let unknown = test::call_service(
+            &app,
+            test::TestRequest::get()
+                .uri("/api/v1/locations/cellar/latest")
+                .to_request(),
+        )
+        .await;
+        assert_eq!(unknown.status(), StatusCode::NOT_FOUND);
+
+        let page =
+            test::call_and_read_body(&app, test::TestRequest::get().uri("/").to_request()).await;
+        let page = String::from_utf8(page.to_vec()).unwrap();
+        assert!(page.contains("64.0 F"));
+        assert!(page.contains("50.0 %"));
+    }
+}
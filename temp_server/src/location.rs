@@ -1,7 +1,9 @@
+use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
 use std::path::PathBuf;
+use utoipa::ToSchema;
 
-#[derive(Clone, Hash, Eq, PartialEq, Debug)]
+#[derive(Clone, Hash, Eq, PartialEq, Ord, PartialOrd, Debug, Serialize, Deserialize, ToSchema)]
 pub struct Location(String);
 
 impl Location {
@@ -14,6 +16,31 @@ impl Location {
     }
 }
 
+/// Checks a name can be used for a location, which is also the name of its csv file.
+pub fn validate_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.trim() != name {
+        Err(format!(
+            "Invalid location name: {:?}, it can't be empty or start or end with spaces",
+            name
+        ))
+    } else if name.starts_with('.')
+        || name.contains(['/', '\\'])
+        || name.chars().any(char::is_control)
+    {
+        Err(format!(
+            "Invalid location name: {:?}, it can't start with a dot or contain slashes",
+            name
+        ))
+    } else if name.contains(".csv") {
+        Err(format!(
+            "Invalid location name: {:?}, it can't contain .csv",
+            name
+        ))
+    } else {
+        Ok(())
+    }
+}
+
 impl From<String> for Location {
     fn from(value: String) -> Self {
         Self(value)
@@ -31,3 +58,18 @@ impl Display for Location {
         write!(f, "{}", self.0.as_str())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_must_make_a_plain_file_name() {
+        assert!(validate_name("living room").is_ok());
+        assert!(validate_name("").is_err());
+        assert!(validate_name(" garage").is_err());
+        assert!(validate_name("../garage").is_err());
+        assert!(validate_name(".hidden").is_err());
+        assert!(validate_name("garage.csv").is_err());
+    }
+}
@@ -0,0 +1,229 @@
+use crate::location::Location;
+use crate::metadata::{LocationMetadata, TemperatureUnit};
+use crate::page::{render_page, SensorRow};
+use crate::state::TemperatureServerState;
+use crate::statistics::{DailySummary, Extreme};
+use crate::PLOT_PRESETS;
+use actix_web::http::StatusCode;
+use actix_web::{get, web, HttpResponseBuilder, Responder};
+use askama::Template;
+use chrono::Local;
+use tracing::error;
+
+// How many of the most recent days are listed on the location page
+const DAYS_SHOWN: usize = 31;
+
+#[derive(Template)]
+#[template(path = "location.html")]
+struct LocationTemplate<'a> {
+    location: &'a str,
+    metadata: LocationMetadata,
+    sensor: SensorRow,
+    // temperatures in the tables are shown in this
+    unit: TemperatureUnit,
+    // (name, description) of each record
+    records: [(&'static str, String); 7],
+    // most recent first
+    days: Vec<&'a DailySummary>,
+    presets: &'a [&'a str],
+}
+
+fn format_extreme(extreme: Option<&Extreme>, unit: &str) -> String {
+    extreme
+        .map(|extreme| {
+            format!(
+                "{:.1} {} at {}",
+                extreme.value,
+                unit,
+                extreme.time.format("%m/%d/%Y %I:%M %p")
+            )
+        })
+        .unwrap_or("-".to_string())
+}
+
+#[utoipa::path(
+    tag = "pages",
+    params(("location" = String, Path, description = "Location name")),
+    responses(
+        (status = 200, description = "Records, daily summaries and a plot of the location", content_type = "text/html", body = String),
+        (status = 404, description = "Unknown location"),
+    )
+)]
+#[get("/location/{location}")]
+pub async fn location_page_handler(
+    location: web::Path<String>,
+    state: web::Data<TemperatureServerState>,
+) -> impl Responder {
+    let location: Location = location.as_str().into();
+    let (metadata, config) = {
+        let store = state.metadata.lock().await;
+        (store.get(&location), store.config(&location))
+    };
+    let unit = metadata.unit();
+    let sensor = match state.file_buf_list.lock().await.get(&location) {
+        None => {
+            return HttpResponseBuilder::new(StatusCode::NOT_FOUND)
+                .body(format!("Unknown location: {}", location))
+        }
+        Some(location_info) => {
+            SensorRow::new(&location, location_info, &metadata, &config, Local::now())
+        }
+    };
+
+    let statistics = match state.get_statistics(&location).await {
+        Ok(Some(statistics)) => statistics,
+        Ok(None) => {
+            return HttpResponseBuilder::new(StatusCode::NOT_FOUND)
+                .body(format!("Unknown location: {}", location))
+        }
+        Err(err) => {
+            error!("Error reading statistics for {}: {}", location, err);
+            return HttpResponseBuilder::new(StatusCode::INTERNAL_SERVER_ERROR).finish();
+        }
+    };
+
+    let records = statistics.records();
+    let temperature = |extreme: Option<Extreme>| {
+        extreme.map(|extreme| Extreme {
+            value: unit.convert_fahrenheit(extreme.value),
+            ..extreme
+        })
+    };
+    let records = [
+        (
+            "Highest Temperature",
+            format_extreme(temperature(records.highest_temperature).as_ref(), unit.symbol()),
+        ),
+        (
+            "Lowest Temperature",
+            format_extreme(temperature(records.lowest_temperature).as_ref(), unit.symbol()),
+        ),
+        (
+            "Highest Humidity",
+            format_extreme(records.highest_humidity.as_ref(), "%"),
+        ),
+        (
+            "Lowest Humidity",
+            format_extreme(records.lowest_humidity.as_ref(), "%"),
+        ),
+        (
+            "Hottest Day",
+            records
+                .hottest_day
+                .map(|day| {
+                    format!(
+                        "{} (mean {:.1} {})",
+                        day.date,
+                        unit.convert_fahrenheit(day.temperature_mean),
+                        unit.symbol()
+                    )
+                })
+                .unwrap_or("-".to_string()),
+        ),
+        (
+            "Coldest Day",
+            records
+                .coldest_day
+                .map(|day| {
+                    format!(
+                        "{} (mean {:.1} {})",
+                        day.date,
+                        unit.convert_fahrenheit(day.temperature_mean),
+                        unit.symbol()
+                    )
+                })
+                .unwrap_or("-".to_string()),
+        ),
+        (
+            "Most Humid Hour",
+            records
+                .most_humid_hour
+                .map(|hour| {
+                    format!(
+                        "{} (mean {:.1} %)",
+                        hour.hour_start.format("%m/%d/%Y %I %p"),
+                        hour.mean
+                    )
+                })
+                .unwrap_or("-".to_string()),
+        ),
+    ];
+
+    render_page(&LocationTemplate {
+        location: location.as_str(),
+        metadata,
+        sensor,
+        unit,
+        records,
+        days: statistics
+            .days(None, None)
+            .into_iter()
+            .rev()
+            .take(DAYS_SHOWN)
+            .collect(),
+        presets: &PLOT_PRESETS,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{readings, TestFolder};
+    use actix_web::test::{call_service, init_service, read_body, TestRequest};
+    use actix_web::App;
+
+    const NASTY_NAME: &str = "<img src=x onerror=alert(1)>";
+
+    #[actix_web::test]
+    async fn pages_escape_what_they_show() {
+        let folder = TestFolder::new("location-page");
+        folder.write_location(NASTY_NAME, &readings(NASTY_NAME, Local::now(), 5));
+        let state = folder.state();
+        state
+            .metadata
+            .lock()
+            .await
+            .set(
+                &NASTY_NAME.into(),
+                LocationMetadata {
+                    description: Some("<script>alert(2)</script>".to_string()),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        let app = init_service(
+            App::new()
+                .app_data(state)
+                .service(location_page_handler)
+                .service(crate::main_page),
+        )
+        .await;
+
+        let page = |uri: &'static str| {
+            let app = &app;
+            async move {
+                let resp = call_service(app, TestRequest::get().uri(uri).to_request()).await;
+                assert_eq!(resp.status(), StatusCode::OK, "{}", uri);
+                String::from_utf8(read_body(resp).await.to_vec()).unwrap()
+            }
+        };
+
+        let location_page = page("/location/%3Cimg%20src=x%20onerror=alert(1)%3E").await;
+        let dashboard = page("/").await;
+        for html in [&location_page, &dashboard] {
+            assert!(html.contains(r#"<link rel="stylesheet" href="/static/style.css">"#));
+            assert!(html.contains("&#60;img src=x onerror=alert(1)&#62;"));
+            assert!(!html.contains(NASTY_NAME));
+            assert!(html.contains("%3Cimg%20src%3Dx%20onerror%3Dalert%281%29%3E"));
+        }
+        assert!(!location_page.contains("<script>alert(2)</script>"));
+
+        // the readings show up in the records and the daily summary
+        assert!(location_page.contains("Highest Temperature"));
+        assert!(location_page.contains("64.0 F"));
+
+        let resp = call_service(&app, TestRequest::get().uri("/location/attic").to_request()).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -1,22 +1,83 @@
+use crate::admin_route::{
+    archive_location_handler, archived_locations_handler, delete_location_handler,
+    merge_location_handler, rename_location_handler, restore_location_handler,
+};
+use crate::aggregation_route::aggregate_handler;
+use crate::alerts::AlertEvent;
+use crate::alerts_route::alerts_handler;
+use crate::chart_route::chart_page_handler;
+use crate::comparison_route::{comparison_handler, comparison_plot_handler};
+use crate::health_route::{all_health_handler, location_health_handler};
+use crate::health::SensorHealth;
+use crate::heatmap_route::heatmap_handler;
+use crate::latest_route::{all_latest_handler, location_latest_handler};
+use crate::location_page_route::location_page_handler;
+use crate::metadata_route::{metadata_handler, set_metadata_handler};
+use crate::notifier_route::deliveries_handler;
+use crate::openapi::openapi_handler;
+use crate::outage_route::outages_handler;
+use crate::page::{render_page, SensorRow};
 use crate::plotting_route::plot_location_handler;
+use crate::query_route::{export_csv_handler, readings_handler};
 use crate::reading_route::reading_handler;
-use actix_web::http::StatusCode;
-use actix_web::{web, HttpResponse};
+use crate::static_route::static_file_handler;
+use crate::statistics_route::statistics_handler;
+use crate::stream_route::reading_stream_handler;
+use actix_web::web;
 use actix_web::HttpServer;
-use actix_web::{get, App, HttpResponseBuilder, Responder};
+use actix_web::{get, App, Responder};
+use askama::Template;
 use chrono::Local;
 use state::TemperatureServerState;
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::PathBuf;
-use std::string::ToString;
 use std::sync::LazyLock;
-use tracing::info;
 
+mod admin;
+mod admin_route;
+mod aggregation;
+mod aggregation_route;
+mod alerts;
+mod alerts_route;
+mod chart_route;
+mod comparison_route;
+mod config;
+mod derived;
+mod downsampling;
+mod email;
+mod health;
+mod health_route;
+mod heatmap;
+mod heatmap_route;
+mod latest_route;
 mod location;
+mod location_page_route;
+mod metadata;
+mod metadata_route;
+mod metric;
+mod notifier;
+mod notifier_route;
+mod openapi;
+mod outage;
+mod outage_route;
+mod page;
+mod plot;
+mod plot_cache;
 mod plotting_route;
+mod query_route;
 mod reading;
 mod reading_route;
+mod rules;
 mod state;
+mod static_route;
+mod statistics;
+mod statistics_route;
+mod stream_route;
+#[cfg(test)]
+mod test_support;
+mod time_range;
+mod webhook;
 
 pub static LOG_FOLDER_PATH: LazyLock<PathBuf> = std::sync::LazyLock::new(|| {
     let p = PathBuf::from("./env_log");
@@ -26,12 +87,34 @@ pub static LOG_FOLDER_PATH: LazyLock<PathBuf> = std::sync::LazyLock::new(|| {
     p
 });
 
-pub static PLOTS_FOLDER_PATH: LazyLock<PathBuf> = std::sync::LazyLock::new(|| {
-    let p = LOG_FOLDER_PATH.join("plots");
-    if !p.exists() {
-        fs::create_dir(&p).unwrap();
-    }
-    p
+// Folder in the log folder for locations moved out of the way by the admin api, kept so they
+// can be restored
+pub const ARCHIVE_FOLDER_NAME: &str = "archive";
+
+// Display names, groups and the like for every location, edited through the api,
+// kept next to the location csv files
+pub const METADATA_FILE_NAME: &str = "metadata.json";
+
+// Every alert that started or stopped firing, one json object per line
+pub const ALERT_LOG_FILE_NAME: &str = "alerts.jsonl";
+
+// Every notification sent, or given up on, one json object per line
+pub const DELIVERY_LOG_FILE_NAME: &str = "deliveries.jsonl";
+
+// Time windows linked to from the dashboard, as `last` values for the plot route
+pub const PLOT_PRESETS: [&str; 3] = ["24h", "7d", "30d"];
+
+pub static CONFIG_PATH: LazyLock<PathBuf> = std::sync::LazyLock::new(|| {
+    std::env::var_os("TEMP_SERVER_CONFIG")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("./temp_server.toml"))
+});
+
+// Bearer token the admin api expects, the admin api is turned off without one
+pub static ADMIN_TOKEN: LazyLock<Option<String>> = std::sync::LazyLock::new(|| {
+    std::env::var("TEMP_SERVER_ADMIN_TOKEN")
+        .ok()
+        .filter(|token| !token.is_empty())
 });
 
 pub static BIND_PORT: LazyLock<u16> = std::sync::LazyLock::new(|| {
@@ -44,13 +127,53 @@ pub static BIND_PORT: LazyLock<u16> = std::sync::LazyLock::new(|| {
 async fn main() -> std::io::Result<()> {
     tracing_subscriber::fmt::init();
 
+    // load the config up front so a bad config file is reported before we start serving
+    LazyLock::force(&config::CONFIG);
+
     let app_state = web::Data::new(TemperatureServerState::default());
+    app_state.load_alert_history().await;
+
+    actix_web::rt::spawn(notifier::watch_health(app_state.clone()));
+    for webhook in &config::CONFIG.webhooks {
+        actix_web::rt::spawn(notifier::run_webhook(webhook.clone(), app_state.clone()));
+    }
+    if let Some(email) = &config::CONFIG.email {
+        actix_web::rt::spawn(email::run_email(email.clone(), app_state.clone()));
+        actix_web::rt::spawn(email::run_digest(email.clone(), app_state.clone()));
+    }
 
     HttpServer::new(move || {
         App::new()
             .app_data(app_state.clone())
             .service(reading_handler)
             .service(plot_location_handler)
+            .service(heatmap_handler)
+            .service(aggregate_handler)
+            .service(all_latest_handler)
+            .service(location_latest_handler)
+            .service(all_health_handler)
+            .service(location_health_handler)
+            .service(reading_stream_handler)
+            .service(metadata_handler)
+            .service(set_metadata_handler)
+            .service(rename_location_handler)
+            .service(merge_location_handler)
+            .service(archive_location_handler)
+            .service(restore_location_handler)
+            .service(delete_location_handler)
+            .service(archived_locations_handler)
+            .service(alerts_handler)
+            .service(deliveries_handler)
+            .service(statistics_handler)
+            .service(outages_handler)
+            .service(location_page_handler)
+            .service(chart_page_handler)
+            .service(static_file_handler)
+            .service(readings_handler)
+            .service(export_csv_handler)
+            .service(comparison_handler)
+            .service(comparison_plot_handler)
+            .service(openapi_handler)
             .service(main_page)
     })
     .bind(("0.0.0.0", *BIND_PORT))?
@@ -58,81 +181,61 @@ async fn main() -> std::io::Result<()> {
     .await
 }
 
-#[get("/")]
-async fn main_page(state: web::Data<TemperatureServerState>) -> impl Responder {
-    let main_page_content = {
-        let mut s = String::new();
-        s.push_str("<h1>All Sensors</h1>");
-        s.push_str("<table  style=\"border:1px solid black;\">");
-        s.push_str("<tr><th>Sensor Name</th><th>Last Modified</th></tr>");
-        let lock = state.file_buf_list.lock().await;
-
-        let location_info_list = lock
-            .iter()
-            .inspect(|(location, location_info)| {
-                let link = format!("/plot/{}", location.as_str());
-
-                let time_modified: Option<String> = location_info
-                    .get_last_modified()
-                    .map(|time| time.format("%m/%d/%Y %I:%M:%S %p").to_string());
-
-                s.push_str(&format!(
-                    r###"<tr><td style="border:1px solid black;"><a href="{}">{}</a></td> <td style="border:1px solid black;">{}</td></tr>"###,
-                    link,
-                    location.as_str(),
-                    time_modified.unwrap_or("Not modified".to_string())
-                ));
-            })
-            .collect::<Vec<_>>();
-
-        s.push_str("<br>");
-
-        location_info_list
-            .iter()
-            .filter(|(_, location_info)| {
-                location_info.get_last_modified().is_none()
-                    || location_info
-                        .get_last_modified()
-                        .is_some_and(|last_modified| {
-                            last_modified
-                                .signed_duration_since(Local::now())
-                                .num_minutes()
-                                > 10
-                        })
-            })
-            .for_each(|(location, location_info)| {
-                let mia_sensor_text = format!(
-                    "<b style=\"color:red; margin-bottom: 10px;\">MIA Sensor: {}, Last modified: {}</b><br>",
-                    location.as_str(),
-                    location_info
-                        .get_last_modified()
-                        .map(|time| time.format("%m/%d/%Y %I:%M:%S %p").to_string())
-                        .unwrap_or("Not modified".to_string())
-                );
-
-                s.push_str(&mia_sensor_text);
-            });
-        s.push_str("</table>");
-        s
-    };
-
-    info!("{}", main_page_content);
-
-    let mut resp = HttpResponseBuilder::new(StatusCode::OK)
-        .content_type("text/html")
-        .body(format!(r###"<!DOCTYPE html>
-    <html>
-        <head>
-            <title>Overview</title>
-        </head>
-        <body style="background: darkgrey;">
-            {}
-        </body>
-    </html>"###, main_page_content));
-
-    resp
-
+/// Sensors that share a group in their metadata, `name` is None for those without one.
+struct SensorGroup {
+    name: Option<String>,
+    sensors: Vec<SensorRow>,
+}
 
+#[derive(Template)]
+#[template(path = "index.html")]
+struct IndexTemplate {
+    groups: Vec<SensorGroup>,
+    firing: Vec<AlertEvent>,
+    presets: &'static [&'static str],
+}
 
+#[utoipa::path(
+    tag = "pages",
+    responses(
+        (status = 200, description = "Overview of every sensor, grouped by the group in their metadata", content_type = "text/html", body = String),
+    )
+)]
+#[get("/")]
+async fn main_page(state: web::Data<TemperatureServerState>) -> impl Responder {
+    let now = Local::now();
+    let metadata = state.metadata.lock().await.clone();
+
+    let mut groups: BTreeMap<Option<String>, Vec<SensorRow>> = BTreeMap::new();
+    for (location, location_info) in state.file_buf_list.lock().await.iter() {
+        let location_metadata = metadata.get(location);
+        groups
+            .entry(location_metadata.group.clone())
+            .or_default()
+            .push(SensorRow::new(
+                location,
+                location_info,
+                &location_metadata,
+                &metadata.config(location),
+                now,
+            ));
+    }
 
+    // named groups first, then the sensors without one
+    let mut groups = groups
+        .into_iter()
+        .map(|(name, mut sensors)| {
+            sensors.sort_by_cached_key(|sensor| (sensor.name.to_lowercase(), sensor.location.clone()));
+            SensorGroup { name, sensors }
+        })
+        .collect::<Vec<_>>();
+    groups.sort_by_key(|group| group.name.is_none());
+
+    let firing = state.alerts.lock().await.firing();
+
+    render_page(&IndexTemplate {
+        groups,
+        firing,
+        presets: &PLOT_PRESETS,
+    })
 }
@@ -0,0 +1,225 @@
+use crate::config::{LocationConfig, CONFIG};
+use crate::derived::fahrenheit_to_celsius;
+use crate::location::Location;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use tracing::info;
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TemperatureUnit {
+    #[default]
+    Fahrenheit,
+    Celsius,
+}
+
+impl TemperatureUnit {
+    /// Converts a temperature in Fahrenheit, which is how every reading is stored.
+    pub fn convert_fahrenheit(&self, fahrenheit: f32) -> f32 {
+        match self {
+            Self::Fahrenheit => fahrenheit,
+            Self::Celsius => fahrenheit_to_celsius(fahrenheit),
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Self::Fahrenheit => "F",
+            Self::Celsius => "C",
+        }
+    }
+}
+
+/// Details about a location that the sensor itself doesn't know, edited through the api.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct LocationMetadata {
+    /// Shown instead of the location name
+    pub display_name: Option<String>,
+    pub description: Option<String>,
+    /// Floor or room the location is in, the dashboard lists each group together
+    pub group: Option<String>,
+    pub tags: Vec<String>,
+    /// How often the sensor reports, overrides the config file
+    pub reading_period_seconds: Option<u32>,
+    /// Unit temperatures are shown in on the pages
+    pub unit: Option<TemperatureUnit>,
+    /// Colour of the location on the dashboard, as #rrggbb
+    #[schema(example = "#ff8800")]
+    pub color: Option<String>,
+}
+
+impl LocationMetadata {
+    /// Trims the text fields, dropping any left empty, and checks the rest.
+    pub fn validate(mut self) -> Result<Self, String> {
+        let trim = |text: Option<String>| {
+            text.map(|text| text.trim().to_string())
+                .filter(|text| !text.is_empty())
+        };
+
+        self.display_name = trim(self.display_name);
+        self.description = trim(self.description);
+        self.group = trim(self.group);
+        self.color = trim(self.color);
+
+        let mut tags = vec![];
+        for tag in self.tags.iter().map(|tag| tag.trim()) {
+            if !tag.is_empty() && !tags.iter().any(|existing| existing == tag) {
+                tags.push(tag.to_string());
+            }
+        }
+        self.tags = tags;
+
+        if self.reading_period_seconds == Some(0) {
+            return Err("reading_period_seconds must be more than 0".to_string());
+        }
+
+        if let Some(color) = &self.color {
+            let hex = color.strip_prefix('#').unwrap_or_default();
+            if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(format!("Invalid color: {}, expected #rrggbb", color));
+            }
+        }
+
+        Ok(self)
+    }
+
+    pub fn display_name<'a>(&'a self, location: &'a Location) -> &'a str {
+        self.display_name.as_deref().unwrap_or(location.as_str())
+    }
+
+    pub fn unit(&self) -> TemperatureUnit {
+        self.unit.unwrap_or_default()
+    }
+}
+
+/// The metadata of every location, kept in a json file next to the location csv files.
+#[derive(Debug, Clone, Default)]
+pub struct MetadataStore {
+    path: PathBuf,
+    locations: BTreeMap<String, LocationMetadata>,
+}
+
+impl MetadataStore {
+    /// A missing file is the same as an empty one, but one that does not parse stops the server
+    /// from starting rather than being overwritten by the next edit.
+    pub fn load(path: PathBuf) -> Self {
+        let locations = match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .unwrap_or_else(|err| panic!("Invalid metadata file {}: {}", path.display(), err)),
+            Err(_) => {
+                info!("No metadata file at {}", path.display());
+                BTreeMap::new()
+            }
+        };
+
+        Self { path, locations }
+    }
+
+    pub fn get(&self, location: &Location) -> LocationMetadata {
+        self.locations
+            .get(location.as_str())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// The config of a location, with anything set in its metadata taking precedence.
+    pub fn config(&self, location: &Location) -> LocationConfig {
+        let mut config = CONFIG.location(location);
+        if let Some(period) = self
+            .locations
+            .get(location.as_str())
+            .and_then(|metadata| metadata.reading_period_seconds)
+        {
+            config.reading_period_seconds = Some(period);
+        }
+        config
+    }
+
+    /// Replaces the metadata of a location and writes the file.
+    pub async fn set(
+        &mut self,
+        location: &Location,
+        metadata: LocationMetadata,
+    ) -> std::io::Result<()> {
+        let mut locations = self.locations.clone();
+        if metadata == LocationMetadata::default() {
+            locations.remove(location.as_str());
+        } else {
+            locations.insert(location.as_str().to_string(), metadata);
+        }
+        self.save(locations).await
+    }
+
+    /// Moves the metadata of a location to its new name, replacing any the new name had.
+    pub async fn rename(&mut self, from: &Location, to: &Location) -> std::io::Result<()> {
+        let mut locations = self.locations.clone();
+        locations.remove(to.as_str());
+        if let Some(metadata) = locations.remove(from.as_str()) {
+            locations.insert(to.as_str().to_string(), metadata);
+        }
+        self.save(locations).await
+    }
+
+    pub async fn remove(&mut self, location: &Location) -> std::io::Result<()> {
+        if !self.locations.contains_key(location.as_str()) {
+            return Ok(());
+        }
+
+        let mut locations = self.locations.clone();
+        locations.remove(location.as_str());
+        self.save(locations).await
+    }
+
+    async fn save(&mut self, locations: BTreeMap<String, LocationMetadata>) -> std::io::Result<()> {
+        // write a copy and move it into place, so a failed write never leaves half a file
+        let contents = serde_json::to_string_pretty(&locations)?;
+        let temporary_path = self.path.with_extension("json.tmp");
+        tokio::fs::write(&temporary_path, contents).await?;
+        tokio::fs::rename(&temporary_path, &self.path).await?;
+
+        self.locations = locations;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_tidies_and_checks_fields() {
+        let metadata = LocationMetadata {
+            display_name: Some("  Garage ".to_string()),
+            description: Some("   ".to_string()),
+            tags: vec![
+                " outside".to_string(),
+                "".to_string(),
+                "outside".to_string(),
+            ],
+            color: Some("#FF8800".to_string()),
+            ..Default::default()
+        }
+        .validate()
+        .unwrap();
+
+        assert_eq!(metadata.display_name.as_deref(), Some("Garage"));
+        assert_eq!(metadata.description, None);
+        assert_eq!(metadata.tags, vec!["outside".to_string()]);
+
+        let bad_color = LocationMetadata {
+            color: Some("orange".to_string()),
+            ..Default::default()
+        };
+        assert!(bad_color.validate().is_err());
+
+        let no_period = LocationMetadata {
+            reading_period_seconds: Some(0),
+            ..Default::default()
+        };
+        assert!(no_period.validate().is_err());
+    }
+}
@@ -0,0 +1,148 @@
+use crate::admin_route::reject_without_admin_token;
+use crate::location::Location;
+use crate::metadata::LocationMetadata;
+use crate::state::TemperatureServerState;
+use actix_web::http::StatusCode;
+use actix_web::{get, put, web, HttpRequest, HttpResponseBuilder, Responder};
+use tracing::{error, info};
+
+#[utoipa::path(
+    tag = "locations",
+    params(("location" = String, Path, description = "Location name")),
+    responses(
+        (status = 200, description = "Metadata of the location, every field is empty until it is set", body = LocationMetadata),
+        (status = 404, description = "Unknown location"),
+    )
+)]
+#[get("/api/v1/locations/{location}/metadata")]
+pub async fn metadata_handler(
+    location: web::Path<String>,
+    state: web::Data<TemperatureServerState>,
+) -> impl Responder {
+    let location: Location = location.as_str().into();
+
+    if !state.file_buf_list.lock().await.contains_key(&location) {
+        return HttpResponseBuilder::new(StatusCode::NOT_FOUND)
+            .body(format!("Unknown location: {}", location));
+    }
+
+    HttpResponseBuilder::new(StatusCode::OK).json(state.metadata.lock().await.get(&location))
+}
+
+#[utoipa::path(
+    tag = "locations",
+    params(("location" = String, Path, description = "Location name")),
+    request_body = LocationMetadata,
+    security(("admin_token" = [])),
+    responses(
+        (status = 200, description = "Metadata replaced, the stored metadata is returned", body = LocationMetadata),
+        (status = 400, description = "Invalid metadata"),
+        (status = 401, description = "Missing or wrong admin token"),
+        (status = 403, description = "The admin api is turned off"),
+        (status = 404, description = "Unknown location"),
+        (status = 500, description = "The metadata file could not be written"),
+    )
+)]
+#[put("/api/v1/locations/{location}/metadata")]
+pub async fn set_metadata_handler(
+    req: HttpRequest,
+    location: web::Path<String>,
+    metadata: web::Json<LocationMetadata>,
+    state: web::Data<TemperatureServerState>,
+) -> impl Responder {
+    if let Some(response) = reject_without_admin_token(&req, &state) {
+        return response;
+    }
+
+    let location: Location = location.as_str().into();
+
+    let metadata = match metadata.into_inner().validate() {
+        Ok(metadata) => metadata,
+        Err(err) => return HttpResponseBuilder::new(StatusCode::BAD_REQUEST).body(err),
+    };
+
+    if !state.file_buf_list.lock().await.contains_key(&location) {
+        return HttpResponseBuilder::new(StatusCode::NOT_FOUND)
+            .body(format!("Unknown location: {}", location));
+    }
+
+    match state.metadata.lock().await.set(&location, metadata.clone()).await {
+        Ok(()) => {
+            info!("Updated metadata of {}", location);
+            HttpResponseBuilder::new(StatusCode::OK).json(metadata)
+        }
+        Err(err) => {
+            error!("Error writing metadata of {}: {}", location, err);
+            HttpResponseBuilder::new(StatusCode::INTERNAL_SERVER_ERROR).finish()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{readings, TestFolder};
+    use actix_web::http::header::AUTHORIZATION;
+    use actix_web::test::{call_and_read_body_json, call_service, init_service, TestRequest};
+    use actix_web::App;
+    use chrono::Local;
+
+    #[actix_web::test]
+    async fn metadata_is_only_changed_with_the_admin_token() {
+        let folder = TestFolder::new("metadata-route");
+        folder.write_location("attic", &readings("attic", Local::now(), 1));
+        let app = init_service(
+            App::new()
+                .app_data(folder.admin_state("secret"))
+                .service(metadata_handler)
+                .service(set_metadata_handler),
+        )
+        .await;
+        let metadata = LocationMetadata {
+            display_name: Some("Attic".to_string()),
+            ..Default::default()
+        };
+        let put = |token: Option<&str>| {
+            let mut request = TestRequest::put()
+                .uri("/api/v1/locations/attic/metadata")
+                .set_json(&metadata);
+            if let Some(token) = token {
+                request = request.insert_header((AUTHORIZATION, token));
+            }
+            request.to_request()
+        };
+        let stored = || {
+            call_and_read_body_json::<_, _, LocationMetadata>(
+                &app,
+                TestRequest::get()
+                    .uri("/api/v1/locations/attic/metadata")
+                    .to_request(),
+            )
+        };
+
+        for token in [
+            None,
+            Some("Bearer not-the-token"),
+            Some("Bearer secre"),
+            Some("secret"),
+        ] {
+            let resp = call_service(&app, put(token)).await;
+            assert_eq!(resp.status(), StatusCode::UNAUTHORIZED, "{:?}", token);
+        }
+        assert_eq!(stored().await, LocationMetadata::default());
+
+        let resp = call_service(&app, put(Some("Bearer secret"))).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(stored().await, metadata);
+
+        // without a token the admin api is turned off
+        let app = init_service(
+            App::new()
+                .app_data(folder.state())
+                .service(set_metadata_handler),
+        )
+        .await;
+        let resp = call_service(&app, put(Some("Bearer secret"))).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    }
+}
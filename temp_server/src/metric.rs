@@ -0,0 +1,100 @@
+use crate::derived::{absolute_humidity, dew_point, heat_index, humidex};
+use crate::reading::Reading;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use utoipa::ToSchema;
+
+#[derive(Clone, Copy, Hash, Eq, PartialEq, Ord, PartialOrd, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    Temperature,
+    Humidity,
+    DewPoint,
+    AbsoluteHumidity,
+    HeatIndex,
+    Humidex,
+}
+
+impl Metric {
+    pub const ALL: [Metric; 6] = [
+        Metric::Temperature,
+        Metric::Humidity,
+        Metric::DewPoint,
+        Metric::AbsoluteHumidity,
+        Metric::HeatIndex,
+        Metric::Humidex,
+    ];
+
+    /// The metrics the sensors actually measure, everything else is derived from these
+    pub const MEASURED: [Metric; 2] = [Metric::Temperature, Metric::Humidity];
+
+    pub fn value(&self, reading: &Reading) -> f32 {
+        let (t, rh) = (reading.temperature(), reading.humidity());
+        match self {
+            Metric::Temperature => t,
+            Metric::Humidity => rh,
+            Metric::DewPoint => dew_point(t, rh),
+            Metric::AbsoluteHumidity => absolute_humidity(t, rh),
+            Metric::HeatIndex => heat_index(t, rh),
+            Metric::Humidex => humidex(t, rh),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Metric::Temperature => "temperature",
+            Metric::Humidity => "humidity",
+            Metric::DewPoint => "dew_point",
+            Metric::AbsoluteHumidity => "absolute_humidity",
+            Metric::HeatIndex => "heat_index",
+            Metric::Humidex => "humidex",
+        }
+    }
+
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            Metric::Temperature => "Temperature",
+            Metric::Humidity => "Humidity",
+            Metric::DewPoint => "Dew Point",
+            Metric::AbsoluteHumidity => "Absolute Humidity",
+            Metric::HeatIndex => "Heat Index",
+            Metric::Humidex => "Humidex",
+        }
+    }
+
+    pub fn unit(&self) -> &'static str {
+        match self {
+            Metric::Temperature | Metric::DewPoint | Metric::HeatIndex => "F",
+            Metric::Humidity => "%",
+            Metric::AbsoluteHumidity => "g/m³",
+            Metric::Humidex => "",
+        }
+    }
+
+    /// Parses a comma separated list of metrics such as "temperature,humidity"
+    pub fn parse_list(list: &str) -> Result<Vec<Metric>, String> {
+        list.split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(Metric::from_str)
+            .collect()
+    }
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Metric::ALL
+            .into_iter()
+            .find(|metric| metric.as_str() == s)
+            .ok_or_else(|| format!("Unknown metric: {}", s))
+    }
+}
+
+impl Display for Metric {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
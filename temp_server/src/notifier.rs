@@ -0,0 +1,376 @@
+use crate::alerts::AlertEvent;
+use crate::health::SensorHealth;
+use crate::location::Location;
+use crate::state::TemperatureServerState;
+use crate::webhook::{self, WebhookConfig};
+use actix_web::web;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Display;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::Receiver;
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+use tracing::{error, info, warn};
+use utoipa::ToSchema;
+
+// How often the health of every location is checked for sensors that stopped or started reporting
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+// How many of the latest deliveries are kept in memory to be listed
+const DELIVERY_HISTORY_CAPACITY: usize = 500;
+
+// How many notifications can wait for a notifier that is busy sending, later ones are dropped
+// and logged as undelivered
+const NOTIFIER_QUEUE_CAPACITY: usize = 1000;
+
+// The longest wait between two attempts to send a notification, however many failed before
+pub const MAX_RETRY_DELAY: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    Alert,
+    Health,
+    /// The daily email digest, which is only sent by email at its own time
+    Digest,
+}
+
+/// A sensor going late or missing, or reporting again after it was.
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthEvent {
+    pub location: Location,
+    pub health: SensorHealth,
+    pub previous: SensorHealth,
+    pub last_seen: Option<DateTime<Local>>,
+    pub time: DateTime<Local>,
+    pub message: String,
+}
+
+/// Something worth telling people about, sent to every notifier that wants its kind.
+/// Serialized with a `kind` field next to the fields of the event.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Notification {
+    Alert(AlertEvent),
+    Health(HealthEvent),
+}
+
+impl Notification {
+    pub fn kind(&self) -> NotificationKind {
+        match self {
+            Notification::Alert(_) => NotificationKind::Alert,
+            Notification::Health(_) => NotificationKind::Health,
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            Notification::Alert(event) => &event.message,
+            Notification::Health(event) => &event.message,
+        }
+    }
+}
+
+/// The outcome of sending one notification to one notifier.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Delivery {
+    /// Name of the notifier from the config file
+    pub notifier: String,
+    pub kind: NotificationKind,
+    pub message: String,
+    /// When the last attempt finished
+    pub time: DateTime<Local>,
+    pub attempts: u32,
+    pub delivered: bool,
+    /// Why the last attempt failed
+    pub error: Option<String>,
+}
+
+/// Every delivery, appended to a json lines file with the latest ones kept in memory.
+pub struct DeliveryLog {
+    path: PathBuf,
+    // oldest first
+    recent: VecDeque<Delivery>,
+}
+
+impl DeliveryLog {
+    pub fn load(path: PathBuf) -> Self {
+        let mut recent = VecDeque::new();
+
+        if let Ok(contents) = fs::read_to_string(&path) {
+            for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+                match serde_json::from_str::<Delivery>(line) {
+                    Ok(delivery) => recent.push_back(delivery),
+                    Err(err) => warn!("Bad line in {}: {}", path.display(), err),
+                }
+                if recent.len() > DELIVERY_HISTORY_CAPACITY {
+                    recent.pop_front();
+                }
+            }
+        }
+
+        Self { path, recent }
+    }
+
+    /// The latest deliveries, newest first.
+    pub fn recent(&self, limit: usize) -> Vec<Delivery> {
+        self.recent.iter().rev().take(limit).cloned().collect()
+    }
+
+    pub async fn record(&mut self, delivery: Delivery) {
+        if let Err(err) = self.append(&delivery).await {
+            error!(
+                "Error writing delivery log {}: {}",
+                self.path.display(),
+                err
+            );
+        }
+
+        self.recent.push_back(delivery);
+        while self.recent.len() > DELIVERY_HISTORY_CAPACITY {
+            self.recent.pop_front();
+        }
+    }
+
+    async fn append(&self, delivery: &Delivery) -> std::io::Result<()> {
+        let line = serde_json::to_string(delivery)? + "\n";
+        tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await?
+            .write_all(line.as_bytes())
+            .await
+    }
+}
+
+/// How many times a notification is sent before giving up, and how long to wait in between.
+#[derive(Debug, Clone, Copy)]
+pub struct Retries {
+    pub max_attempts: u32,
+    /// Before the first retry, doubling after every failed attempt up to [`MAX_RETRY_DELAY`]
+    pub first_delay: Duration,
+}
+
+impl Retries {
+    /// Defaults to 5 attempts, retrying first after 5 seconds.
+    pub fn new(max_attempts: Option<u32>, retry_after_seconds: Option<u64>) -> Self {
+        Self {
+            max_attempts: max_attempts.unwrap_or(5).max(1),
+            first_delay: Duration::from_secs(retry_after_seconds.unwrap_or(5)),
+        }
+    }
+
+    /// How long to wait after `attempt` (starting at 1) failed.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        2u32.checked_pow(attempt.saturating_sub(1))
+            .and_then(|factor| self.first_delay.checked_mul(factor))
+            .map_or(MAX_RETRY_DELAY, |delay| delay.min(MAX_RETRY_DELAY))
+    }
+
+    /// Runs `send` on the blocking thread pool until it succeeds, fails in a way that sending
+    /// again will not fix, or runs out of attempts. Returns the delivery for the log.
+    pub async fn send<E>(
+        &self,
+        notifier: &str,
+        kind: NotificationKind,
+        message: &str,
+        send: impl Fn() -> Result<(), E> + Clone + Send + 'static,
+        worth_retrying: impl Fn(&E) -> bool,
+    ) -> Delivery
+    where
+        E: Display + Send + 'static,
+    {
+        let mut attempts = 0;
+        let error = loop {
+            attempts += 1;
+            let err = match web::block(send.clone()).await {
+                Ok(Ok(())) => break None,
+                Ok(Err(err)) => err,
+                Err(err) => {
+                    error!("Error sending to {}: {}", notifier, err);
+                    break Some(err.to_string());
+                }
+            };
+            warn!(
+                "Attempt {} to send to {} failed: {}",
+                attempts, notifier, err
+            );
+            if attempts >= self.max_attempts || !worth_retrying(&err) {
+                break Some(err.to_string());
+            }
+            tokio::time::sleep(self.delay(attempts)).await;
+        };
+
+        if error.is_none() {
+            info!("Sent {:?} to {}", kind, notifier);
+        }
+        Delivery {
+            notifier: notifier.to_string(),
+            kind,
+            message: message.to_string(),
+            time: Local::now(),
+            attempts,
+            delivered: error.is_none(),
+            error,
+        }
+    }
+}
+
+/// Posts every notification the webhook wants to it, one at a time so they arrive in order.
+pub async fn run_webhook(webhook: WebhookConfig, state: web::Data<TemperatureServerState>) {
+    let wanted = webhook.clone();
+    let mut queue = queue_notifications(&state, &webhook.name, move |notification| {
+        wanted.wants(notification)
+    });
+    let agent = webhook::agent();
+    let retries = webhook.retries();
+
+    while let Some(notification) = queue.recv().await {
+        let (agent, request_webhook) = (agent.clone(), webhook.clone());
+        let body = webhook.body(&notification);
+        let delivery = retries
+            .send(
+                &webhook.name,
+                notification.kind(),
+                notification.message(),
+                move || webhook::send(&agent, &request_webhook, &body),
+                webhook::worth_retrying,
+            )
+            .await;
+        state.deliveries.lock().await.record(delivery).await;
+    }
+}
+
+/// Takes every notification `wants` picks off the notification channel as soon as it is sent,
+/// and queues it for the notifier. A notifier waiting to retry a failed send would otherwise fall
+/// behind the channel and miss notifications. The queue ends once the server is shutting down.
+pub fn queue_notifications(
+    state: &web::Data<TemperatureServerState>,
+    notifier: &str,
+    wants: impl Fn(&Notification) -> bool + 'static,
+) -> mpsc::Receiver<Notification> {
+    let mut notifications = state.notifications.subscribe();
+    let (queue, queued) = mpsc::channel(NOTIFIER_QUEUE_CAPACITY);
+    let (state, notifier) = (state.clone(), notifier.to_string());
+
+    actix_web::rt::spawn(async move {
+        while let Some(notification) = next_notification(&mut notifications, &notifier).await {
+            if !wants(&notification) {
+                continue;
+            }
+            match queue.try_send(notification) {
+                Ok(()) => {}
+                Err(TrySendError::Full(notification)) => {
+                    warn!(
+                        "Notifier {} has too many notifications waiting, dropped: {}",
+                        notifier,
+                        notification.message()
+                    );
+                    let delivery = Delivery {
+                        notifier: notifier.clone(),
+                        kind: notification.kind(),
+                        message: notification.message().to_string(),
+                        time: Local::now(),
+                        attempts: 0,
+                        delivered: false,
+                        error: Some("Too many notifications were waiting to be sent".to_string()),
+                    };
+                    state.deliveries.lock().await.record(delivery).await;
+                }
+                Err(TrySendError::Closed(_)) => return,
+            }
+        }
+    });
+
+    queued
+}
+
+/// Waits for the next notification, None once the server is shutting down.
+async fn next_notification(
+    notifications: &mut Receiver<Notification>,
+    notifier: &str,
+) -> Option<Notification> {
+    loop {
+        match notifications.recv().await {
+            Ok(notification) => return Some(notification),
+            Err(RecvError::Closed) => return None,
+            Err(RecvError::Lagged(skipped)) => warn!(
+                "Notifier {} fell behind, skipped {} notifications",
+                notifier, skipped
+            ),
+        }
+    }
+}
+
+/// Sends a health notification whenever a location goes late or missing, or starts reporting
+/// again. Locations are only compared with how they were when the server, or they, first
+/// showed up, so a restart does not notify about every sensor that was already missing.
+pub async fn watch_health(state: web::Data<TemperatureServerState>) {
+    let mut interval = tokio::time::interval(HEALTH_CHECK_INTERVAL);
+    let mut previous: HashMap<Location, SensorHealth> = HashMap::new();
+
+    loop {
+        interval.tick().await;
+        let now = Local::now();
+        let metadata = state.metadata.lock().await.clone();
+
+        let current = state
+            .file_buf_list
+            .lock()
+            .await
+            .iter()
+            .map(|(location, location_info)| {
+                let last_seen = location_info.get_last_modified().copied();
+                let config = metadata.config(location);
+                (
+                    location.clone(),
+                    (SensorHealth::new(last_seen, &config, now), last_seen),
+                )
+            })
+            .collect::<HashMap<_, _>>();
+
+        for (location, (health, last_seen)) in &current {
+            let Some(&was) = previous.get(location) else {
+                continue;
+            };
+            if was == *health {
+                continue;
+            }
+
+            let name = metadata.get(location).display_name(location).to_string();
+            let message = match (health, last_seen) {
+                (SensorHealth::Ok, _) => format!("{} is reporting again", name),
+                (_, Some(last_seen)) => format!(
+                    "{} is {}, last seen {}",
+                    name,
+                    health.as_str(),
+                    last_seen.format("%m/%d/%Y %I:%M %p")
+                ),
+                (_, None) => format!("{} is {}", name, health.as_str()),
+            };
+            info!("{}", message);
+
+            // there being no notifiers is not an error
+            let _ = state.notifications.send(Notification::Health(HealthEvent {
+                location: location.clone(),
+                health: *health,
+                previous: was,
+                last_seen: *last_seen,
+                time: now,
+                message,
+            }));
+        }
+
+        previous = current
+            .into_iter()
+            .map(|(location, (health, _))| (location, health))
+            .collect();
+    }
+}
@@ -0,0 +1,37 @@
+use crate::notifier::Delivery;
+use crate::state::TemperatureServerState;
+use actix_web::http::StatusCode;
+use actix_web::{get, web, HttpResponseBuilder, Responder};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+// How many deliveries are listed when the request does not say
+const DEFAULT_DELIVERY_LIMIT: usize = 50;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeliveriesQuery {
+    /// How many of the latest deliveries to list, defaults to 50
+    limit: Option<usize>,
+}
+
+#[utoipa::path(
+    tag = "alerts",
+    params(DeliveriesQuery),
+    responses(
+        (status = 200, description = "The latest notifications sent to the notifiers, newest first", body = Vec<Delivery>),
+    )
+)]
+#[get("/api/v1/notifications/deliveries")]
+pub async fn deliveries_handler(
+    query: web::Query<DeliveriesQuery>,
+    state: web::Data<TemperatureServerState>,
+) -> impl Responder {
+    let deliveries = state
+        .deliveries
+        .lock()
+        .await
+        .recent(query.limit.unwrap_or(DEFAULT_DELIVERY_LIMIT));
+
+    HttpResponseBuilder::new(StatusCode::OK).json(deliveries)
+}
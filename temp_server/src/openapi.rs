@@ -0,0 +1,99 @@
+use actix_web::http::StatusCode;
+use actix_web::{get, HttpResponseBuilder, Responder};
+use utoipa::openapi::security::{Http, HttpAuthScheme, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "temp_server",
+        description = "Temperature and humidity logging server for pico w sensors"
+    ),
+    paths(
+        crate::reading_route::reading_handler,
+        crate::latest_route::all_latest_handler,
+        crate::latest_route::location_latest_handler,
+        crate::health_route::all_health_handler,
+        crate::health_route::location_health_handler,
+        crate::stream_route::reading_stream_handler,
+        crate::metadata_route::metadata_handler,
+        crate::metadata_route::set_metadata_handler,
+        crate::admin_route::rename_location_handler,
+        crate::admin_route::merge_location_handler,
+        crate::admin_route::archive_location_handler,
+        crate::admin_route::restore_location_handler,
+        crate::admin_route::delete_location_handler,
+        crate::admin_route::archived_locations_handler,
+        crate::alerts_route::alerts_handler,
+        crate::notifier_route::deliveries_handler,
+        crate::query_route::readings_handler,
+        crate::query_route::export_csv_handler,
+        crate::aggregation_route::aggregate_handler,
+        crate::statistics_route::statistics_handler,
+        crate::outage_route::outages_handler,
+        crate::comparison_route::comparison_handler,
+        crate::comparison_route::comparison_plot_handler,
+        crate::plotting_route::plot_location_handler,
+        crate::heatmap_route::heatmap_handler,
+        crate::location_page_route::location_page_handler,
+        crate::chart_route::chart_page_handler,
+        crate::static_route::static_file_handler,
+        crate::main_page,
+        openapi_handler,
+    ),
+    modifiers(&AdminSecurity)
+)]
+pub struct ApiDoc;
+
+/// Documents the bearer token the admin routes expect.
+struct AdminSecurity;
+
+impl Modify for AdminSecurity {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "admin_token",
+                SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
+            );
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "meta",
+    responses(
+        (status = 200, description = "This OpenAPI document", content_type = "application/json", body = Object),
+    )
+)]
+#[get("/api/openapi.json")]
+pub async fn openapi_handler() -> impl Responder {
+    HttpResponseBuilder::new(StatusCode::OK).json(ApiDoc::openapi())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ApiDoc;
+    use std::path::PathBuf;
+    use utoipa::OpenApi;
+
+    // The committed copy of the document, which the temp_client crate is checked against.
+    // Run the tests with UPDATE_OPENAPI=1 to regenerate it after changing a route.
+    fn snapshot_path() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("openapi.json")
+    }
+
+    #[test]
+    fn openapi_snapshot_is_up_to_date() {
+        let generated = ApiDoc::openapi().to_pretty_json().unwrap() + "\n";
+
+        if std::env::var_os("UPDATE_OPENAPI").is_some() {
+            std::fs::write(snapshot_path(), &generated).unwrap();
+        }
+
+        let committed = std::fs::read_to_string(snapshot_path()).unwrap_or_default();
+        assert!(
+            committed == generated,
+            "openapi.json is out of date, run `UPDATE_OPENAPI=1 cargo test` to regenerate it"
+        );
+    }
+}
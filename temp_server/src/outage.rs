@@ -0,0 +1,146 @@
+use crate::reading::Reading;
+use chrono::{DateTime, Duration, Local};
+use serde::Serialize;
+use std::ops::Range;
+use utoipa::ToSchema;
+
+/// A stretch of time a sensor did not report in.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema)]
+pub struct Outage {
+    /// Time of the last reading before the outage, or the start of the range when there was none
+    pub start: DateTime<Local>,
+    /// Time of the first reading after the outage, missing when the sensor had not come back by
+    /// the end of the range
+    pub end: Option<DateTime<Local>>,
+    pub duration_seconds: i64,
+}
+
+impl Outage {
+    fn new(start: DateTime<Local>, end: Option<DateTime<Local>>, until: DateTime<Local>) -> Self {
+        Self {
+            start,
+            end,
+            duration_seconds: (end.unwrap_or(until) - start).num_seconds(),
+        }
+    }
+}
+
+/// Finds every stretch of `window` longer than `max_gap` without a reading, `readings` must be
+/// in time order and inside the window. That includes the time from the start of the window to
+/// the first reading, and from the last reading to the end of the window, which is reported as
+/// an outage that has not ended. A window without any readings is one long outage.
+pub fn find_outages(
+    readings: &[Reading],
+    max_gap: Duration,
+    window: Range<DateTime<Local>>,
+) -> Vec<Outage> {
+    let mut outages = vec![];
+    let mut before = window.start;
+
+    for after in readings.iter().map(Reading::reading_time) {
+        if after - before > max_gap {
+            outages.push(Outage::new(before, Some(after), after));
+        }
+        before = after;
+    }
+    if window.end - before > max_gap {
+        outages.push(Outage::new(before, None, window.end));
+    }
+
+    outages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::location::Location;
+    use chrono::TimeZone;
+
+    fn noon() -> DateTime<Local> {
+        Local.with_ymd_and_hms(2025, 1, 1, 12, 0, 0).unwrap()
+    }
+
+    /// Readings at the given minutes past noon
+    fn readings(minutes: &[i64]) -> Vec<Reading> {
+        let location = Location::from("test");
+        minutes
+            .iter()
+            .map(|minute| {
+                Reading::new(
+                    location.clone(),
+                    70.0,
+                    50.0,
+                    noon() + Duration::minutes(*minute),
+                )
+            })
+            .collect()
+    }
+
+    /// The window from noon to `minutes` past noon
+    fn until(minutes: i64) -> Range<DateTime<Local>> {
+        noon()..noon() + Duration::minutes(minutes)
+    }
+
+    #[test]
+    fn gaps_longer_than_the_limit_are_outages() {
+        let readings = readings(&[0, 1, 2, 3, 10, 11, 14, 15]);
+        let outages = find_outages(&readings, Duration::minutes(3), until(15));
+
+        assert_eq!(outages.len(), 1);
+        assert_eq!(outages[0].start, readings[3].reading_time());
+        assert_eq!(outages[0].end, Some(readings[4].reading_time()));
+        assert_eq!(outages[0].duration_seconds, 7 * 60);
+    }
+
+    #[test]
+    fn a_sensor_that_stopped_reporting_has_an_open_outage() {
+        let readings = readings(&[0, 1, 2]);
+        let last = readings[2].reading_time();
+
+        let outages = find_outages(&readings, Duration::minutes(3), until(4));
+        assert!(outages.is_empty());
+
+        let outages = find_outages(&readings, Duration::minutes(3), until(32));
+        assert_eq!(
+            outages,
+            vec![Outage {
+                start: last,
+                end: None,
+                duration_seconds: 30 * 60
+            }]
+        );
+    }
+
+    #[test]
+    fn a_sensor_that_started_late_has_an_outage_from_the_start() {
+        let readings = readings(&[20, 21, 22]);
+
+        let outages = find_outages(&readings, Duration::minutes(3), until(22));
+        assert_eq!(
+            outages,
+            vec![Outage {
+                start: noon(),
+                end: Some(readings[0].reading_time()),
+                duration_seconds: 20 * 60
+            }]
+        );
+
+        let outages = find_outages(&readings[1..], Duration::minutes(30), until(22));
+        assert!(outages.is_empty());
+    }
+
+    #[test]
+    fn a_window_without_readings_is_one_outage() {
+        let outages = find_outages(&[], Duration::minutes(3), until(60));
+        assert_eq!(
+            outages,
+            vec![Outage {
+                start: noon(),
+                end: None,
+                duration_seconds: 60 * 60
+            }]
+        );
+
+        assert!(find_outages(&[], Duration::minutes(3), until(2)).is_empty());
+    }
+}
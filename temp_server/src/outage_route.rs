@@ -0,0 +1,143 @@
+use crate::location::Location;
+use crate::outage::{find_outages, Outage};
+use crate::state::TemperatureServerState;
+use crate::time_range::resolve_range;
+use actix_web::http::StatusCode;
+use actix_web::{get, web, HttpResponseBuilder, Responder};
+use chrono::{DateTime, Duration, Local};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use utoipa::{IntoParams, ToSchema};
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct OutagesQuery {
+    /// Start of the range, inclusive
+    from: Option<DateTime<Local>>,
+    /// End of the range, exclusive, defaults to now
+    to: Option<DateTime<Local>>,
+    /// How far back from `to` to look, such as 24h or 30d, defaults to 7d
+    #[param(example = "30d")]
+    last: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct OutagesResponse {
+    location: Location,
+    /// How often the location is expected to report
+    reading_period_seconds: i64,
+    /// Gaps between readings longer than this are outages
+    max_gap_seconds: i64,
+    outages: Vec<Outage>,
+}
+
+#[utoipa::path(
+    tag = "readings",
+    params(
+        ("location" = String, Path, description = "Location name"),
+        OutagesQuery,
+    ),
+    responses(
+        (status = 200, description = "Times the location did not report, oldest first", body = OutagesResponse),
+        (status = 400, description = "Bad range"),
+        (status = 404, description = "Unknown location"),
+    )
+)]
+#[get("/api/v1/locations/{location}/outages")]
+pub async fn outages_handler(
+    location: web::Path<String>,
+    query: web::Query<OutagesQuery>,
+    state: web::Data<TemperatureServerState>,
+) -> impl Responder {
+    let (from, to) = match resolve_range(
+        query.from,
+        query.to,
+        query.last.as_deref(),
+        Duration::days(7),
+    ) {
+        Ok(range) => range,
+        Err(err) => return HttpResponseBuilder::new(StatusCode::BAD_REQUEST).body(err),
+    };
+
+    let location: Location = location.as_str().into();
+    let location_config = state.metadata.lock().await.config(&location);
+
+    let readings = match state
+        .get_readings_between(&location, Some(from), Some(to))
+        .await
+    {
+        Ok(Some(readings)) => readings,
+        Ok(None) => {
+            return HttpResponseBuilder::new(StatusCode::NOT_FOUND)
+                .body(format!("Unknown location: {}", location))
+        }
+        Err(err) => {
+            error!("Error reading outages for {}: {}", location, err);
+            return HttpResponseBuilder::new(StatusCode::INTERNAL_SERVER_ERROR).finish();
+        }
+    };
+
+    // the sensor can't be expected to have reported after now
+    let outages = find_outages(
+        &readings,
+        location_config.max_gap(),
+        from..to.min(Local::now()),
+    );
+
+    HttpResponseBuilder::new(StatusCode::OK).json(OutagesResponse {
+        location,
+        reading_period_seconds: location_config.reading_period().num_seconds(),
+        max_gap_seconds: location_config.max_gap().num_seconds(),
+        outages,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{readings, TestFolder};
+    use actix_web::test::{call_and_read_body_json, init_service, TestRequest};
+    use actix_web::App;
+    use serde_json::Value;
+
+    #[actix_web::test]
+    async fn a_range_without_readings_is_an_outage() {
+        let folder = TestFolder::new("outage-route");
+        let now = Local::now();
+        // half an hour of readings ending a day ago, then nothing
+        folder.write_location("garage", &readings("garage", now - Duration::days(1), 30));
+        let app = init_service(App::new().app_data(folder.state()).service(outages_handler)).await;
+
+        let outages = |query: String| {
+            let app = &app;
+            async move {
+                let body: Value = call_and_read_body_json(
+                    app,
+                    TestRequest::get()
+                        .uri(&format!("/api/v1/locations/garage/outages?{}", query))
+                        .to_request(),
+                )
+                .await;
+                body["outages"].as_array().unwrap().clone()
+            }
+        };
+        let time = |time: DateTime<Local>| time.to_rfc3339().replace('+', "%2B");
+
+        // the sensor was dead for the whole range
+        let dead = outages(format!(
+            "from={}&to={}",
+            time(now - Duration::hours(12)),
+            time(now - Duration::hours(6))
+        ))
+        .await;
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0]["duration_seconds"], 6 * 3600);
+        assert!(dead[0]["end"].is_null());
+
+        // the range starts before the first reading and reaches the present
+        let started_late = outages("last=2d".to_string()).await;
+        assert_eq!(started_late.len(), 2);
+        assert!(!started_late[0]["end"].is_null());
+        assert!(started_late[1]["end"].is_null());
+    }
+}
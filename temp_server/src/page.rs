@@ -0,0 +1,88 @@
+use crate::config::LocationConfig;
+use crate::health::SensorHealth;
+use crate::location::Location;
+use crate::metadata::LocationMetadata;
+use crate::state::LocationInfo;
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, HttpResponseBuilder};
+use askama::Template;
+use chrono::{DateTime, Local};
+use tracing::error;
+
+/// Renders a page template into a html response, templates escape everything they're given
+/// so location names can't inject markup.
+pub fn render_page(page: &impl Template) -> HttpResponse {
+    match page.render() {
+        Ok(html) => HttpResponseBuilder::new(StatusCode::OK)
+            .content_type("text/html; charset=utf-8")
+            .body(html),
+        Err(err) => {
+            error!("Error rendering page: {}", err);
+            HttpResponseBuilder::new(StatusCode::INTERNAL_SERVER_ERROR).finish()
+        }
+    }
+}
+
+/// The latest state of a sensor, formatted for the pages.
+pub struct SensorRow {
+    pub location: String,
+    /// The display name from the metadata, or the location
+    pub name: String,
+    pub description: String,
+    pub tags: Vec<String>,
+    pub color: Option<String>,
+    pub temperature: String,
+    pub humidity: String,
+    pub last_modified: String,
+    pub health: SensorHealth,
+    // the rest is for live.js to keep the health up to date, times are rfc3339
+    pub last_seen: String,
+    pub late_after_seconds: i64,
+    pub missing_after_seconds: i64,
+    pub unit: &'static str,
+}
+
+impl SensorRow {
+    pub fn new(
+        location: &Location,
+        location_info: &LocationInfo,
+        metadata: &LocationMetadata,
+        config: &LocationConfig,
+        now: DateTime<Local>,
+    ) -> Self {
+        let unit = metadata.unit();
+        let last_seen = location_info.get_last_modified().copied();
+
+        let (temperature, humidity) = location_info
+            .get_latest_reading()
+            .map(|reading| {
+                (
+                    format!(
+                        "{:.1} {}",
+                        unit.convert_fahrenheit(reading.temperature()),
+                        unit.symbol()
+                    ),
+                    format!("{:.1} %", reading.humidity()),
+                )
+            })
+            .unwrap_or(("-".to_string(), "-".to_string()));
+
+        Self {
+            location: location.as_str().to_string(),
+            name: metadata.display_name(location).to_string(),
+            description: metadata.description.clone().unwrap_or_default(),
+            tags: metadata.tags.clone(),
+            color: metadata.color.clone(),
+            temperature,
+            humidity,
+            last_modified: last_seen
+                .map(|time| time.format("%m/%d/%Y %I:%M:%S %p").to_string())
+                .unwrap_or("Not modified".to_string()),
+            health: SensorHealth::new(last_seen, config, now),
+            last_seen: last_seen.map(|time| time.to_rfc3339()).unwrap_or_default(),
+            late_after_seconds: config.late_after().num_seconds(),
+            missing_after_seconds: config.missing_after().num_seconds(),
+            unit: unit.symbol(),
+        }
+    }
+}
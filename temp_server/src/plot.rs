@@ -0,0 +1,410 @@
+use crate::downsampling::TimeSeries;
+use crate::metric::Metric;
+use actix_web::http::header::ACCEPT;
+use actix_web::{web, HttpRequest};
+use chrono::{DateTime, Local};
+use image::{ImageFormat, RgbImage};
+use plotters::backend::{BitMapBackend, DrawingBackend, SVGBackend};
+use plotters::chart::{ChartBuilder, SeriesLabelPosition};
+use plotters::coord::Shift;
+use plotters::drawing::{DrawingArea, DrawingAreaErrorKind, IntoDrawingArea};
+use plotters::element::{PathElement, Polygon, Rectangle};
+use plotters::prelude::{
+    Color, DashedLineSeries, IntoFont, LineSeries, RGBColor, ShapeStyle, BLACK, BLUE, CYAN,
+    GREEN, MAGENTA, RED, WHITE,
+};
+use serde::Deserialize;
+use std::io::Cursor;
+use std::ops::Range;
+use utoipa::ToSchema;
+
+const ORANGE: RGBColor = RGBColor(255, 140, 0);
+
+// Plot sizes are clamped to this range of pixels on each side
+const MIN_PLOT_SIZE: u32 = 100;
+const MAX_PLOT_SIZE: u32 = 4000;
+
+// The dpi that font sizes and line widths are designed for
+const BASE_DPI: u32 = 96;
+
+pub fn metric_color(metric: Metric) -> RGBColor {
+    match metric {
+        Metric::Temperature => RED,
+        Metric::Humidity => GREEN,
+        Metric::DewPoint => BLUE,
+        Metric::AbsoluteHumidity => CYAN,
+        Metric::HeatIndex => ORANGE,
+        Metric::Humidex => MAGENTA,
+    }
+}
+
+/// A darker shade of the metric colour, for lines drawn over the metric itself.
+pub fn metric_overlay_color(metric: Metric) -> RGBColor {
+    let RGBColor(red, green, blue) = metric_color(metric);
+    RGBColor(red / 2, green / 2, blue / 2)
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PlotFormat {
+    #[default]
+    Svg,
+    Png,
+    Webp,
+}
+
+impl PlotFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            PlotFormat::Svg => "image/svg+xml",
+            PlotFormat::Png => "image/png",
+            PlotFormat::Webp => "image/webp",
+        }
+    }
+
+    /// The format asked for in the query, or else the one the `Accept` header prefers, or svg.
+    pub fn negotiate(format: Option<PlotFormat>, req: &HttpRequest) -> PlotFormat {
+        format.unwrap_or_else(|| {
+            req.headers()
+                .get(ACCEPT)
+                .and_then(|accept| accept.to_str().ok())
+                .and_then(PlotFormat::from_accept)
+                .unwrap_or_default()
+        })
+    }
+
+    /// Picks a format from an `Accept` header, taking the highest quality value and preferring
+    /// svg, then png, then webp between equally acceptable formats.
+    /// An exact media type takes precedence over `image/*`, which takes precedence over `*/*`.
+    pub fn from_accept(accept: &str) -> Option<PlotFormat> {
+        let entries: Vec<(&str, f32)> = accept
+            .split(',')
+            .filter_map(|entry| {
+                let mut parts = entry.split(';').map(str::trim);
+                let media_type = parts.next().filter(|media_type| !media_type.is_empty())?;
+                let quality = parts
+                    .find_map(|param| param.strip_prefix("q="))
+                    .and_then(|q| q.parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                Some((media_type, quality))
+            })
+            .collect();
+
+        let quality_of = |format: PlotFormat| {
+            [format.content_type(), "image/*", "*/*"]
+                .iter()
+                .find_map(|media_type| {
+                    entries
+                        .iter()
+                        .find(|(entry, _)| entry == media_type)
+                        .map(|(_, quality)| *quality)
+                })
+                .unwrap_or(0.0)
+        };
+
+        [PlotFormat::Svg, PlotFormat::Png, PlotFormat::Webp]
+            .into_iter()
+            .map(|format| (format, quality_of(format)))
+            .filter(|(_, quality)| *quality > 0.0)
+            // min_by keeps the first of equal elements, so ties go to the earlier format
+            .min_by(|(_, quality_a), (_, quality_b)| quality_b.total_cmp(quality_a))
+            .map(|(format, _)| format)
+    }
+}
+
+/// Output size of a plot, `dpi` scales text and lines so high dpi images are not spindly.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PlotSize {
+    pub width: u32,
+    pub height: u32,
+    pub dpi: u32,
+}
+
+impl PlotSize {
+    pub fn new(width: Option<u32>, height: Option<u32>, dpi: Option<u32>) -> Self {
+        Self {
+            width: width.unwrap_or(1000).clamp(MIN_PLOT_SIZE, MAX_PLOT_SIZE),
+            height: height.unwrap_or(1000).clamp(MIN_PLOT_SIZE, MAX_PLOT_SIZE),
+            dpi: dpi.unwrap_or(BASE_DPI).clamp(24, 600),
+        }
+    }
+
+    pub fn scale(&self) -> f64 {
+        self.dpi as f64 / BASE_DPI as f64
+    }
+
+    pub fn scaled(&self, size: u32) -> u32 {
+        (size as f64 * self.scale()).round() as u32
+    }
+}
+
+pub struct PlotSeries {
+    pub label: String,
+    pub color: RGBColor,
+    /// Drawn against the right hand axis
+    pub secondary: bool,
+    /// Separate lines, the series is broken between them
+    pub lines: Vec<TimeSeries>,
+}
+
+/// A shaded area between a low and a high value, such as the min/max of each bucket.
+pub struct PlotBand {
+    pub label: String,
+    pub color: RGBColor,
+    pub secondary: bool,
+    /// Separate areas of (time, low, high), the band is broken between them
+    pub areas: Vec<Vec<(DateTime<Local>, f32, f32)>>,
+}
+
+/// A dashed horizontal line across the whole plot.
+pub struct PlotThreshold {
+    pub label: String,
+    pub color: RGBColor,
+    pub secondary: bool,
+    pub value: f32,
+}
+
+/// Everything needed to draw a plot of one location, independent of the output format.
+pub struct Plot {
+    pub title: String,
+    pub x_range: Range<DateTime<Local>>,
+    pub x_label_format: &'static str,
+    pub primary_range: Range<f32>,
+    pub primary_description: String,
+    /// The right hand axis range and description, None for a single axis plot
+    pub secondary_axis: Option<(Range<f32>, String)>,
+    pub series: Vec<PlotSeries>,
+    pub bands: Vec<PlotBand>,
+    pub thresholds: Vec<PlotThreshold>,
+    /// Times without any readings, shaded grey
+    pub outages: Vec<Range<DateTime<Local>>>,
+    pub stroke_width: u32,
+    pub point_size: u32,
+}
+
+impl Drawable for Plot {
+    fn draw<DB: DrawingBackend>(
+        &self,
+        root: &DrawingArea<DB, Shift>,
+        size: PlotSize,
+    ) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+        let font = |font_size: u32| ("sans-serif", size.scaled(font_size)).into_font();
+
+        root.fill(&WHITE)?;
+
+        let secondary_range = self
+            .secondary_axis
+            .as_ref()
+            .map(|(range, _)| range.clone())
+            .unwrap_or(0f32..1f32);
+
+        let mut chart = ChartBuilder::on(root)
+            .caption(&self.title, font(40))
+            .x_label_area_size(size.scaled(40))
+            .y_label_area_size(size.scaled(60))
+            .right_y_label_area_size(match self.secondary_axis {
+                None => 0,
+                Some(_) => size.scaled(60),
+            })
+            .build_cartesian_2d(self.x_range.clone(), self.primary_range.clone())?
+            .set_secondary_coord(self.x_range.clone(), secondary_range);
+
+        chart
+            .configure_mesh()
+            .x_labels(8)
+            .x_label_formatter(&|time| time.format(self.x_label_format).to_string())
+            .x_label_style(font(14))
+            .y_label_style(font(14))
+            .y_desc(&self.primary_description)
+            .axis_desc_style(font(14))
+            .draw()?;
+
+        if let Some((_, description)) = &self.secondary_axis {
+            chart
+                .configure_secondary_axes()
+                .label_style(font(14))
+                .y_desc(description)
+                .axis_desc_style(font(14))
+                .draw()?;
+        }
+
+        let legend_width = size.scaled(15) as i32;
+
+        let no_data = BLACK.mix(0.12).filled();
+        let outages = self.outages.iter().map(|outage| {
+            Rectangle::new(
+                [
+                    (outage.start, self.primary_range.start),
+                    (outage.end, self.primary_range.end),
+                ],
+                no_data,
+            )
+        });
+        let drawn = chart.draw_series(outages)?;
+        if !self.outages.is_empty() {
+            drawn.label("No data").legend(move |(x, y)| {
+                Rectangle::new([(x - legend_width, y - 4), (x, y + 4)], no_data)
+            });
+        }
+
+        // bands go underneath the lines they surround
+        for band in &self.bands {
+            let fill = band.color.mix(0.2).filled();
+
+            for (area_idx, area) in band.areas.iter().enumerate() {
+                // along the top of the band, then back along the bottom
+                let outline = area
+                    .iter()
+                    .map(|(time, _, high)| (*time, *high))
+                    .chain(area.iter().rev().map(|(time, low, _)| (*time, *low)))
+                    .collect::<Vec<_>>();
+                let polygon = std::iter::once(Polygon::new(outline, fill));
+                let drawn = if band.secondary {
+                    chart.draw_secondary_series(polygon)?
+                } else {
+                    chart.draw_series(polygon)?
+                };
+                if area_idx == 0 {
+                    drawn.label(&band.label).legend(move |(x, y)| {
+                        Rectangle::new([(x - legend_width, y - 4), (x, y + 4)], fill)
+                    });
+                }
+            }
+        }
+
+        for series in &self.series {
+            let color = series.color;
+            let style: ShapeStyle = color.into();
+
+            for (line_idx, line) in series.lines.iter().enumerate() {
+                let line_series = LineSeries::new(
+                    line.iter().copied(),
+                    style.stroke_width(size.scaled(self.stroke_width)),
+                )
+                .point_size(size.scaled(self.point_size));
+                let drawn = if series.secondary {
+                    chart.draw_secondary_series(line_series)?
+                } else {
+                    chart.draw_series(line_series)?
+                };
+                // only label the first line so each series appears once in the legend
+                if line_idx == 0 {
+                    drawn.label(&series.label).legend(move |(x, y)| {
+                        Rectangle::new([(x - legend_width, y + 1), (x, y)], color)
+                    });
+                }
+            }
+        }
+
+        for threshold in &self.thresholds {
+            let style = ShapeStyle::from(threshold.color).stroke_width(size.scaled(2));
+            let line = [
+                (self.x_range.start, threshold.value),
+                (self.x_range.end, threshold.value),
+            ];
+            let dashed = DashedLineSeries::new(line, size.scaled(10), size.scaled(6), style);
+            let drawn = if threshold.secondary {
+                chart.draw_secondary_series(dashed)?
+            } else {
+                chart.draw_series(dashed)?
+            };
+            drawn
+                .label(&threshold.label)
+                .legend(move |(x, y)| PathElement::new([(x - legend_width, y), (x, y)], style));
+        }
+
+        chart
+            .configure_series_labels()
+            .position(SeriesLabelPosition::UpperRight)
+            .margin(size.scaled(20))
+            .legend_area_size(size.scaled(5))
+            .border_style(BLUE)
+            .background_style(BLUE.mix(0.1))
+            .label_font(font(20))
+            .draw()?;
+
+        root.present()
+    }
+}
+
+/// Anything drawn with plotters that can be rendered to every [PlotFormat].
+pub trait Drawable {
+    fn draw<DB: DrawingBackend>(
+        &self,
+        root: &DrawingArea<DB, Shift>,
+        size: PlotSize,
+    ) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>>;
+}
+
+/// Renders on the blocking thread pool, as drawing and encoding a large bitmap would hold up
+/// the worker serving other requests. See [render_now].
+pub async fn render(
+    drawable: impl Drawable + Send + 'static,
+    size: PlotSize,
+    format: PlotFormat,
+) -> Result<Vec<u8>, String> {
+    web::block(move || render_now(&drawable, size, format))
+        .await
+        .map_err(|err| err.to_string())?
+}
+
+/// Renders to an in memory svg document, or an in memory bitmap encoded as a png or webp image.
+fn render_now(
+    drawable: &impl Drawable,
+    size: PlotSize,
+    format: PlotFormat,
+) -> Result<Vec<u8>, String> {
+    let image_format = match format {
+        PlotFormat::Svg => {
+            let mut svg = String::new();
+            {
+                let root = SVGBackend::with_string(&mut svg, (size.width, size.height))
+                    .into_drawing_area();
+                drawable.draw(&root, size).map_err(|err| err.to_string())?;
+            }
+            return Ok(svg.into_bytes());
+        }
+        PlotFormat::Png => ImageFormat::Png,
+        PlotFormat::Webp => ImageFormat::WebP,
+    };
+
+    let mut buffer = vec![0u8; size.width as usize * size.height as usize * 3];
+    {
+        let root =
+            BitMapBackend::with_buffer(&mut buffer, (size.width, size.height)).into_drawing_area();
+        drawable.draw(&root, size).map_err(|err| err.to_string())?;
+    }
+
+    let image = RgbImage::from_raw(size.width, size.height, buffer)
+        .ok_or("Plot buffer does not match its size")?;
+
+    let mut encoded = Cursor::new(vec![]);
+    image
+        .write_to(&mut encoded, image_format)
+        .map_err(|err| err.to_string())?;
+    Ok(encoded.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accept_header_picks_highest_quality() {
+        assert_eq!(PlotFormat::from_accept("image/png"), Some(PlotFormat::Png));
+        assert_eq!(
+            PlotFormat::from_accept("image/webp;q=0.9, image/png;q=0.5"),
+            Some(PlotFormat::Webp)
+        );
+        assert_eq!(
+            PlotFormat::from_accept("image/webp, image/png, image/svg+xml;q=0.8"),
+            Some(PlotFormat::Png)
+        );
+        assert_eq!(PlotFormat::from_accept("*/*"), Some(PlotFormat::Svg));
+        assert_eq!(
+            PlotFormat::from_accept("image/svg+xml;q=0, image/*"),
+            Some(PlotFormat::Png)
+        );
+        assert_eq!(PlotFormat::from_accept("text/html"), None);
+    }
+}
@@ -0,0 +1,201 @@
+use crate::aggregation::BucketWidth;
+use crate::downsampling::Downsampling;
+use crate::location::Location;
+use crate::metric::Metric;
+use crate::plot::{PlotFormat, PlotSize};
+use actix_web::web::Bytes;
+use chrono::{DateTime, Duration, Local};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::time::SystemTime;
+
+// The most rendered plots kept around, the oldest is dropped first
+const PLOT_CACHE_CAPACITY: usize = 64;
+
+/// Everything about a plot request that changes the rendered image, apart from the data itself.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlotOptions {
+    pub metrics: Vec<Metric>,
+    pub downsampling: Downsampling,
+    pub temperature_range: Option<(f32, f32)>,
+    pub humidity_range: Option<(f32, f32)>,
+    pub band: bool,
+    pub rolling_mean: Option<Duration>,
+    pub thresholds: Vec<(Metric, f32)>,
+    pub format: PlotFormat,
+    pub size: PlotSize,
+}
+
+// a NaN axis range or threshold never equals itself, which only means its plots are never found in the cache
+impl Eq for PlotOptions {}
+
+impl Hash for PlotOptions {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let range_bits =
+            |range: Option<(f32, f32)>| range.map(|(low, high)| (low.to_bits(), high.to_bits()));
+
+        self.metrics.hash(state);
+        self.downsampling.hash(state);
+        range_bits(self.temperature_range).hash(state);
+        range_bits(self.humidity_range).hash(state);
+        self.band.hash(state);
+        self.rolling_mean.hash(state);
+        for (metric, value) in &self.thresholds {
+            metric.hash(state);
+            value.to_bits().hash(state);
+        }
+        self.format.hash(state);
+        self.size.hash(state);
+    }
+}
+
+/// Identifies a rendered plot, `last_write` is when the location csv file was last written
+/// so new readings make for a new key.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PlotKey {
+    pub location: Location,
+    pub from: DateTime<Local>,
+    pub to: DateTime<Local>,
+    pub options: PlotOptions,
+    pub last_write: Option<SystemTime>,
+    /// Where lines break and outages are shaded, which changes along with the location metadata
+    pub max_gap: Duration,
+}
+
+impl PlotKey {
+    /// A quoted entity tag for the image this key renders to.
+    pub fn etag(&self) -> String {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        format!("\"{:016x}\"", hasher.finish())
+    }
+}
+
+/// Identifies a rendered comparison plot, `last_writes` are when each location csv file was
+/// last written, in the order of `locations`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ComparisonKey {
+    pub locations: Vec<Location>,
+    pub metric: Metric,
+    pub from: DateTime<Local>,
+    pub to: DateTime<Local>,
+    pub step: BucketWidth,
+    pub format: PlotFormat,
+    pub size: PlotSize,
+    pub last_writes: Vec<SystemTime>,
+}
+
+/// Recently rendered plots, so dashboards refreshing an unchanged plot don't redraw it.
+pub struct PlotCache<K = PlotKey> {
+    plots: HashMap<K, Bytes>,
+    // keys in the order they were inserted
+    order: VecDeque<K>,
+}
+
+impl<K> Default for PlotCache<K> {
+    fn default() -> Self {
+        Self {
+            plots: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+}
+
+impl<K: Clone + Eq + Hash> PlotCache<K> {
+    pub fn get(&self, key: &K) -> Option<Bytes> {
+        self.plots.get(key).cloned()
+    }
+
+    pub fn insert(&mut self, key: K, plot: Bytes) {
+        if self.plots.insert(key.clone(), plot).is_some() {
+            return;
+        }
+        self.order.push_back(key);
+
+        while self.order.len() > PLOT_CACHE_CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.plots.remove(&oldest);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn key(idx: i64) -> PlotKey {
+        let to = Local.with_ymd_and_hms(2025, 3, 1, 12, 0, 0).unwrap() + Duration::hours(idx);
+        PlotKey {
+            location: "kitchen".into(),
+            from: to - Duration::days(1),
+            to,
+            options: PlotOptions {
+                metrics: Metric::MEASURED.to_vec(),
+                downsampling: Downsampling::default(),
+                temperature_range: None,
+                humidity_range: None,
+                band: false,
+                rolling_mean: None,
+                thresholds: vec![],
+                format: PlotFormat::Svg,
+                size: PlotSize::new(None, None, None),
+            },
+            last_write: Some(SystemTime::UNIX_EPOCH),
+            max_gap: Duration::minutes(3),
+        }
+    }
+
+    #[test]
+    fn the_oldest_plot_is_dropped_first() {
+        let mut cache = PlotCache::default();
+        for idx in 0..PLOT_CACHE_CAPACITY as i64 {
+            cache.insert(key(idx), Bytes::from(idx.to_string()));
+        }
+        // drawing a plot again replaces it without making it any newer
+        cache.insert(key(0), Bytes::from("again"));
+        assert_eq!(cache.get(&key(0)), Some(Bytes::from("again")));
+
+        cache.insert(key(PLOT_CACHE_CAPACITY as i64), Bytes::from("newest"));
+        assert_eq!(cache.get(&key(0)), None);
+        assert_eq!(cache.get(&key(1)), Some(Bytes::from("1")));
+        assert_eq!(
+            cache.get(&key(PLOT_CACHE_CAPACITY as i64)),
+            Some(Bytes::from("newest"))
+        );
+        assert_eq!(cache.plots.len(), PLOT_CACHE_CAPACITY);
+        assert_eq!(cache.order.len(), PLOT_CACHE_CAPACITY);
+    }
+
+    #[test]
+    fn etags_change_with_anything_that_changes_the_image() {
+        let etag = key(0).etag();
+        assert_eq!(key(0).etag(), etag);
+        assert!(etag.starts_with('"') && etag.ends_with('"'));
+
+        let changed = [
+            PlotKey {
+                last_write: Some(SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1)),
+                ..key(0)
+            },
+            PlotKey {
+                max_gap: Duration::minutes(30),
+                ..key(0)
+            },
+            PlotKey {
+                location: "garage".into(),
+                ..key(0)
+            },
+            key(1),
+        ];
+        for key in changed {
+            assert_ne!(key.etag(), etag, "{:?}", key);
+        }
+
+        let mut options = key(0).options;
+        options.format = PlotFormat::Png;
+        assert_ne!(PlotKey { options, ..key(0) }.etag(), etag);
+    }
+}
@@ -1,235 +1,858 @@
+use crate::aggregation::{aggregate, Aggregate, BucketWidth};
+use crate::config::CONFIG;
+use crate::downsampling::{lttb, min_max, rolling_mean, Downsampling, TimeSeries};
+use crate::location::Location;
+use crate::metric::Metric;
+use crate::outage::find_outages;
+use crate::plot::{
+    metric_color, metric_overlay_color, render, Plot, PlotBand, PlotFormat, PlotSeries, PlotSize,
+    PlotThreshold,
+};
+use crate::plot_cache::{PlotKey, PlotOptions};
 use crate::state::TemperatureServerState;
-use crate::{LOG_FOLDER_PATH, PLOTS_FOLDER_PATH};
+use crate::time_range::{parse_duration, resolve_range};
 use actix_web::http::StatusCode;
-use actix_web::{get, web, HttpResponseBuilder, Responder};
-use plotters::backend::SVGBackend;
-use plotters::chart::{ChartBuilder, SeriesLabelPosition};
-use plotters::drawing::IntoDrawingArea;
-use plotters::element::Rectangle;
-use plotters::prelude::{Color, IntoFont, LineSeries, ShapeStyle, BLUE, GREEN, RED, WHITE};
-use std::str::from_utf8;
-use tokio::fs;
-use tokio::fs::OpenOptions;
-use tokio::io::{AsyncReadExt, AsyncSeekExt};
-use tracing::log::warn;
+use actix_web::http::header::{
+    HeaderValue, HttpDate, ACCEPT, CACHE_CONTROL, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH,
+    LAST_MODIFIED, VARY,
+};
+use actix_web::web::Bytes;
+use actix_web::{get, web, HttpRequest, HttpResponseBuilder, Responder};
+use chrono::{DateTime, Duration, Local};
+use serde::Deserialize;
+use std::str::FromStr;
+use std::time::SystemTime;
 use tracing::{error, info};
+use utoipa::IntoParams;
+
+// The number of buckets the plotted range is split into when plotting bucket means,
+// gaps in the data shorter than one of these buckets are not shown
+const PLOT_POINTS: usize = 100;
+
+// The most points drawn for each series when down-sampling raw readings, half the plot width
+const DOWNSAMPLED_POINTS: usize = 500;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PlotQuery {
+    /// Comma separated list of metrics to draw, defaults to temperature and humidity
+    #[param(example = "temperature,humidity,dew_point")]
+    series: Option<String>,
+    /// Start of the range, inclusive
+    from: Option<DateTime<Local>>,
+    /// End of the range, exclusive, defaults to now
+    to: Option<DateTime<Local>>,
+    /// How far back from `to` to plot, such as 24h, 7d or 30d, defaults to 24h
+    #[param(example = "7d")]
+    last: Option<String>,
+    /// How to reduce the readings to fit the plot, defaults to lttb
+    #[param(inline)]
+    downsample: Option<Downsampling>,
+    /// Fixed temperature axis range as "min,max", overrides the location config
+    #[param(example = "60,80")]
+    temperature_range: Option<String>,
+    /// Fixed humidity axis range as "min,max", overrides the location config
+    #[param(example = "0,100")]
+    humidity_range: Option<String>,
+    /// Shade the lowest to highest reading of each bucket behind every metric
+    band: Option<bool>,
+    /// Also draw the mean of the readings over this trailing window, such as 1h
+    #[param(example = "1h")]
+    rolling_mean: Option<String>,
+    /// Comma separated list of metric:value pairs to draw dashed horizontal lines at
+    #[param(example = "humidity:60")]
+    thresholds: Option<String>,
+    /// Image format, overrides the Accept header, defaults to svg
+    #[param(inline)]
+    format: Option<PlotFormat>,
+    /// Image width in pixels, defaults to 1000
+    width: Option<u32>,
+    /// Image height in pixels, defaults to 1000
+    height: Option<u32>,
+    /// Scales text and lines, defaults to 96
+    dpi: Option<u32>,
+}
 
-#[get("/plot/{location}")]
-pub async fn plot_location_handler(
-    location: web::Path<String>,
-    state: web::Data<TemperatureServerState>,
-) -> impl Responder {
-    let file_name = format!("{}.svg", location.as_str());
-
-    info!("{}", file_name);
-    let path = PLOTS_FOLDER_PATH.join(file_name);
-
-    let backend = SVGBackend::new(&path, (1000, 1000)).into_drawing_area();
+/// Parses an axis range written as "min,max"
+fn parse_axis_range(range: &str) -> Result<(f32, f32), String> {
+    let bad_range = || format!("Axis range should look like min,max: {}", range);
+    let (low, high) = range.split_once(',').ok_or_else(bad_range)?;
+    let low: f32 = low.trim().parse().map_err(|_| bad_range())?;
+    let high: f32 = high.trim().parse().map_err(|_| bad_range())?;
+
+    if low < high {
+        Ok((low, high))
+    } else {
+        Err(bad_range())
+    }
+}
 
-    backend.fill(&WHITE).unwrap();
+/// Parses thresholds written as "metric:value,metric:value"
+fn parse_thresholds(thresholds: &str) -> Result<Vec<(Metric, f32)>, String> {
+    thresholds
+        .split(',')
+        .map(str::trim)
+        .filter(|threshold| !threshold.is_empty())
+        .map(|threshold| {
+            let bad_threshold = || format!("Threshold should look like metric:value: {}", threshold);
+            let (metric, value) = threshold.split_once(':').ok_or_else(bad_threshold)?;
+            let metric: Metric = metric.trim().parse()?;
+            let value: f32 = value.trim().parse().map_err(|_| bad_threshold())?;
+            if value.is_finite() {
+                Ok((metric, value))
+            } else {
+                Err(bad_threshold())
+            }
+        })
+        .collect()
+}
 
-    let location = location.as_str().into();
+/// Humidity style metrics are drawn against the right hand axis, everything else against the left.
+pub fn on_secondary_axis(metric: Metric) -> bool {
+    matches!(metric, Metric::Humidity | Metric::AbsoluteHumidity)
+}
 
-    info!("Getting data");
+/// An axis range that fits every value with a little room above and below,
+/// or `fixed` if one was configured.
+fn axis_range(values: impl Iterator<Item = f32>, fixed: Option<(f32, f32)>) -> std::ops::Range<f32> {
+    if let Some((low, high)) = fixed {
+        return low..high;
+    }
+
+    let (lowest, highest) = values.fold((f32::INFINITY, f32::NEG_INFINITY), |(low, high), v| {
+        (low.min(v), high.max(v))
+    });
+    if lowest > highest {
+        // nothing to draw
+        return 0f32..100f32;
+    }
+
+    let padding = ((highest - lowest) * 0.1).max(1.0);
+    (lowest - padding)..(highest + padding)
+}
 
-    // TODO: this needs to eventually draw WAY more datapoints, as one is taken every minute, so this needs to scale all the points down quite a bit
+fn axis_description(metrics: &[Metric]) -> String {
+    metrics
+        .iter()
+        .map(|metric| match metric.unit() {
+            "" => metric.display_name().to_string(),
+            unit => format!("{} ({})", metric.display_name(), unit),
+        })
+        .collect::<Vec<_>>()
+        .join(" / ")
+}
 
-    let (temperature_data, humidity_data) = {
-        let mut lock = state.file_buf_list.lock().await;
-        let string_data = {
-            String::from_utf8(match lock.get_mut(&location) {
-                None => {
-                    let file_path = LOG_FOLDER_PATH.join(location.path());
+/// A tick label format that suits how much time the x axis covers.
+pub fn time_label_format(span: Duration) -> &'static str {
+    if span <= Duration::days(1) {
+        "%I:%M %p"
+    } else if span <= Duration::days(7) {
+        "%m/%d %I %p"
+    } else if span <= Duration::days(365) {
+        "%m/%d"
+    } else {
+        "%m/%Y"
+    }
+}
 
-                    match OpenOptions::new()
-                        .append(true)
-                        .write(true)
-                        .read(true)
-                        .create(true) // TODO: this could be create_new(true) which would move us to error case if the file already exists, which would allow us to have possibly more clean code?
-                        .open(file_path)
-                        .await
-                    {
-                        Ok(mut file) => {
-                            let mut data = vec![];
+/// Splits a time series into separate lines wherever two neighbouring points are more than
+/// `max_gap` apart, so missing data is drawn as a break rather than a straight line across it.
+pub fn split_on_gaps<T: Copy>(
+    points: &[(DateTime<Local>, T)],
+    max_gap: Duration,
+) -> Vec<Vec<(DateTime<Local>, T)>> {
+    let mut lines: Vec<Vec<(DateTime<Local>, T)>> = vec![];
+
+    for point in points {
+        match lines.last_mut() {
+            Some(line) if line.last().is_some_and(|last| point.0 - last.0 <= max_gap) => {
+                line.push(*point)
+            }
+            _ => lines.push(vec![*point]),
+        }
+    }
 
-                            file.read_to_end(&mut data).await.unwrap();
+    lines
+}
 
-                            lock.insert(location.clone(), file.into());
+/// Reads and draws the plot described by `key`.
+async fn render_plot(
+    state: &TemperatureServerState,
+    key: &PlotKey,
+    bucket_width: BucketWidth,
+) -> Result<Vec<u8>, String> {
+    let PlotKey {
+        location,
+        from: start,
+        to: end,
+        options,
+        max_gap,
+        ..
+    } = key;
+    let max_gap = *max_gap;
+    let metrics = &options.metrics;
+
+    let readings = state
+        .get_readings_between(location, Some(*start), Some(*end))
+        .await
+        .map_err(|err| format!("Error reading plot data: {}", err))?
+        .unwrap_or_default();
+
+    info!("len: {}", readings.len());
+
+    let downsampling = options.downsampling;
+
+    let raw_data = |metric: Metric| {
+        readings
+            .iter()
+            .map(|reading| (reading.reading_time(), metric.value(reading)))
+            .collect::<TimeSeries>()
+    };
 
-                            data
-                        }
-                        Err(err) => {
-                            error!("Error opening plot file: {}", err);
-                            todo!()
+    // each metric is split into separate lines wherever a whole bucket has no readings,
+    // or for raw readings wherever there is an outage
+    let series: Vec<(Metric, Vec<TimeSeries>)> = match downsampling {
+        Downsampling::Mean => {
+            // plot the mean of each bucket at its middle
+            let rows = aggregate(&readings, bucket_width, metrics, &[Aggregate::Mean]);
+
+            metrics
+                .iter()
+                .map(|metric| {
+                    let data = rows
+                        .iter()
+                        .filter_map(|row| {
+                            row.get(*metric, Aggregate::Mean).map(|value| {
+                                (row.bucket_start + bucket_width.duration() / 2, value as f32)
+                            })
+                        })
+                        .collect::<TimeSeries>();
+                    (*metric, split_on_gaps(&data, bucket_width.duration()))
+                })
+                .collect()
+        }
+        Downsampling::Lttb | Downsampling::MinMax => metrics
+            .iter()
+            .map(|metric| {
+                let data = raw_data(*metric);
+
+                let lines = split_on_gaps(&data, max_gap)
+                    .into_iter()
+                    .map(|line| {
+                        // every line gets its share of the points by how many readings it has
+                        let share = (DOWNSAMPLED_POINTS * line.len() / data.len().max(1)).max(3);
+                        match downsampling {
+                            Downsampling::MinMax => min_max(&line, share / 2),
+                            _ => lttb(&line, share),
                         }
-                    }
-                }
-                Some(location_file) => {
-                    location_file.get_file_mut(false).rewind().await.unwrap();
+                    })
+                    .collect();
+                (*metric, lines)
+            })
+            .collect(),
+    };
 
-                    let mut data = vec![];
-                    location_file
-                        .get_file_mut(false)
-                        .read_to_end(&mut data)
-                        .await
-                        .unwrap();
+    // only split the metrics over two axes when there is something to put on each of them
+    let dual_axes = metrics.iter().any(|metric| on_secondary_axis(*metric))
+        && metrics.iter().any(|metric| !on_secondary_axis(*metric));
+    let is_secondary = |metric: Metric| dual_axes && on_secondary_axis(metric);
+
+    let mut plot_series: Vec<PlotSeries> = series
+        .into_iter()
+        .map(|(metric, lines)| PlotSeries {
+            label: metric.display_name().to_string(),
+            color: metric_color(metric),
+            secondary: is_secondary(metric),
+            lines,
+        })
+        .collect();
+
+    if let Some(window) = options.rolling_mean {
+        for metric in metrics {
+            let data = rolling_mean(&raw_data(*metric), window);
+            let lines = split_on_gaps(&data, max_gap)
+                .into_iter()
+                .map(|line| {
+                    let share = (DOWNSAMPLED_POINTS * line.len() / data.len().max(1)).max(3);
+                    lttb(&line, share)
+                })
+                .collect();
 
-                    data
+            plot_series.push(PlotSeries {
+                label: format!("{} rolling mean", metric.display_name()),
+                color: metric_overlay_color(*metric),
+                secondary: is_secondary(*metric),
+                lines,
+            });
+        }
+    }
+
+    let bands: Vec<PlotBand> = if options.band {
+        let rows = aggregate(
+            &readings,
+            bucket_width,
+            metrics,
+            &[Aggregate::Min, Aggregate::Max],
+        );
+
+        metrics
+            .iter()
+            .map(|metric| {
+                let data = rows
+                    .iter()
+                    .filter_map(|row| {
+                        let low = row.get(*metric, Aggregate::Min)?;
+                        let high = row.get(*metric, Aggregate::Max)?;
+                        Some((
+                            row.bucket_start + bucket_width.duration() / 2,
+                            (low as f32, high as f32),
+                        ))
+                    })
+                    .collect::<Vec<_>>();
+
+                PlotBand {
+                    label: format!("{} min/max", metric.display_name()),
+                    color: metric_color(*metric),
+                    secondary: is_secondary(*metric),
+                    areas: split_on_gaps(&data, bucket_width.duration())
+                        .into_iter()
+                        .map(|area| {
+                            area.into_iter()
+                                .map(|(time, (low, high))| (time, low, high))
+                                .collect()
+                        })
+                        .collect(),
                 }
             })
-            .unwrap()
-        };
+            .collect()
+    } else {
+        vec![]
+    };
 
-        let lines = string_data
-            .lines()
-            .map(|s| {
-                s.split(",")
-                    .skip(1)
-                    .map(|s| s.trim().to_string())
-                    .collect::<Vec<String>>()
-            })
-            .collect::<Vec<Vec<String>>>();
+    let thresholds: Vec<PlotThreshold> = options
+        .thresholds
+        .iter()
+        .map(|(metric, value)| PlotThreshold {
+            label: match metric.unit() {
+                "" => format!("{} {}", metric.display_name(), value),
+                unit => format!("{} {} {}", metric.display_name(), value, unit),
+            },
+            color: metric_overlay_color(*metric),
+            secondary: is_secondary(*metric),
+            value: *value,
+        })
+        .collect();
+
+    // everything drawn against an axis has to fit on it
+    let values = |secondary: bool| {
+        let lines = plot_series
+            .iter()
+            .filter(move |series| series.secondary == secondary)
+            .flat_map(|series| series.lines.iter().flatten().map(|(_, v)| *v));
+        let bands = bands
+            .iter()
+            .filter(move |band| band.secondary == secondary)
+            .flat_map(|band| band.areas.iter().flatten())
+            .flat_map(|(_, low, high)| [*low, *high]);
+        let thresholds = thresholds
+            .iter()
+            .filter(move |threshold| threshold.secondary == secondary)
+            .map(|threshold| threshold.value);
+        lines.chain(bands).chain(thresholds)
+    };
+
+    let (secondary_metrics, primary_metrics): (Vec<Metric>, Vec<Metric>) =
+        metrics.iter().partition(|metric| is_secondary(**metric));
+    let primary_range = if primary_metrics.iter().all(|metric| on_secondary_axis(*metric)) {
+        options.humidity_range
+    } else {
+        options.temperature_range
+    };
 
-        let mut temperature_data: Vec<(_, f32)> = vec![];
-        let mut humidity_data: Vec<(_, f32)> = vec![];
+    // widen the x axis out to whole buckets, ranges ending now are already aligned
+    let x_end = match bucket_width.bucket_start(*end) {
+        bucket_start if bucket_start == *end => *end,
+        bucket_start => bucket_start + bucket_width.duration(),
+    };
+    let x_range = bucket_width.bucket_start(*start)..x_end;
 
-        info!("len: {}", lines.len());
-        for (idx, line) in lines.iter().enumerate() {
-            // skip first line!
-            if idx == 0 {
-                continue;
-            }
+    let outages = find_outages(&readings, max_gap, *start..(*end).min(Local::now()))
+        .into_iter()
+        .map(|outage| outage.start..outage.end.unwrap_or(x_end))
+        .collect();
 
-            match (
-                line.get(0).and_then(|s| s.parse().ok()),
-                line.get(1).and_then(|s| s.parse().ok()),
-            ) {
-                (Some(t), Some(h)) => {
-                    temperature_data.push((idx as f32, t));
-                    humidity_data.push((idx as f32, h));
-                }
-                _ => {
-                    warn!("Bad line: {}: {:?}", idx, line);
-                }
-            }
-        }
+    // down-sampled raw readings are too dense to mark every point
+    let (stroke_width, point_size) = match downsampling {
+        Downsampling::Mean => (4, 3),
+        Downsampling::Lttb | Downsampling::MinMax => (2, 0),
+    };
 
-        // resize data arrays if they are longer than 100 data points for the graph
-        if temperature_data.len() > 100 {
-            loop {
-                if temperature_data.len() <= 100 {
-                    break;
-                }
-                temperature_data.remove(0);
-            }
+    let plot = Plot {
+        title: format!("Environmental Data for: {}", location.as_str()),
+        x_range,
+        x_label_format: time_label_format(*end - *start),
+        primary_range: axis_range(values(false), primary_range),
+        primary_description: axis_description(&primary_metrics),
+        secondary_axis: (!secondary_metrics.is_empty()).then(|| {
+            (
+                axis_range(values(true), options.humidity_range),
+                axis_description(&secondary_metrics),
+            )
+        }),
+        series: plot_series,
+        bands,
+        thresholds,
+        outages,
+        stroke_width,
+        point_size,
+    };
 
-            let subtraction_amount = lines.len() - 100;
+    render(plot, options.size, options.format).await
+}
 
-            let new_temperature_data = temperature_data
-                .into_iter()
-                .map(|(old_idx, temp)| ((old_idx - subtraction_amount as f32) as f32, temp))
-                .collect();
+/// Whether the client's copy of the plot, described by its conditional request headers, is current.
+fn not_modified(req: &HttpRequest, etag: &str, last_modified: Option<SystemTime>) -> bool {
+    let header = |name| {
+        req.headers()
+            .get(name)
+            .and_then(|value: &HeaderValue| value.to_str().ok())
+    };
 
-            temperature_data = new_temperature_data;
+    // If-Modified-Since is ignored when If-None-Match is sent
+    if let Some(if_none_match) = header(IF_NONE_MATCH) {
+        return if_none_match.split(',').map(str::trim).any(|tag| {
+            tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag
+        });
+    }
+
+    match (header(IF_MODIFIED_SINCE).map(HttpDate::from_str), last_modified) {
+        // http dates only have whole seconds
+        (Some(Ok(since)), Some(last_modified)) => {
+            last_modified < SystemTime::from(since) + std::time::Duration::from_secs(1)
+        }
+        _ => false,
+    }
+}
 
-            loop {
-                if humidity_data.len() <= 100 {
-                    break;
-                }
-                humidity_data.remove(0);
-            }
+#[utoipa::path(
+    tag = "plots",
+    params(
+        ("location" = String, Path, description = "Location name"),
+        PlotQuery,
+    ),
+    responses(
+        (status = 200, description = "Plot of the location", content(
+            (String = "image/svg+xml"),
+            (Vec<u8> = "image/png"),
+            (Vec<u8> = "image/webp"),
+        )),
+        (status = 304, description = "The plot has not changed since the client's copy"),
+        (status = 400, description = "Unknown metric, a bad range or a bad overlay"),
+    )
+)]
+#[get("/plot/{location}")]
+pub async fn plot_location_handler(
+    req: HttpRequest,
+    location: web::Path<String>,
+    query: web::Query<PlotQuery>,
+    state: web::Data<TemperatureServerState>,
+) -> impl Responder {
+    let metrics = match query.series.as_deref().map(Metric::parse_list) {
+        None => Metric::MEASURED.to_vec(),
+        Some(Ok(metrics)) if !metrics.is_empty() => metrics,
+        Some(Ok(_)) => Metric::MEASURED.to_vec(),
+        Some(Err(err)) => return HttpResponseBuilder::new(StatusCode::BAD_REQUEST).body(err),
+    };
 
-            let new_humidity_data = humidity_data
-                .into_iter()
-                .map(|(old_idx, temp)| ((old_idx - subtraction_amount as f32) as f32, temp))
-                .collect();
+    let (start, end) = match resolve_range(
+        query.from,
+        query.to,
+        query.last.as_deref(),
+        Duration::days(1),
+    ) {
+        Ok(range) => range,
+        Err(err) => return HttpResponseBuilder::new(StatusCode::BAD_REQUEST).body(err),
+    };
 
-            humidity_data = new_humidity_data;
+    let location: Location = location.as_str().into();
+
+    let location_plot_config = CONFIG.location(&location).plot;
+    let (temperature_range, humidity_range) = match (
+        query.temperature_range.as_deref().map(parse_axis_range).transpose(),
+        query.humidity_range.as_deref().map(parse_axis_range).transpose(),
+    ) {
+        (Ok(temperature_range), Ok(humidity_range)) => (
+            temperature_range.or(location_plot_config.temperature_range),
+            humidity_range.or(location_plot_config.humidity_range),
+        ),
+        (Err(err), _) | (_, Err(err)) => {
+            return HttpResponseBuilder::new(StatusCode::BAD_REQUEST).body(err)
         }
+    };
 
-        (temperature_data, humidity_data)
+    let rolling_mean = match query.rolling_mean.as_deref().map(parse_duration).transpose() {
+        Ok(window) => window,
+        Err(err) => return HttpResponseBuilder::new(StatusCode::BAD_REQUEST).body(err),
     };
 
-    info!(
-        "Data len: {}, {}",
-        temperature_data.len(),
-        humidity_data.len()
-    );
+    let thresholds = match query.thresholds.as_deref().map(parse_thresholds).transpose() {
+        Ok(thresholds) => thresholds.unwrap_or_default(),
+        Err(err) => return HttpResponseBuilder::new(StatusCode::BAD_REQUEST).body(err),
+    };
+    if let Some((metric, _)) = thresholds.iter().find(|(metric, _)| !metrics.contains(metric)) {
+        return HttpResponseBuilder::new(StatusCode::BAD_REQUEST)
+            .body(format!("Threshold for a metric that is not plotted: {}", metric));
+    }
+
+    let format = PlotFormat::negotiate(query.format, &req);
+
+    let bucket_width = BucketWidth::for_span(end - start, PLOT_POINTS);
+
+    // a range ending now moves with every request, snap it to whole buckets so repeated
+    // requests draw the same plot until the range moves on to the next bucket
+    let (start, end, window_moved) = if query.to.is_none() {
+        let bucket_start = bucket_width.bucket_start(end);
+        (
+            bucket_width.bucket_start(start),
+            bucket_start + bucket_width.duration(),
+            Some(SystemTime::from(bucket_start)),
+        )
+    } else {
+        (start, end, None)
+    };
 
-    let highest_temp = temperature_data
-        .iter()
-        .map(|(_, temp)| *temp)
-        .max_by(|temp, temp2| temp.total_cmp(temp2))
-        .unwrap_or(100f32)
-        .max(100f32);
-
-    let mut chart = ChartBuilder::on(&backend)
-        .caption(
-            format!("Environmental Data for: {}", location.as_str()),
-            ("sans-serif", 40).into_font(),
+    let last_write = match state.get_last_write_time(&location).await {
+        Ok(last_write) => last_write,
+        Err(err) => {
+            error!("Error reading plot file time: {}", err);
+            return HttpResponseBuilder::new(StatusCode::INTERNAL_SERVER_ERROR).finish();
+        }
+    };
+    let last_modified = last_write.max(window_moved);
+
+    // raw readings are broken wherever the sensor missed enough readings to count as an outage
+    let max_gap = state.metadata.lock().await.config(&location).max_gap();
+
+    let key = PlotKey {
+        location,
+        from: start,
+        to: end,
+        options: PlotOptions {
+            metrics,
+            downsampling: query.downsample.unwrap_or_default(),
+            temperature_range,
+            humidity_range,
+            band: query.band.unwrap_or(false),
+            rolling_mean,
+            thresholds,
+            format,
+            size: PlotSize::new(query.width, query.height, query.dpi),
+        },
+        last_write,
+        max_gap,
+    };
+    let etag = key.etag();
+
+    let is_not_modified = not_modified(&req, &etag, last_modified);
+
+    let mut response = HttpResponseBuilder::new(if is_not_modified {
+        StatusCode::NOT_MODIFIED
+    } else {
+        StatusCode::OK
+    });
+    response
+        .insert_header((ETAG, etag))
+        // always check the plot is current before reusing it
+        .insert_header((CACHE_CONTROL, "no-cache"))
+        // without a format the image type follows the Accept header
+        .insert_header((VARY, ACCEPT.as_str()));
+    if let Some(last_modified) = last_modified {
+        response.insert_header((LAST_MODIFIED, HttpDate::from(last_modified)));
+    }
+
+    if is_not_modified {
+        return response.finish();
+    }
+
+    let cached = state.plot_cache.lock().await.get(&key);
+    let content = match cached {
+        Some(content) => content,
+        None => match render_plot(&state, &key, bucket_width).await {
+            Ok(content) => {
+                let content = Bytes::from(content);
+                state.plot_cache.lock().await.insert(key, content.clone());
+                content
+            }
+            Err(err) => {
+                error!("Error drawing plot: {}", err);
+                return HttpResponseBuilder::new(StatusCode::INTERNAL_SERVER_ERROR).finish();
+            }
+        },
+    };
+
+    response.content_type(format.content_type()).body(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::LocationMetadata;
+    use crate::reading::Reading;
+    use crate::test_support::TestFolder;
+    use actix_web::http::header::{HeaderName, CONTENT_TYPE};
+    use actix_web::test::{call_service, init_service, read_body, TestRequest};
+    use actix_web::App;
+    use chrono::TimeZone;
+    use plotters::style::RGBColor;
+
+    fn start() -> DateTime<Local> {
+        Local.with_ymd_and_hms(2025, 3, 1, 10, 0, 0).unwrap()
+    }
+
+    /// Two hours of readings a minute apart from 10 AM, with half an hour missing after 10:40.
+    fn folder(name: &str) -> TestFolder {
+        let folder = TestFolder::new(name);
+        let readings = (0..120)
+            .filter(|minute| !(40..70).contains(minute))
+            .map(|minute| {
+                Reading::new(
+                    "kitchen".into(),
+                    60.0 + (minute % 7) as f32,
+                    40.0,
+                    start() + Duration::minutes(minute),
+                )
+            })
+            .collect::<Vec<_>>();
+        folder.write_location("kitchen", &readings);
+        folder
+    }
+
+    /// Plots the two hours of readings as svg, the returned status and body.
+    async fn plot(folder: &TestFolder, query: &str) -> (StatusCode, String) {
+        let app = init_service(
+            App::new()
+                .app_data(folder.state())
+                .service(plot_location_handler),
+        )
+        .await;
+        let range = |time: DateTime<Local>| time.to_rfc3339().replace('+', "%2B");
+        let uri = format!(
+            "/plot/kitchen?from={}&to={}&{}",
+            range(start()),
+            range(start() + Duration::hours(2)),
+            query
+        );
+
+        let resp = call_service(&app, TestRequest::get().uri(&uri).to_request()).await;
+        let status = resp.status();
+        let body = read_body(resp).await;
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    fn lines_in(svg: &str, color: RGBColor) -> usize {
+        let RGBColor(red, green, blue) = color;
+        svg.matches(&format!(
+            "<polyline fill=\"none\" opacity=\"1\" stroke=\"#{:02X}{:02X}{:02X}\"",
+            red, green, blue
+        ))
+        .count()
+    }
+
+    #[actix_web::test]
+    async fn readings_are_drawn_against_their_time() {
+        let folder = folder("plot-time");
+        let (status, svg) = plot(&folder, "").await;
+        assert_eq!(status, StatusCode::OK);
+
+        assert!(svg.contains("Environmental Data for: kitchen"));
+        for label in ["10:00 AM", "10:45 AM", "11:45 AM"] {
+            assert!(svg.contains(label), "no {} tick", label);
+        }
+        // the missing half hour breaks each line in two and is shaded
+        assert_eq!(lines_in(&svg, metric_color(Metric::Temperature)), 2);
+        assert_eq!(lines_in(&svg, metric_color(Metric::Humidity)), 2);
+        assert!(svg.contains("No data"));
+    }
+
+    #[actix_web::test]
+    async fn temperature_and_humidity_get_their_own_axes() {
+        let folder = folder("plot-axes");
+
+        let (_, svg) = plot(&folder, "").await;
+        assert!(svg.contains("Temperature (F)"));
+        assert!(svg.contains("Humidity (%)"));
+        assert!(!svg.contains(" / "));
+        // the temperature axis fits the readings rather than going up to 100
+        assert!(!svg.contains("\n100.0\n"));
+
+        let (_, svg) = plot(&folder, "series=temperature").await;
+        assert!(!svg.contains("Humidity (%)"));
+
+        let (status, svg) = plot(&folder, "temperature_range=0,100").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(svg.contains("\n100\n") || svg.contains("\n100.0\n"));
+
+        let (status, _) = plot(&folder, "temperature_range=80,60").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn durations_too_long_are_bad_requests() {
+        let folder = folder("plot-durations");
+
+        for query in [
+            "last=9223372036854775807s",
+            "last=99999999w",
+            "rolling_mean=9223372036854775807w",
+        ] {
+            let (status, _) = plot(&folder, query).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", query);
+        }
+    }
+
+    #[test]
+    fn clients_with_the_current_plot_are_not_sent_it_again() {
+        let etag = "\"0123456789abcdef\"";
+        let modified = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+        let request = |headers: &[(HeaderName, String)]| {
+            let mut request = TestRequest::get();
+            for header in headers {
+                request = request.insert_header(header.clone());
+            }
+            request.to_http_request()
+        };
+        let http_date = |time: SystemTime| HttpDate::from(time).to_string();
+
+        assert!(!not_modified(&request(&[]), etag, Some(modified)));
+
+        for if_none_match in [etag.to_string(), format!("W/{}", etag), "*".to_string()] {
+            assert!(not_modified(
+                &request(&[(IF_NONE_MATCH, if_none_match)]),
+                etag,
+                None
+            ));
+        }
+        assert!(not_modified(
+            &request(&[(IF_NONE_MATCH, format!("\"other\", {}", etag))]),
+            etag,
+            None
+        ));
+        assert!(!not_modified(
+            &request(&[(IF_NONE_MATCH, "\"other\"".to_string())]),
+            etag,
+            None
+        ));
+
+        let since = |time| request(&[(IF_MODIFIED_SINCE, http_date(time))]);
+        assert!(not_modified(&since(modified), etag, Some(modified)));
+        // http dates drop the fraction of a second
+        assert!(not_modified(
+            &since(modified),
+            etag,
+            Some(modified + std::time::Duration::from_millis(500))
+        ));
+        assert!(!not_modified(
+            &since(modified),
+            etag,
+            Some(modified + std::time::Duration::from_secs(1))
+        ));
+        assert!(!not_modified(&since(modified), etag, None));
+
+        // a stale etag wins over a current date
+        assert!(!not_modified(
+            &request(&[
+                (IF_NONE_MATCH, "\"other\"".to_string()),
+                (IF_MODIFIED_SINCE, http_date(modified)),
+            ]),
+            etag,
+            Some(modified)
+        ));
+    }
+
+    #[actix_web::test]
+    async fn changing_the_reading_period_changes_the_plot() {
+        let folder = folder("plot-max-gap");
+        let state = folder.state();
+        let app = init_service(
+            App::new()
+                .app_data(state.clone())
+                .service(plot_location_handler),
         )
-        .x_label_area_size(20)
-        .y_label_area_size(40)
-        .build_cartesian_2d(0f32..100f32, 0f32..highest_temp)
-        .unwrap();
-
-    // Probably don't want to add an X Axis description since the time is arbitrary if we have scaled the data. For that, it is much more useful to simply use the CSV data
-    // chart.configure_mesh().x_desc("Time").draw().unwrap();
-    // A Y-Axis description would indeed be useful however
-    chart
-        .configure_mesh()
-        .y_label_style(("sans-serif", 14).into_font())
-        .y_desc("Humidity (%) / Temperature (F)")
-        .axis_desc_style(("sans-serif", 14).into_font())
-        .draw()
-        .unwrap();
-
-    info!("Got data");
-
-    let temperature_style: ShapeStyle = RED.into();
-    let humidity_style: ShapeStyle = GREEN.into();
-    let stroke_width = 4;
-    let point_size = 3;
-
-    chart
-        .draw_series(
-            LineSeries::new(humidity_data, humidity_style.stroke_width(stroke_width))
-                .point_size(point_size),
+        .await;
+        let uri = "/plot/kitchen?last=1h";
+        let etag =
+            |resp: &actix_web::dev::ServiceResponse| resp.headers().get(ETAG).unwrap().clone();
+
+        let first = call_service(&app, TestRequest::get().uri(uri).to_request()).await;
+        let first_etag = etag(&first);
+        let resp = call_service(
+            &app,
+            TestRequest::get()
+                .uri(uri)
+                .insert_header((IF_NONE_MATCH, first_etag.clone()))
+                .to_request(),
         )
-        .unwrap()
-        .label("Humidity")
-        .legend(|(x, y)| Rectangle::new([(x - 15, y + 1), (x, y)], GREEN));
-
-    chart
-        .draw_series(
-            LineSeries::new(
-                temperature_data,
-                temperature_style.stroke_width(stroke_width),
+        .await;
+        assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+
+        // a longer reading period moves where lines break and outages start
+        state
+            .metadata
+            .lock()
+            .await
+            .set(
+                &"kitchen".into(),
+                LocationMetadata {
+                    reading_period_seconds: Some(3600),
+                    ..Default::default()
+                },
             )
-            .point_size(point_size),
+            .await
+            .unwrap();
+
+        let resp = call_service(
+            &app,
+            TestRequest::get()
+                .uri(uri)
+                .insert_header((IF_NONE_MATCH, first_etag.clone()))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_ne!(etag(&resp), first_etag);
+    }
+
+    #[actix_web::test]
+    async fn images_follow_the_accept_header() {
+        let folder = folder("plot-formats");
+        let app = init_service(
+            App::new()
+                .app_data(folder.state())
+                .service(plot_location_handler),
         )
-        .unwrap()
-        .label("Temperature")
-        .legend(|(x, y)| Rectangle::new([(x - 15, y + 1), (x, y)], RED));
-
-    chart
-        .configure_series_labels()
-        .position(SeriesLabelPosition::UpperRight)
-        .margin(20)
-        .legend_area_size(5)
-        .border_style(BLUE)
-        .background_style(BLUE.mix(0.1))
-        .label_font(("Calibri", 20))
-        .draw()
-        .unwrap();
-
-    backend.present().unwrap();
-
-    let file_data = fs::read(&path).await.unwrap();
-    let content = from_utf8(&file_data).unwrap().to_string();
-
-    HttpResponseBuilder::new(StatusCode::OK)
-        // .append_header(("Content-Disposition", "inline"))
-        .content_type("image/svg+xml")
-        .body(content)
+        .await;
+
+        for (accept, content_type, signature) in [
+            ("image/png", "image/png", &b"\x89PNG\r\n\x1a\n"[..]),
+            ("image/webp", "image/webp", b"RIFF"),
+            ("image/svg+xml", "image/svg+xml", b"<svg"),
+        ] {
+            let resp = call_service(
+                &app,
+                TestRequest::get()
+                    .uri("/plot/kitchen?last=1h&width=400&height=300")
+                    .insert_header((ACCEPT, accept))
+                    .to_request(),
+            )
+            .await;
+            assert_eq!(resp.status(), StatusCode::OK, "{}", accept);
+            assert_eq!(resp.headers().get(CONTENT_TYPE).unwrap(), content_type);
+            assert_eq!(resp.headers().get(VARY).unwrap(), "accept");
+            let body = read_body(resp).await;
+            assert!(body.starts_with(signature), "{}", accept);
+            if accept == "image/webp" {
+                assert_eq!(&body[8..12], b"WEBP");
+            }
+        }
+    }
 }
@@ -0,0 +1,182 @@
+use crate::derived::DerivedMetrics;
+use crate::location::Location;
+use crate::metric::Metric;
+use crate::reading::Reading;
+use crate::state::TemperatureServerState;
+use actix_web::http::header::ContentDisposition;
+use actix_web::http::StatusCode;
+use actix_web::{get, web, HttpResponse, HttpResponseBuilder, Responder};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use utoipa::{IntoParams, ToSchema};
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ReadingsQuery {
+    /// Start of the range, inclusive
+    from: Option<DateTime<Local>>,
+    /// End of the range, exclusive
+    to: Option<DateTime<Local>>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ReadingWithMetrics {
+    #[serde(flatten)]
+    reading: Reading,
+    #[serde(flatten)]
+    derived: DerivedMetrics,
+}
+
+impl From<Reading> for ReadingWithMetrics {
+    fn from(reading: Reading) -> Self {
+        Self {
+            derived: DerivedMetrics::from(&reading),
+            reading,
+        }
+    }
+}
+
+async fn readings_or_error(
+    state: &TemperatureServerState,
+    location: &Location,
+    query: &ReadingsQuery,
+) -> Result<Vec<Reading>, HttpResponse> {
+    match state
+        .get_readings_between(location, query.from, query.to)
+        .await
+    {
+        Ok(Some(readings)) => Ok(readings),
+        Ok(None) => Err(HttpResponseBuilder::new(StatusCode::NOT_FOUND)
+            .body(format!("Unknown location: {}", location))),
+        Err(err) => {
+            error!("Error reading location {}: {}", location, err);
+            Err(HttpResponseBuilder::new(StatusCode::INTERNAL_SERVER_ERROR).finish())
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "readings",
+    params(
+        ("location" = String, Path, description = "Location name"),
+        ReadingsQuery,
+    ),
+    responses(
+        (status = 200, description = "Every reading in the range with its derived metrics", body = Vec<ReadingWithMetrics>),
+        (status = 404, description = "Unknown location"),
+    )
+)]
+#[get("/api/v1/locations/{location}/readings")]
+pub async fn readings_handler(
+    location: web::Path<String>,
+    query: web::Query<ReadingsQuery>,
+    state: web::Data<TemperatureServerState>,
+) -> impl Responder {
+    let location: Location = location.as_str().into();
+
+    match readings_or_error(&state, &location, &query).await {
+        Ok(readings) => HttpResponseBuilder::new(StatusCode::OK).json(
+            readings
+                .into_iter()
+                .map(ReadingWithMetrics::from)
+                .collect::<Vec<_>>(),
+        ),
+        Err(resp) => resp,
+    }
+}
+
+#[utoipa::path(
+    tag = "readings",
+    params(
+        ("location" = String, Path, description = "Location name"),
+        ReadingsQuery,
+    ),
+    responses(
+        (status = 200, description = "Every reading in the range with its derived metrics", content_type = "text/csv", body = String),
+        (status = 404, description = "Unknown location"),
+    )
+)]
+#[get("/api/v1/locations/{location}/export.csv")]
+pub async fn export_csv_handler(
+    location: web::Path<String>,
+    query: web::Query<ReadingsQuery>,
+    state: web::Data<TemperatureServerState>,
+) -> impl Responder {
+    let location: Location = location.as_str().into();
+
+    let readings = match readings_or_error(&state, &location, &query).await {
+        Ok(readings) => readings,
+        Err(resp) => return resp,
+    };
+
+    let mut writer = csv::Writer::from_writer(vec![]);
+
+    let header = std::iter::once("time").chain(Metric::ALL.iter().map(|metric| metric.as_str()));
+    let written = writer.write_record(header).and_then(|_| {
+        readings.iter().try_for_each(|reading| {
+            writer.write_record(
+                std::iter::once(reading.reading_time().to_rfc3339()).chain(
+                    Metric::ALL
+                        .iter()
+                        .map(|metric| format!("{:.2}", metric.value(reading))),
+                ),
+            )
+        })
+    });
+
+    match written.map_err(|err| err.to_string()).and_then(|_| {
+        writer.into_inner().map_err(|err| err.to_string())
+    }) {
+        Ok(data) => HttpResponseBuilder::new(StatusCode::OK)
+            .content_type("text/csv")
+            .insert_header(ContentDisposition::attachment(format!(
+                "{}.csv",
+                location.as_str()
+            )))
+            .body(data),
+        Err(err) => {
+            error!("Error writing csv export for {}: {}", location, err);
+            HttpResponseBuilder::new(StatusCode::INTERNAL_SERVER_ERROR).finish()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{readings, TestFolder};
+    use actix_web::http::header;
+    use actix_web::test::{call_service, init_service, read_body, TestRequest};
+    use actix_web::App;
+
+    #[actix_web::test]
+    async fn export_names_the_file_after_the_location() {
+        let folder = TestFolder::new("query-export");
+        folder.write_location("say \"hi\"", &readings("say \"hi\"", Local::now(), 3));
+        let app = init_service(
+            App::new()
+                .app_data(folder.state())
+                .service(export_csv_handler),
+        )
+        .await;
+
+        let resp = call_service(
+            &app,
+            TestRequest::get()
+                .uri("/api/v1/locations/say%20%22hi%22/export.csv")
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_DISPOSITION).unwrap(),
+            r#"attachment; filename="say \"hi\".csv""#
+        );
+
+        let body = read_body(resp).await;
+        let body = std::str::from_utf8(&body).unwrap();
+        assert!(body.starts_with("time,temperature,"));
+        assert_eq!(body.lines().count(), 4);
+    }
+}
@@ -1,10 +1,15 @@
+use crate::derived::celsius_to_fahrenheit;
 use crate::location::Location;
 use actix_web::web;
 use actix_web::web::Path;
-use chrono::{DateTime, Local};
-use std::path::PathBuf;
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use serde::Serialize;
+use utoipa::ToSchema;
 
-#[derive(Debug)]
+// Format used for the first column of every location csv file, e.g. "01/31/2025 01:02:03 PM"
+pub const FILE_TIME_FORMAT: &str = "%m/%d/%Y %I:%M:%S %p";
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
 pub struct Reading {
     location: Location,
     temperature: f32,
@@ -13,6 +18,39 @@ pub struct Reading {
 }
 
 impl Reading {
+    pub fn new(
+        location: Location,
+        temperature: f32,
+        humidity: f32,
+        reading_time: DateTime<Local>,
+    ) -> Self {
+        Self {
+            location,
+            temperature,
+            humidity,
+            reading_time,
+        }
+    }
+
+    /// Parses a single line of a location csv file, returns None for the header or a malformed line.
+    pub fn from_file_line(location: &Location, line: &str) -> Option<Self> {
+        let mut columns = line.split(',').map(|s| s.trim());
+
+        let reading_time = columns
+            .next()
+            .and_then(|s| NaiveDateTime::parse_from_str(s, FILE_TIME_FORMAT).ok())
+            .and_then(|time| Local.from_local_datetime(&time).earliest())?;
+        let temperature = columns.next().and_then(|s| s.parse().ok())?;
+        let humidity = columns.next().and_then(|s| s.parse().ok())?;
+
+        Some(Self::new(
+            location.clone(),
+            temperature,
+            humidity,
+            reading_time,
+        ))
+    }
+
     pub fn location(&self) -> Location {
         self.location.as_str().into()
     }
@@ -25,24 +63,15 @@ impl Reading {
         self.humidity
     }
 
-    pub fn path(&self) -> PathBuf {
-        self.location.path()
-    }
-
     pub fn format_to_file(&self) -> String {
-        let date_string = format!("{}", self.reading_time.format("%m/%d/%Y"));
-        let time_string = format!("{}", self.reading_time.format("%I:%M:%S %p"));
-
         format!(
-            "{} {},{},{}\n",
-            date_string,
-            time_string,
+            "{},{},{}\n",
+            self.reading_time.format(FILE_TIME_FORMAT),
             self.temperature(),
             self.humidity()
         )
     }
 
-    #[allow(dead_code)]
     pub fn reading_time(&self) -> DateTime<Local> {
         self.reading_time
     }
@@ -53,10 +82,8 @@ impl From<web::Path<(String, f32, f32)>> for Reading {
         let value = value.into_inner();
         Self {
             location: value.0.into(),
-            temperature: {
-                // We convert the reading to Fahrenheit since the sensor itself spits out Celcius measurements.
-                (value.1 * 1.8) + 32.0
-            },
+            // We convert the reading to Fahrenheit since the sensor itself spits out Celcius measurements.
+            temperature: celsius_to_fahrenheit(value.1),
             humidity: value.2,
             reading_time: Local::now(),
         }
@@ -1,12 +1,25 @@
+use crate::notifier::Notification;
 use crate::reading::Reading;
 use crate::state::{LocationInfo, TemperatureServerState};
-use crate::LOG_FOLDER_PATH;
 use actix_web::http::StatusCode;
 use actix_web::{get, web, HttpResponseBuilder, Responder};
 use tokio::fs::OpenOptions;
 use tokio::io::AsyncWriteExt;
 use tracing::{error, info};
 
+#[utoipa::path(
+    tag = "readings",
+    params(
+        ("location" = String, Path, description = "Name of the location the sensor is in"),
+        ("temperature" = f32, Path, description = "Temperature in Celsius"),
+        ("humidity" = f32, Path, description = "Relative humidity in percent"),
+    ),
+    responses(
+        (status = 201, description = "Reading stored, this was the first reading for the location"),
+        (status = 200, description = "Reading stored"),
+        (status = 500, description = "The location file could not be opened"),
+    )
+)]
 #[get("/reading/{location}/{temperature}/{humidity}")]
 pub async fn reading_handler(
     reading: web::Path<(String, f32, f32)>,
@@ -23,17 +36,16 @@ pub async fn reading_handler(
 
     let mut lock = state.file_buf_list.lock().await;
 
-    match lock.get_mut(&location) {
+    let response = match lock.get_mut(&location) {
         None => {
-            let file_path = LOG_FOLDER_PATH.join(reading.path());
+            let file_path = state.location_path(&location);
             let file_already_exists = file_path.exists();
 
             match OpenOptions::new()
                 .append(true)
-                .write(true)
                 .read(true)
                 .create(true) // TODO: this could be create_new(true) which would move us to error case if the file already exists, which would allow us to have possibly more clean code?
-                .open(file_path)
+                .open(&file_path)
                 .await
             {
                 Ok(mut file) => {
@@ -48,7 +60,11 @@ pub async fn reading_handler(
 
                     let _ = file.write(file_format_data.as_bytes()).await.unwrap();
                     info!("Wrote to file");
-                    lock.insert(location.clone(), LocationInfo::from(file));
+                    let mut location_info = LocationInfo::new(file, file_path);
+                    // there being nobody watching the stream is not an error
+                    let _ = state.readings.send(reading.clone());
+                    location_info.record_reading(reading.clone());
+                    lock.insert(location.clone(), location_info);
 
                     HttpResponseBuilder::new(StatusCode::CREATED).await.unwrap()
                 }
@@ -67,8 +83,27 @@ pub async fn reading_handler(
                 .await
                 .unwrap();
             info!("Wrote to file");
+            let _ = state.readings.send(reading.clone());
+            file.record_reading(reading.clone());
 
             HttpResponseBuilder::new(StatusCode::OK).await.unwrap()
         }
+    };
+    drop(lock);
+
+    if response.status().is_success() {
+        let group = state.metadata.lock().await.get(&location).group;
+        let events = state
+            .alerts
+            .lock()
+            .await
+            .process(&reading, group.as_deref())
+            .await;
+        for event in events {
+            // there being no notifiers is not an error
+            let _ = state.notifications.send(Notification::Alert(event));
+        }
     }
+
+    response
 }
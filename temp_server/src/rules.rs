@@ -0,0 +1,351 @@
+use crate::location::Location;
+use crate::metadata::TemperatureUnit;
+use crate::metric::Metric;
+use chrono::{DateTime, Duration, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use utoipa::ToSchema;
+
+// How many readings the baseline of a `deviates` condition needs before it is trusted
+const MIN_BASELINE_READINGS: usize = 30;
+
+/// When a rule's metric is out of bounds, in the unit of the rule.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    Above(f32),
+    Below(f32),
+    /// Below the first or above the second
+    Outside(f32, f32),
+    /// Went up by at least `by` within the last `minutes`
+    Rises {
+        by: f32,
+        minutes: u32,
+    },
+    /// Went down by at least `by` within the last `minutes`, such as a door left open
+    Falls {
+        by: f32,
+        minutes: u32,
+    },
+    /// Stayed within `tolerance` for at least `minutes`, such as a stuck sensor
+    Flatline {
+        minutes: u32,
+        #[serde(default)]
+        tolerance: f32,
+    },
+    /// At least `z_score` standard deviations from the mean of the last `baseline_hours`
+    Deviates {
+        z_score: f32,
+        baseline_hours: u32,
+    },
+}
+
+impl Condition {
+    /// Whether a new value is out of bounds, `recent` are the values of the earlier readings
+    /// of the location, oldest first, going back at least [`Condition::window`] when there are
+    /// that many.
+    pub fn matches(
+        &self,
+        value: f32,
+        time: DateTime<Local>,
+        recent: &[(DateTime<Local>, f32)],
+    ) -> bool {
+        let since = time - self.window();
+        let within = || {
+            recent
+                .iter()
+                .filter(move |(reading_time, _)| *reading_time >= since)
+                .map(|(_, value)| *value)
+        };
+
+        match *self {
+            Condition::Above(limit) => value > limit,
+            Condition::Below(limit) => value < limit,
+            Condition::Outside(low, high) => value < low || value > high,
+            Condition::Rises { by, .. } => within().any(|earlier| value - earlier >= by),
+            Condition::Falls { by, .. } => within().any(|earlier| earlier - value >= by),
+            Condition::Flatline { tolerance, .. } => {
+                // the last reading from before the window shows the value was already there
+                // when it started
+                let Some(start) = recent
+                    .iter()
+                    .rposition(|(reading_time, _)| *reading_time <= since)
+                else {
+                    return false;
+                };
+                recent[start..]
+                    .iter()
+                    .all(|(_, earlier)| (value - earlier).abs() <= tolerance)
+            }
+            Condition::Deviates { z_score, .. } => {
+                let baseline = within().collect::<Vec<_>>();
+                if baseline.len() < MIN_BASELINE_READINGS {
+                    return false;
+                }
+                let mean = baseline.iter().sum::<f32>() / baseline.len() as f32;
+                let variance = baseline
+                    .iter()
+                    .map(|earlier| (earlier - mean).powi(2))
+                    .sum::<f32>()
+                    / baseline.len() as f32;
+                let deviation = variance.sqrt();
+                deviation > f32::EPSILON && (value - mean).abs() / deviation >= z_score
+            }
+        }
+    }
+
+    /// How far back the condition looks at earlier readings.
+    pub fn window(&self) -> Duration {
+        match *self {
+            Condition::Above(_) | Condition::Below(_) | Condition::Outside(_, _) => {
+                Duration::zero()
+            }
+            Condition::Rises { minutes, .. }
+            | Condition::Falls { minutes, .. }
+            | Condition::Flatline { minutes, .. } => Duration::minutes(minutes as i64),
+            Condition::Deviates { baseline_hours, .. } => Duration::hours(baseline_hours as i64),
+        }
+    }
+
+    pub fn describe(&self, unit: &str) -> String {
+        match self {
+            Condition::Above(limit) => format!("above {:.1} {}", limit, unit),
+            Condition::Below(limit) => format!("below {:.1} {}", limit, unit),
+            Condition::Outside(low, high) => {
+                format!("outside {:.1} to {:.1} {}", low, high, unit)
+            }
+            Condition::Rises { by, minutes } => {
+                format!("up {:.1} {} or more within {} minutes", by, unit, minutes)
+            }
+            Condition::Falls { by, minutes } => {
+                format!("down {:.1} {} or more within {} minutes", by, unit, minutes)
+            }
+            Condition::Flatline { minutes, tolerance } => format!(
+                "unchanged (within {:.1} {}) for {} minutes",
+                tolerance, unit, minutes
+            ),
+            Condition::Deviates {
+                z_score,
+                baseline_hours,
+            } => format!(
+                "{:.1} or more standard deviations from its mean over {} hours",
+                z_score, baseline_hours
+            ),
+        }
+    }
+
+    /// Checks the numbers of the condition make sense.
+    fn validate(&self) -> Result<(), String> {
+        match *self {
+            Condition::Above(_) | Condition::Below(_) => Ok(()),
+            Condition::Outside(low, high) if low >= high => {
+                Err("has an outside range with the low end above the high end".to_string())
+            }
+            Condition::Outside(_, _) => Ok(()),
+            Condition::Rises { by, minutes } | Condition::Falls { by, minutes }
+                if by <= 0.0 || minutes == 0 =>
+            {
+                Err("needs a change above 0 over at least a minute".to_string())
+            }
+            Condition::Rises { .. } | Condition::Falls { .. } => Ok(()),
+            Condition::Flatline { minutes, tolerance } if minutes == 0 || tolerance < 0.0 => {
+                Err("needs at least a minute and a tolerance of 0 or more".to_string())
+            }
+            Condition::Flatline { .. } => Ok(()),
+            Condition::Deviates {
+                z_score,
+                baseline_hours,
+            } if z_score <= 0.0 || baseline_hours == 0 => {
+                Err("needs a z_score above 0 and a baseline of at least an hour".to_string())
+            }
+            Condition::Deviates { .. } => Ok(()),
+        }
+    }
+}
+
+/// An alert rule from the config file, such as
+///
+/// ```toml
+/// [[rules]]
+/// name = "freezer too warm"
+/// locations = ["freezer"]
+/// metric = "temperature"
+/// unit = "celsius"
+/// condition = { above = -10.0 }
+/// for_minutes = 5
+/// ```
+///
+/// A rule applies to the listed locations and every location in `group`, or to every
+/// location when neither is given. Conditions that compare readings, such as
+/// `condition = { falls = { by = 5.0, minutes = 10 } }`, compare them in the rule's unit too.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    /// Identifies the rule in alerts, must be unique
+    pub name: String,
+    #[serde(default)]
+    pub locations: Vec<String>,
+    /// A group from the location metadata
+    pub group: Option<String>,
+    pub metric: Metric,
+    pub condition: Condition,
+    /// Unit of the condition for temperature metrics, defaults to Fahrenheit
+    pub unit: Option<TemperatureUnit>,
+    /// How long the condition has to hold before the alert fires
+    #[serde(default)]
+    pub for_minutes: u32,
+}
+
+impl Rule {
+    pub fn applies_to(&self, location: &Location, group: Option<&str>) -> bool {
+        if self.locations.is_empty() && self.group.is_none() {
+            return true;
+        }
+
+        self.locations.iter().any(|name| name == location.as_str())
+            || self
+                .group
+                .as_deref()
+                .is_some_and(|rule_group| Some(rule_group) == group)
+    }
+
+    pub fn hold_for(&self) -> Duration {
+        Duration::minutes(self.for_minutes as i64)
+    }
+
+    /// Readings are stored in Fahrenheit, this converts a value of the rule's metric to the rule's unit.
+    pub fn convert(&self, value: f32) -> f32 {
+        if self.metric.unit() == "F" {
+            self.unit.unwrap_or_default().convert_fahrenheit(value)
+        } else {
+            value
+        }
+    }
+
+    /// The unit the rule's values are in.
+    pub fn unit_symbol(&self) -> &'static str {
+        if self.metric.unit() == "F" {
+            self.unit.unwrap_or_default().symbol()
+        } else {
+            self.metric.unit()
+        }
+    }
+}
+
+/// Checks rule names are unique and their conditions make sense.
+pub fn validate_rules(rules: &[Rule]) -> Result<(), String> {
+    let mut names = HashSet::new();
+
+    for rule in rules {
+        if rule.name.trim().is_empty() {
+            return Err("Every rule needs a name".to_string());
+        }
+        if !names.insert(rule.name.as_str()) {
+            return Err(format!("Duplicate rule name: {}", rule.name));
+        }
+        if let Err(err) = rule.condition.validate() {
+            return Err(format!("Rule {} {}", rule.name, err));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(toml: &str) -> Rule {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn rules_apply_to_their_locations_and_group() {
+        let everywhere = rule("name = 'a'\nmetric = 'humidity'\ncondition = { above = 60.0 }");
+        assert!(everywhere.applies_to(&"garage".into(), None));
+
+        let scoped = rule(
+            "name = 'b'\nlocations = ['closet']\ngroup = 'Basement'\nmetric = 'humidity'\ncondition = { above = 60.0 }",
+        );
+        assert!(scoped.applies_to(&"closet".into(), None));
+        assert!(scoped.applies_to(&"freezer".into(), Some("Basement")));
+        assert!(!scoped.applies_to(&"garage".into(), Some("Upstairs")));
+    }
+
+    #[test]
+    fn temperature_rules_use_their_unit() {
+        let freezer = rule(
+            "name = 'freezer'\nmetric = 'temperature'\nunit = 'celsius'\ncondition = { above = -10.0 }",
+        );
+        // 16 F is about -8.9 C
+        let now = Local::now();
+        assert!(freezer.condition.matches(freezer.convert(16.0), now, &[]));
+        assert!(!freezer.condition.matches(freezer.convert(12.0), now, &[]));
+
+        let outside =
+            rule("name = 'o'\nmetric = 'humidity'\ncondition = { outside = [30.0, 60.0] }");
+        assert!(outside.condition.matches(outside.convert(65.0), now, &[]));
+        assert!(!outside.condition.matches(outside.convert(45.0), now, &[]));
+    }
+
+    /// (minutes ago, value) pairs as the recent values of a location, oldest first
+    fn recent(now: DateTime<Local>, values: &[(i64, f32)]) -> Vec<(DateTime<Local>, f32)> {
+        values
+            .iter()
+            .map(|(minutes_ago, value)| (now - Duration::minutes(*minutes_ago), *value))
+            .collect()
+    }
+
+    #[test]
+    fn changes_are_compared_within_their_window() {
+        let now = Local::now();
+        // a 5 F drop is a drop of about 2.8 C
+        let door = rule(
+            "name = 'door'\nmetric = 'temperature'\nunit = 'celsius'\ncondition = { falls = { by = 2.5, minutes = 10 } }",
+        );
+        let earlier = recent(now, &[(15, door.convert(80.0)), (8, door.convert(70.0))]);
+        assert!(door.condition.matches(door.convert(65.0), now, &earlier));
+        assert!(!door.condition.matches(door.convert(67.0), now, &earlier));
+
+        let rising = rule(
+            "name = 'rising'\nmetric = 'humidity'\ncondition = { rises = { by = 10.0, minutes = 10 } }",
+        );
+        assert!(rising.condition.matches(62.0, now, &recent(now, &[(5, 50.0)])));
+        assert!(!rising.condition.matches(62.0, now, &recent(now, &[(20, 50.0)])));
+    }
+
+    #[test]
+    fn flatlines_need_their_whole_window() {
+        let now = Local::now();
+        let stuck = rule(
+            "name = 'stuck'\nmetric = 'humidity'\ncondition = { flatline = { minutes = 60, tolerance = 0.1 } }",
+        );
+        let flat = recent(now, &[(90, 40.0), (61, 50.0), (30, 50.05), (1, 50.0)]);
+        assert!(stuck.condition.matches(50.0, now, &flat));
+        assert!(!stuck.condition.matches(50.5, now, &flat));
+        // not enough history to know it has been flat for an hour
+        assert!(!stuck.condition.matches(50.0, now, &flat[2..]));
+    }
+
+    #[test]
+    fn deviations_are_measured_against_the_baseline() {
+        let now = Local::now();
+        let odd = rule(
+            "name = 'odd'\nmetric = 'humidity'\ncondition = { deviates = { z_score = 3.0, baseline_hours = 24 } }",
+        );
+        let baseline = (1..=60)
+            .map(|minutes_ago| (minutes_ago, if minutes_ago % 2 == 0 { 49.0 } else { 51.0 }))
+            .rev()
+            .collect::<Vec<_>>();
+        // the baseline has a mean of 50 and a standard deviation of 1
+        assert!(odd.condition.matches(53.5, now, &recent(now, &baseline)));
+        assert!(!odd.condition.matches(52.5, now, &recent(now, &baseline)));
+        // too few readings to trust
+        assert!(!odd.condition.matches(60.0, now, &recent(now, &baseline[50..])));
+
+        assert!(validate_rules(&[rule(
+            "name = 'bad'\nmetric = 'humidity'\ncondition = { deviates = { z_score = 0.0, baseline_hours = 24 } }"
+        )])
+        .is_err());
+    }
+}
@@ -1,40 +1,126 @@
+use crate::alerts::AlertEngine;
+use crate::config::CONFIG;
 use crate::location::Location;
-use crate::LOG_FOLDER_PATH;
-use chrono::{DateTime, Local};
+use crate::metadata::MetadataStore;
+use crate::notifier::{DeliveryLog, Notification};
+use crate::plot_cache::{ComparisonKey, PlotCache};
+use crate::reading::Reading;
+use crate::statistics::LocationStatistics;
+use crate::{
+    ADMIN_TOKEN, ALERT_LOG_FILE_NAME, ARCHIVE_FOLDER_NAME, DELIVERY_LOG_FILE_NAME, LOG_FOLDER_PATH,
+    METADATA_FILE_NAME,
+};
+use chrono::{DateTime, Duration, Local};
 use std::collections::HashMap;
 use std::fs;
 use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::time::SystemTime;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncSeekExt, BufReader};
+use tokio::sync::{broadcast, Mutex};
+use tracing::warn;
 
 pub struct TemperatureServerState {
     pub file_buf_list: Arc<Mutex<HashMap<Location, LocationInfo>>>,
+    pub plot_cache: Arc<Mutex<PlotCache>>,
+    pub comparison_plot_cache: Arc<Mutex<PlotCache<ComparisonKey>>>,
+    /// Every reading stored from now on, for the live updating pages
+    pub readings: broadcast::Sender<Reading>,
+    /// Locked on its own, never while holding `file_buf_list`
+    pub metadata: Arc<Mutex<MetadataStore>>,
+    /// Locked on its own, never while holding `file_buf_list`
+    pub alerts: Arc<Mutex<AlertEngine>>,
+    /// Alert and health events for the notifiers
+    pub notifications: broadcast::Sender<Notification>,
+    pub deliveries: Arc<Mutex<DeliveryLog>>,
+    /// Bearer token the admin api expects, the admin api is turned off without one
+    pub admin_token: Option<String>,
+    /// Where the location csv files are kept, with the metadata and logs next to them
+    pub log_folder: PathBuf,
 }
 
 pub struct LocationInfo {
     file: tokio::fs::File,
+    // readers open the file again by its path, so they don't need to hold the lock while reading
+    path: PathBuf,
     last_modified: Option<DateTime<Local>>,
+    latest_reading: Option<Reading>,
+    // built from the csv file the first time they are needed, then kept up to date as readings arrive
+    statistics: Option<LocationStatistics>,
+    // readings that arrived while the statistics were being built, Some while that is going on
+    pending_statistics: Option<Vec<Reading>>,
 }
 
-impl From<File> for LocationInfo {
-    fn from(file: File) -> Self {
-        Self {
-            file: file.into(),
-            last_modified: None,
-        }
-    }
+// How many readings a slow stream client can fall behind by before it misses some
+const READING_CHANNEL_CAPACITY: usize = 64;
+
+// How many notifications a slow notifier can fall behind by before it misses some
+const NOTIFICATION_CHANNEL_CAPACITY: usize = 256;
+
+// How far back from the end of a csv file we look for the last reading when starting up
+const LAST_READING_SEARCH_BYTES: u64 = 1024;
+
+// How far before the alert rules' window to start reading, to find the last reading before it
+const ALERT_HISTORY_LEAD: Duration = Duration::hours(1);
+
+// Once the search for the start of a time range is narrowed down to this many bytes we just read forward
+const RANGE_SEARCH_BLOCK_BYTES: u64 = 4096;
+
+// Enough bytes to always contain one complete line after a partial one
+const LINE_PROBE_BYTES: u64 = 128;
+
+/// Finds the last complete reading in a location csv file without reading the whole file.
+fn read_last_reading(file: &mut File, location: &Location) -> std::io::Result<Option<Reading>> {
+    let len = file.metadata()?.len();
+    file.seek(SeekFrom::Start(len.saturating_sub(LAST_READING_SEARCH_BYTES)))?;
+
+    let mut tail = String::new();
+    file.read_to_string(&mut tail)?;
+
+    Ok(tail
+        .lines()
+        .rev()
+        .find_map(|line| Reading::from_file_line(location, line)))
 }
 
-impl From<tokio::fs::File> for LocationInfo {
-    fn from(file: tokio::fs::File) -> Self {
+impl LocationInfo {
+    /// `file` is the location csv file at `path`, opened for appending.
+    pub fn new(file: tokio::fs::File, path: PathBuf) -> Self {
         Self {
             file,
+            path,
             last_modified: None,
+            latest_reading: None,
+            statistics: None,
+            pending_statistics: None,
         }
     }
-}
 
-impl LocationInfo {
+    /// Opens an existing location csv file, restoring the latest reading from it.
+    pub fn open(path: &Path, location: &Location) -> std::io::Result<Self> {
+        let mut file = fs::OpenOptions::new()
+            .append(true)
+            .read(true)
+            .create(true) // TODO: this could be create_new(true) which would move us to error case if the file already exists, which would allow us to have possibly more clean code?
+            .open(path)?;
+
+        let latest_reading = match read_last_reading(&mut file, location) {
+            Ok(reading) => reading,
+            Err(err) => {
+                warn!("Unable to read last reading for {}: {}", location, err);
+                None
+            }
+        };
+
+        let mut location_info = LocationInfo::new(file.into(), path.to_path_buf());
+        // the sensor was last seen when it sent its last stored reading
+        location_info.last_modified = latest_reading.as_ref().map(|reading| reading.reading_time());
+        location_info.latest_reading = latest_reading;
+        Ok(location_info)
+    }
+
     pub fn get_file_mut(&mut self, update_last_modified: bool) -> &mut tokio::fs::File {
         if update_last_modified {
             self.last_modified = Some(Local::now());
@@ -42,57 +128,558 @@ impl LocationInfo {
         &mut self.file
     }
 
+    /// When the csv file was last written to, according to the file system.
+    pub async fn get_last_write_time(&self) -> std::io::Result<SystemTime> {
+        self.file.metadata().await?.modified()
+    }
+
     pub fn get_last_modified(&self) -> Option<&DateTime<Local>> {
         self.last_modified.as_ref()
     }
+
+    pub fn get_latest_reading(&self) -> Option<&Reading> {
+        self.latest_reading.as_ref()
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Updates the in memory view of this location with a reading that was just written to its file.
+    pub fn record_reading(&mut self, reading: Reading) {
+        if let Some(statistics) = self.statistics.as_mut() {
+            statistics.record(&reading);
+        } else if let Some(pending) = self.pending_statistics.as_mut() {
+            pending.push(reading.clone());
+        }
+        self.last_modified = Some(reading.reading_time());
+        self.latest_reading = Some(reading);
+    }
+
+    /// Reads every reading stored in this locations csv file, in the order they were written.
+    pub async fn read_readings(&self, location: &Location) -> std::io::Result<Vec<Reading>> {
+        read_readings_between(&self.path, location, None, None).await
+    }
 }
 
-impl Default for TemperatureServerState {
-    fn default() -> Self {
+/// Reads the readings in the location csv file at `path` taken in `from..to`, either end may be
+/// left open. The file is opened on its own, so this can run while readings are being appended.
+pub async fn read_readings_between(
+    path: &Path,
+    location: &Location,
+    from: Option<DateTime<Local>>,
+    to: Option<DateTime<Local>>,
+) -> std::io::Result<Vec<Reading>> {
+    let mut file = tokio::fs::File::open(path).await?;
+    read_readings_from(&mut file, location, from, to).await
+}
+
+/// Readings are appended in time order, so rather than parsing the whole file this binary
+/// searches for where `from` starts and stops reading once it passes `to`.
+async fn read_readings_from(
+    file: &mut tokio::fs::File,
+    location: &Location,
+    from: Option<DateTime<Local>>,
+    to: Option<DateTime<Local>>,
+) -> std::io::Result<Vec<Reading>> {
+    let start = match from {
+        None => 0,
+        Some(from) => find_offset_before(file, location, from).await?,
+    };
+    file.seek(SeekFrom::Start(start)).await?;
+
+    let mut lines = BufReader::new(file).lines();
+    // the first line is either the header, or part of a line that is before `from`
+    let _ = lines.next_line().await?;
+
+    let mut readings = vec![];
+    while let Some(line) = lines.next_line().await? {
+        match Reading::from_file_line(location, &line) {
+            None => warn!("Bad line in {}: {:?}", location, line),
+            Some(reading) => {
+                if to.is_some_and(|to| reading.reading_time() >= to) {
+                    break;
+                }
+                if from.is_none_or(|from| reading.reading_time() >= from) {
+                    readings.push(reading);
+                }
+            }
+        }
+    }
+
+    Ok(readings)
+}
+
+/// A byte offset in the file where the first complete line after it was taken before `from`,
+/// or 0 if there is no such line.
+async fn find_offset_before(
+    file: &mut tokio::fs::File,
+    location: &Location,
+    from: DateTime<Local>,
+) -> std::io::Result<u64> {
+    let (mut low, mut high) = (0, file.metadata().await?.len());
+
+    while high - low > RANGE_SEARCH_BLOCK_BYTES {
+        let middle = low + (high - low) / 2;
+        if first_time_after(file, location, middle)
+            .await?
+            .is_some_and(|time| time < from)
+        {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+
+    Ok(low)
+}
+
+/// The time of the first complete line that starts after `offset`.
+async fn first_time_after(
+    file: &mut tokio::fs::File,
+    location: &Location,
+    offset: u64,
+) -> std::io::Result<Option<DateTime<Local>>> {
+    file.seek(SeekFrom::Start(offset)).await?;
+
+    let mut probe = vec![];
+    file.take(LINE_PROBE_BYTES).read_to_end(&mut probe).await?;
+
+    Ok(String::from_utf8_lossy(&probe)
+        .split('\n')
+        .nth(1)
+        .and_then(|line| Reading::from_file_line(location, line))
+        .map(|reading| reading.reading_time()))
+}
+
+impl TemperatureServerState {
+    /// Ok(None) if the location has never reported.
+    pub async fn get_last_write_time(&self, location: &Location) -> std::io::Result<Option<SystemTime>> {
+        let lock = self.file_buf_list.lock().await;
+
+        match lock.get(location) {
+            None => Ok(None),
+            Some(location_info) => location_info.get_last_write_time().await.map(Some),
+        }
+    }
+
+    /// Gives the alert rules that look back at earlier readings the readings stored before the
+    /// server started, so they don't have to wait for new ones to fill their window.
+    pub async fn load_alert_history(&self) {
+        let Some(window) = self.alerts.lock().await.window() else {
+            return;
+        };
+        // the last reading from before the window too, see `AlertEngine::remember_reading`
+        let since = Local::now() - window - ALERT_HISTORY_LEAD;
+
+        let paths = self
+            .file_buf_list
+            .lock()
+            .await
+            .iter()
+            .map(|(location, location_info)| (location.clone(), location_info.path().to_path_buf()))
+            .collect::<Vec<_>>();
+
+        let mut readings = vec![];
+        for (location, path) in paths {
+            match read_readings_between(&path, &location, Some(since), None).await {
+                Ok(location_readings) => readings.extend(location_readings),
+                Err(err) => warn!("Unable to read {} for the alert rules: {}", location, err),
+            }
+        }
+
+        let mut alerts = self.alerts.lock().await;
+        for reading in readings {
+            alerts.remember_reading(reading);
+        }
+    }
+
+    /// Reads the stored readings for a location taken in `from..to`, either end may be left open.
+    /// Ok(None) if the location has never reported.
+    pub async fn get_readings_between(
+        &self,
+        location: &Location,
+        from: Option<DateTime<Local>>,
+        to: Option<DateTime<Local>>,
+    ) -> std::io::Result<Option<Vec<Reading>>> {
+        let path = match self.file_buf_list.lock().await.get(location) {
+            None => return Ok(None),
+            Some(location_info) => location_info.path().to_path_buf(),
+        };
+
+        read_readings_between(&path, location, from, to).await.map(Some)
+    }
+
+    /// The statistics for a location, built from its csv file the first time they are asked for.
+    /// The file is read without holding the lock, so readings keep being stored meanwhile.
+    /// Ok(None) if the location has never reported.
+    pub async fn get_statistics(
+        &self,
+        location: &Location,
+    ) -> std::io::Result<Option<LocationStatistics>> {
+        let path = match self.file_buf_list.lock().await.get_mut(location) {
+            None => return Ok(None),
+            Some(location_info) => {
+                if let Some(statistics) = &location_info.statistics {
+                    return Ok(Some(statistics.clone()));
+                }
+                location_info.pending_statistics.get_or_insert_default();
+                location_info.path.clone()
+            }
+        };
+
+        let readings = read_readings_between(&path, location, None, None).await?;
+        let mut statistics = LocationStatistics::from_readings(&readings);
+        let last_read = readings.last().map(|reading| reading.reading_time());
+
+        let mut lock = self.file_buf_list.lock().await;
+        let Some(location_info) = lock.get_mut(location) else {
+            return Ok(Some(statistics));
+        };
+        if let Some(built) = &location_info.statistics {
+            // someone else got there first
+            return Ok(Some(built.clone()));
+        }
+        // readings stored while the file was being read may or may not have made it in
+        for reading in location_info.pending_statistics.take().unwrap_or_default() {
+            if last_read.is_none_or(|last_read| reading.reading_time() > last_read) {
+                statistics.record(&reading);
+            }
+        }
+        location_info.statistics = Some(statistics.clone());
+        Ok(Some(statistics))
+    }
+}
+
+impl TemperatureServerState {
+    /// Opens every location csv file in `folder`, along with the metadata and logs kept next to them.
+    /// The admin api is turned off.
+    pub fn load(folder: &Path) -> Self {
         let mut hash_map = HashMap::new();
 
-        match fs::read_dir(LOG_FOLDER_PATH.clone()) {
-            Ok(dir) => {
-                dir.into_iter()
-                    .filter_map(|entry| {
-                        entry
-                            .ok()
-                            .and_then(|entry_dir| match entry_dir.file_name().to_str() {
-                                None => None,
-                                Some(file_name) => {
-                                    if file_name.contains(".csv") {
-                                        Some(entry_dir)
-                                    } else {
-                                        None
-                                    }
+        if let Ok(dir) = fs::read_dir(folder) {
+            dir.into_iter()
+                .filter_map(|entry| {
+                    entry
+                        .ok()
+                        .and_then(|entry_dir| match entry_dir.file_name().to_str() {
+                            None => None,
+                            Some(file_name) => {
+                                if file_name.contains(".csv") {
+                                    Some(entry_dir)
+                                } else {
+                                    None
                                 }
-                            })
-                    })
-                    .filter_map(|entry| {
-                        entry
-                            .file_name()
-                            .to_str()
-                            .map(|name| (name.to_string(), entry.path()))
-                    })
-                    .for_each(|(csv_filename, entry_path)| {
-                        hash_map.insert(
-                            csv_filename.replace(".csv", "").into(),
-                            fs::OpenOptions::new()
-                                .append(true)
-                                .write(true)
-                                .read(true)
-                                .create(true) // TODO: this could be create_new(true) which would move us to error case if the file already exists, which would allow us to have possibly more clean code?
-                                .open(entry_path)
-                                .unwrap()
-                                .into(),
-                        );
-                    });
-            }
-            Err(_) => {}
+                            }
+                        })
+                })
+                .filter_map(|entry| {
+                    entry
+                        .file_name()
+                        .to_str()
+                        .map(|name| (name.to_string(), entry.path()))
+                })
+                .for_each(|(csv_filename, entry_path)| {
+                    let location: Location = csv_filename.replace(".csv", "").into();
+                    let location_info = LocationInfo::open(&entry_path, &location).unwrap();
+                    hash_map.insert(location, location_info);
+                });
         }
 
         Self {
             file_buf_list: Arc::new(Mutex::new(hash_map)),
+            plot_cache: Arc::new(Mutex::new(PlotCache::default())),
+            comparison_plot_cache: Arc::new(Mutex::new(PlotCache::default())),
+            readings: broadcast::channel(READING_CHANNEL_CAPACITY).0,
+            metadata: Arc::new(Mutex::new(MetadataStore::load(
+                folder.join(METADATA_FILE_NAME),
+            ))),
+            alerts: Arc::new(Mutex::new(AlertEngine::load(
+                CONFIG.rules.clone(),
+                folder.join(ALERT_LOG_FILE_NAME),
+            ))),
+            notifications: broadcast::channel(NOTIFICATION_CHANNEL_CAPACITY).0,
+            deliveries: Arc::new(Mutex::new(DeliveryLog::load(
+                folder.join(DELIVERY_LOG_FILE_NAME),
+            ))),
+            admin_token: None,
+            log_folder: folder.to_path_buf(),
+        }
+    }
+
+    /// Where the csv file of `location` is, or goes when it first reports.
+    pub fn location_path(&self, location: &Location) -> PathBuf {
+        self.log_folder.join(location.path())
+    }
+
+    /// Where the admin api moves archived locations to.
+    pub fn archive_folder(&self) -> PathBuf {
+        self.log_folder.join(ARCHIVE_FOLDER_NAME)
+    }
+}
+
+impl Default for TemperatureServerState {
+    fn default() -> Self {
+        Self {
+            admin_token: ADMIN_TOKEN.clone(),
+            ..Self::load(&LOG_FOLDER_PATH)
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{readings, TestFolder};
+    use chrono::TimeZone;
+
+    fn end() -> DateTime<Local> {
+        Local.with_ymd_and_hms(2025, 3, 1, 12, 0, 0).unwrap()
+    }
+
+    /// A csv file of `count` readings a minute apart up to `end`, big enough to be searched.
+    fn write_readings(folder: &TestFolder, count: usize) -> (PathBuf, Vec<Reading>) {
+        let stored = readings("kitchen", end(), count);
+        folder.write_location("kitchen", &stored);
+        (folder.location_path("kitchen"), stored)
+    }
+
+    async fn between(
+        path: &Path,
+        from: Option<DateTime<Local>>,
+        to: Option<DateTime<Local>>,
+    ) -> Vec<DateTime<Local>> {
+        read_readings_between(path, &"kitchen".into(), from, to)
+            .await
+            .unwrap()
+            .iter()
+            .map(|reading| reading.reading_time())
+            .collect()
+    }
+
+    fn times(readings: &[Reading]) -> Vec<DateTime<Local>> {
+        readings
+            .iter()
+            .map(|reading| reading.reading_time())
+            .collect()
+    }
+
+    #[actix_web::test]
+    async fn ranges_are_read_from_where_they_start() {
+        let folder = TestFolder::new("state-range");
+        let (path, stored) = write_readings(&folder, 1000);
+        assert!(fs::metadata(&path).unwrap().len() > 4 * RANGE_SEARCH_BLOCK_BYTES);
+        let first = stored[0].reading_time();
+        let last = stored[999].reading_time();
+
+        assert_eq!(between(&path, None, None).await, times(&stored));
+        assert_eq!(
+            between(&path, Some(first - Duration::days(1)), None).await,
+            times(&stored)
+        );
+        assert!(between(&path, Some(last + Duration::seconds(1)), None)
+            .await
+            .is_empty());
+        assert_eq!(
+            between(&path, Some(last), None).await,
+            times(&stored[999..])
+        );
+        assert_eq!(
+            between(&path, Some(stored[500].reading_time()), None).await,
+            times(&stored[500..])
+        );
+        // somewhere between two readings
+        assert_eq!(
+            between(
+                &path,
+                Some(stored[500].reading_time() + Duration::seconds(30)),
+                None
+            )
+            .await,
+            times(&stored[501..])
+        );
+    }
+
+    #[actix_web::test]
+    async fn ranges_stop_before_to() {
+        let folder = TestFolder::new("state-range-to");
+        let (path, stored) = write_readings(&folder, 1000);
+
+        assert_eq!(
+            between(
+                &path,
+                Some(stored[200].reading_time()),
+                Some(stored[300].reading_time())
+            )
+            .await,
+            times(&stored[200..300])
+        );
+        assert_eq!(
+            between(&path, None, Some(stored[10].reading_time())).await,
+            times(&stored[..10])
+        );
+        assert!(between(&path, None, Some(stored[0].reading_time()))
+            .await
+            .is_empty());
+    }
+
+    #[actix_web::test]
+    async fn probes_find_the_next_whole_line() {
+        let folder = TestFolder::new("state-probe");
+        let (path, stored) = write_readings(&folder, 10);
+        let location: Location = "kitchen".into();
+        let mut file = tokio::fs::File::open(&path).await.unwrap();
+
+        let header = "Date,Time,Temperature,Humidity\n".len() as u64;
+        let line = stored[0].format_to_file().len() as u64;
+
+        // from the start of a line, that line might have been cut off so the next one is used
+        let at_line_start = header + 3 * line;
+        assert_eq!(
+            first_time_after(&mut file, &location, at_line_start)
+                .await
+                .unwrap(),
+            Some(stored[4].reading_time())
+        );
+        // from the middle of a line the rest of it is skipped
+        assert_eq!(
+            first_time_after(&mut file, &location, at_line_start + line / 2)
+                .await
+                .unwrap(),
+            Some(stored[4].reading_time())
+        );
+        // the header is skipped too
+        assert_eq!(
+            first_time_after(&mut file, &location, 0).await.unwrap(),
+            Some(stored[0].reading_time())
+        );
+        // past the last line there is nothing
+        assert_eq!(
+            first_time_after(&mut file, &location, header + 9 * line + 1)
+                .await
+                .unwrap(),
+            None
+        );
+    }
+
+    #[actix_web::test]
+    async fn lines_longer_than_a_probe_are_read_past() {
+        let folder = TestFolder::new("state-long-line");
+        let stored = readings("kitchen", end(), 1000);
+        let mut contents = "Date,Time,Temperature,Humidity\n".to_string();
+        for (idx, reading) in stored.iter().enumerate() {
+            if idx % 100 == 50 {
+                contents.push_str(&"x".repeat(LINE_PROBE_BYTES as usize * 3));
+                contents.push('\n');
+            }
+            contents.push_str(&reading.format_to_file());
+        }
+        let path = folder.location_path("kitchen");
+        fs::write(&path, contents).unwrap();
+
+        let mut file = tokio::fs::File::open(&path).await.unwrap();
+        let long_line = "Date,Time,Temperature,Humidity\n".len() as u64
+            + 50 * stored[0].format_to_file().len() as u64;
+        assert_eq!(
+            first_time_after(&mut file, &"kitchen".into(), long_line)
+                .await
+                .unwrap(),
+            None
+        );
+
+        for idx in [0, 49, 50, 51, 450, 999] {
+            assert_eq!(
+                between(&path, Some(stored[idx].reading_time()), None).await,
+                times(&stored[idx..]),
+                "from reading {}",
+                idx
+            );
+        }
+    }
+
+    #[actix_web::test]
+    async fn empty_files_have_no_readings() {
+        let folder = TestFolder::new("state-empty");
+        let path = folder.location_path("kitchen");
+        let location: Location = "kitchen".into();
+
+        fs::write(&path, "").unwrap();
+        assert!(between(&path, None, None).await.is_empty());
+        assert!(
+            between(&path, Some(end()), Some(end() + Duration::hours(1)))
+                .await
+                .is_empty()
+        );
+        let mut file = tokio::fs::File::open(&path).await.unwrap();
+        assert_eq!(
+            find_offset_before(&mut file, &location, end())
+                .await
+                .unwrap(),
+            0
+        );
+
+        fs::write(&path, "Date,Time,Temperature,Humidity\n").unwrap();
+        assert!(between(&path, Some(end()), None).await.is_empty());
+    }
+
+    #[actix_web::test]
+    async fn statistics_catch_up_on_readings_stored_while_they_are_built() {
+        let folder = TestFolder::new("state-statistics");
+        let stored = readings("kitchen", Local::now(), 3);
+        folder.write_location("kitchen", &stored);
+        let state = folder.state();
+        let location: Location = "kitchen".into();
+
+        let newer = Reading::new(
+            location.clone(),
+            90.0,
+            50.0,
+            Local::now() + Duration::minutes(1),
+        );
+        {
+            // as if both were stored after the file was opened for the statistics,
+            // the first one made it into the file before it was read, the second one didn't
+            let mut lock = state.file_buf_list.lock().await;
+            let location_info = lock.get_mut(&location).unwrap();
+            location_info.pending_statistics = Some(vec![stored[2].clone(), newer.clone()]);
+        }
+
+        let statistics = state.get_statistics(&location).await.unwrap().unwrap();
+        let count = statistics
+            .days(None, None)
+            .iter()
+            .map(|day| day.count)
+            .sum::<usize>();
+        assert_eq!(count, 4);
+        assert_eq!(
+            statistics.records().highest_temperature.unwrap().value,
+            90.0
+        );
+
+        // from now on readings go straight into the statistics
+        let newest = Reading::new(
+            location.clone(),
+            95.0,
+            50.0,
+            Local::now() + Duration::minutes(2),
+        );
+        state
+            .file_buf_list
+            .lock()
+            .await
+            .get_mut(&location)
+            .unwrap()
+            .record_reading(newest);
+        let statistics = state.get_statistics(&location).await.unwrap().unwrap();
+        assert_eq!(
+            statistics.records().highest_temperature.unwrap().value,
+            95.0
+        );
+
+        assert!(state
+            .get_statistics(&"garage".into())
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
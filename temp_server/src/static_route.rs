@@ -0,0 +1,43 @@
+use actix_web::http::header::CACHE_CONTROL;
+use actix_web::http::StatusCode;
+use actix_web::{get, web, HttpResponseBuilder, Responder};
+
+/// Files served under /static, built into the binary so the server needs nothing next to it.
+/// (name, content type, contents)
+const STATIC_FILES: &[(&str, &str, &str)] = &[
+    (
+        "chart.js",
+        "text/javascript; charset=utf-8",
+        include_str!("../static/chart.js"),
+    ),
+    (
+        "live.js",
+        "text/javascript; charset=utf-8",
+        include_str!("../static/live.js"),
+    ),
+    (
+        "style.css",
+        "text/css; charset=utf-8",
+        include_str!("../static/style.css"),
+    ),
+];
+
+#[utoipa::path(
+    tag = "pages",
+    params(("file" = String, Path, description = "File name")),
+    responses(
+        (status = 200, description = "A static asset used by the pages"),
+        (status = 404, description = "Unknown file"),
+    )
+)]
+#[get("/static/{file}")]
+pub async fn static_file_handler(file: web::Path<String>) -> impl Responder {
+    match STATIC_FILES.iter().find(|(name, _, _)| *name == file.as_str()) {
+        Some((_, content_type, contents)) => HttpResponseBuilder::new(StatusCode::OK)
+            .content_type(*content_type)
+            .insert_header((CACHE_CONTROL, "public, max-age=3600"))
+            .body(*contents),
+        None => HttpResponseBuilder::new(StatusCode::NOT_FOUND)
+            .body(format!("Unknown file: {}", file.as_str())),
+    }
+}
@@ -0,0 +1,307 @@
+use crate::aggregation::Aggregate;
+use crate::metric::Metric;
+use crate::reading::Reading;
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, Timelike};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use utoipa::ToSchema;
+
+/// A single extreme value and when it happened.
+#[derive(Debug, Clone, Copy, Serialize, ToSchema)]
+pub struct Extreme {
+    pub value: f32,
+    pub time: DateTime<Local>,
+}
+
+impl Extreme {
+    fn new(value: f32, time: DateTime<Local>) -> Self {
+        Self { value, time }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct DailySummary {
+    pub date: NaiveDate,
+    pub count: usize,
+    pub temperature_high: Extreme,
+    pub temperature_low: Extreme,
+    pub temperature_mean: f32,
+    pub humidity_high: Extreme,
+    pub humidity_low: Extreme,
+    pub humidity_mean: f32,
+    #[serde(skip)]
+    temperature_sum: f64,
+    #[serde(skip)]
+    humidity_sum: f64,
+}
+
+impl DailySummary {
+    /// The day's lowest, highest or mean temperature or humidity, None for anything else.
+    pub fn value(&self, metric: Metric, aggregate: Aggregate) -> Option<f32> {
+        match (metric, aggregate) {
+            (Metric::Temperature, Aggregate::Min) => Some(self.temperature_low.value),
+            (Metric::Temperature, Aggregate::Max) => Some(self.temperature_high.value),
+            (Metric::Temperature, Aggregate::Mean) => Some(self.temperature_mean),
+            (Metric::Humidity, Aggregate::Min) => Some(self.humidity_low.value),
+            (Metric::Humidity, Aggregate::Max) => Some(self.humidity_high.value),
+            (Metric::Humidity, Aggregate::Mean) => Some(self.humidity_mean),
+            _ => None,
+        }
+    }
+
+    fn new(reading: &Reading) -> Self {
+        let time = reading.reading_time();
+        Self {
+            date: time.date_naive(),
+            count: 1,
+            temperature_high: Extreme::new(reading.temperature(), time),
+            temperature_low: Extreme::new(reading.temperature(), time),
+            temperature_mean: reading.temperature(),
+            humidity_high: Extreme::new(reading.humidity(), time),
+            humidity_low: Extreme::new(reading.humidity(), time),
+            humidity_mean: reading.humidity(),
+            temperature_sum: reading.temperature() as f64,
+            humidity_sum: reading.humidity() as f64,
+        }
+    }
+
+    fn record(&mut self, reading: &Reading) {
+        let time = reading.reading_time();
+        let (temperature, humidity) = (reading.temperature(), reading.humidity());
+
+        if temperature > self.temperature_high.value {
+            self.temperature_high = Extreme::new(temperature, time);
+        }
+        if temperature < self.temperature_low.value {
+            self.temperature_low = Extreme::new(temperature, time);
+        }
+        if humidity > self.humidity_high.value {
+            self.humidity_high = Extreme::new(humidity, time);
+        }
+        if humidity < self.humidity_low.value {
+            self.humidity_low = Extreme::new(humidity, time);
+        }
+
+        self.count += 1;
+        self.temperature_sum += temperature as f64;
+        self.humidity_sum += humidity as f64;
+        self.temperature_mean = (self.temperature_sum / self.count as f64) as f32;
+        self.humidity_mean = (self.humidity_sum / self.count as f64) as f32;
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, ToSchema)]
+pub struct HourRecord {
+    pub hour_start: NaiveDateTime,
+    pub mean: f32,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Records {
+    pub highest_temperature: Option<Extreme>,
+    pub lowest_temperature: Option<Extreme>,
+    pub highest_humidity: Option<Extreme>,
+    pub lowest_humidity: Option<Extreme>,
+    /// The day with the highest mean temperature
+    pub hottest_day: Option<DailySummary>,
+    /// The day with the lowest mean temperature
+    pub coldest_day: Option<DailySummary>,
+    /// The hour with the highest mean humidity
+    pub most_humid_hour: Option<HourRecord>,
+}
+
+/// Daily summaries and all-time records for one location, kept up to date one reading at a time.
+#[derive(Debug, Default, Clone)]
+pub struct LocationStatistics {
+    days: BTreeMap<NaiveDate, DailySummary>,
+    // (humidity sum, reading count) for each local hour
+    hourly_humidity: BTreeMap<NaiveDateTime, (f64, usize)>,
+}
+
+impl LocationStatistics {
+    pub fn from_readings(readings: &[Reading]) -> Self {
+        let mut statistics = Self::default();
+        readings
+            .iter()
+            .for_each(|reading| statistics.record(reading));
+        statistics
+    }
+
+    pub fn record(&mut self, reading: &Reading) {
+        let time = reading.reading_time();
+
+        self.days
+            .entry(time.date_naive())
+            .and_modify(|day| day.record(reading))
+            .or_insert_with(|| DailySummary::new(reading));
+
+        if let Some(hour_start) = time
+            .naive_local()
+            .with_minute(0)
+            .and_then(|t| t.with_second(0))
+            .and_then(|t| t.with_nanosecond(0))
+        {
+            let hour = self.hourly_humidity.entry(hour_start).or_default();
+            hour.0 += reading.humidity() as f64;
+            hour.1 += 1;
+        }
+    }
+
+    /// Daily summaries between `from` and `to` inclusive, oldest first. None when `from` is after
+    /// `to`.
+    pub fn days(&self, from: Option<NaiveDate>, to: Option<NaiveDate>) -> Vec<&DailySummary> {
+        let (from, to) = (from.unwrap_or(NaiveDate::MIN), to.unwrap_or(NaiveDate::MAX));
+        if from > to {
+            return vec![];
+        }
+        self.days
+            .range(from..=to)
+            .map(|(_, day)| day)
+            .collect()
+    }
+
+    pub fn records(&self) -> Records {
+        let days = || self.days.values();
+
+        Records {
+            highest_temperature: days()
+                .map(|day| day.temperature_high)
+                .max_by(|a, b| a.value.total_cmp(&b.value)),
+            lowest_temperature: days()
+                .map(|day| day.temperature_low)
+                .min_by(|a, b| a.value.total_cmp(&b.value)),
+            highest_humidity: days()
+                .map(|day| day.humidity_high)
+                .max_by(|a, b| a.value.total_cmp(&b.value)),
+            lowest_humidity: days()
+                .map(|day| day.humidity_low)
+                .min_by(|a, b| a.value.total_cmp(&b.value)),
+            hottest_day: days()
+                .max_by(|a, b| a.temperature_mean.total_cmp(&b.temperature_mean))
+                .cloned(),
+            coldest_day: days()
+                .min_by(|a, b| a.temperature_mean.total_cmp(&b.temperature_mean))
+                .cloned(),
+            most_humid_hour: self
+                .hourly_humidity
+                .iter()
+                .map(|(hour_start, (sum, count))| HourRecord {
+                    hour_start: *hour_start,
+                    mean: (sum / *count as f64) as f32,
+                    count: *count,
+                })
+                .max_by(|a, b| a.mean.total_cmp(&b.mean)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn reading(day: u32, hour: u32, temperature: f32, humidity: f32) -> Reading {
+        Reading::new(
+            "kitchen".into(),
+            temperature,
+            humidity,
+            Local.with_ymd_and_hms(2025, 3, day, hour, 0, 0).unwrap(),
+        )
+    }
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 3, day).unwrap()
+    }
+
+    fn statistics() -> LocationStatistics {
+        LocationStatistics::from_readings(&[
+            reading(1, 6, 60.0, 40.0),
+            reading(1, 12, 70.0, 50.0),
+            reading(1, 18, 65.0, 45.0),
+            reading(2, 6, 50.0, 80.0),
+            reading(2, 6, 52.0, 70.0),
+            reading(2, 12, 58.0, 30.0),
+            reading(3, 12, 75.0, 60.0),
+        ])
+    }
+
+    #[test]
+    fn days_keep_their_extremes_and_means() {
+        let statistics = statistics();
+        let days = statistics.days(None, None);
+        assert_eq!(days.len(), 3);
+
+        let first = days[0];
+        assert_eq!(first.date, date(1));
+        assert_eq!(first.count, 3);
+        assert_eq!(first.temperature_high.value, 70.0);
+        assert_eq!(
+            first.temperature_high.time,
+            reading(1, 12, 0.0, 0.0).reading_time()
+        );
+        assert_eq!(first.temperature_low.value, 60.0);
+        assert_eq!(
+            first.temperature_low.time,
+            reading(1, 6, 0.0, 0.0).reading_time()
+        );
+        assert_eq!(first.temperature_mean, 65.0);
+        assert_eq!(first.humidity_high.value, 50.0);
+        assert_eq!(first.humidity_low.value, 40.0);
+        assert_eq!(first.humidity_mean, 45.0);
+
+        assert_eq!(first.value(Metric::Temperature, Aggregate::Max), Some(70.0));
+        assert_eq!(first.value(Metric::Humidity, Aggregate::Mean), Some(45.0));
+        assert_eq!(first.value(Metric::Temperature, Aggregate::Median), None);
+    }
+
+    #[test]
+    fn days_are_limited_to_the_given_dates() {
+        let statistics = statistics();
+        let dates = |from, to| {
+            statistics
+                .days(from, to)
+                .into_iter()
+                .map(|day| day.date)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(dates(Some(date(2)), None), vec![date(2), date(3)]);
+        assert_eq!(dates(None, Some(date(2))), vec![date(1), date(2)]);
+        assert_eq!(dates(Some(date(2)), Some(date(2))), vec![date(2)]);
+        assert!(dates(Some(date(4)), None).is_empty());
+        assert!(dates(Some(date(3)), Some(date(1))).is_empty());
+    }
+
+    #[test]
+    fn records_are_the_extremes_over_every_day() {
+        let records = statistics().records();
+
+        assert_eq!(records.highest_temperature.unwrap().value, 75.0);
+        assert_eq!(records.lowest_temperature.unwrap().value, 50.0);
+        assert_eq!(records.highest_humidity.unwrap().value, 80.0);
+        assert_eq!(records.lowest_humidity.unwrap().value, 30.0);
+        assert_eq!(records.hottest_day.unwrap().date, date(3));
+        assert_eq!(records.coldest_day.unwrap().date, date(2));
+
+        // the two readings at 6 on the 2nd average out to 75
+        let most_humid_hour = records.most_humid_hour.unwrap();
+        assert_eq!(
+            most_humid_hour.hour_start,
+            date(2).and_hms_opt(6, 0, 0).unwrap()
+        );
+        assert_eq!(most_humid_hour.mean, 75.0);
+        assert_eq!(most_humid_hour.count, 2);
+    }
+
+    #[test]
+    fn no_readings_have_no_records() {
+        let statistics = LocationStatistics::default();
+        let records = statistics.records();
+
+        assert!(statistics.days(None, None).is_empty());
+        assert!(records.highest_temperature.is_none());
+        assert!(records.hottest_day.is_none());
+        assert!(records.most_humid_hour.is_none());
+    }
+}
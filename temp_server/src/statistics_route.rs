@@ -0,0 +1,122 @@
+use crate::location::Location;
+use crate::state::TemperatureServerState;
+use crate::statistics::{DailySummary, Records};
+use actix_web::http::StatusCode;
+use actix_web::{get, web, HttpResponseBuilder, Responder};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use utoipa::{IntoParams, ToSchema};
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct StatisticsQuery {
+    /// First day to include
+    from: Option<NaiveDate>,
+    /// Last day to include
+    to: Option<NaiveDate>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct StatisticsResponse {
+    location: Location,
+    records: Records,
+    days: Vec<DailySummary>,
+}
+
+#[utoipa::path(
+    tag = "statistics",
+    params(
+        ("location" = String, Path, description = "Location name"),
+        StatisticsQuery,
+    ),
+    responses(
+        (status = 200, description = "All-time records and daily summaries", body = StatisticsResponse),
+        (status = 400, description = "from is after to"),
+        (status = 404, description = "Unknown location"),
+    )
+)]
+#[get("/api/v1/locations/{location}/statistics")]
+pub async fn statistics_handler(
+    location: web::Path<String>,
+    query: web::Query<StatisticsQuery>,
+    state: web::Data<TemperatureServerState>,
+) -> impl Responder {
+    let location: Location = location.as_str().into();
+    if let (Some(from), Some(to)) = (query.from, query.to) {
+        if from > to {
+            return HttpResponseBuilder::new(StatusCode::BAD_REQUEST)
+                .body("from must not be after to");
+        }
+    }
+
+    match state.get_statistics(&location).await {
+        Ok(None) => HttpResponseBuilder::new(StatusCode::NOT_FOUND)
+            .body(format!("Unknown location: {}", location)),
+        Ok(Some(statistics)) => HttpResponseBuilder::new(StatusCode::OK).json(StatisticsResponse {
+            records: statistics.records(),
+            days: statistics
+                .days(query.from, query.to)
+                .into_iter()
+                .cloned()
+                .collect(),
+            location,
+        }),
+        Err(err) => {
+            error!("Error reading statistics for {}: {}", location, err);
+            HttpResponseBuilder::new(StatusCode::INTERNAL_SERVER_ERROR).finish()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{readings, TestFolder};
+    use actix_web::test::{call_and_read_body_json, call_service, init_service, TestRequest};
+    use actix_web::App;
+    use chrono::{Duration, Local};
+    use serde_json::Value;
+
+    #[actix_web::test]
+    async fn days_are_limited_to_the_requested_dates() {
+        let folder = TestFolder::new("statistics-route");
+        let end = Local::now().date_naive().and_hms_opt(12, 0, 0).unwrap();
+        let end = end.and_local_timezone(Local).earliest().unwrap();
+        let mut all = readings("closet", end - Duration::days(1), 5);
+        all.extend(readings("closet", end, 5));
+        folder.write_location("closet", &all);
+        let app = init_service(
+            App::new()
+                .app_data(folder.state())
+                .service(statistics_handler),
+        )
+        .await;
+
+        let today = end.date_naive();
+        let uri = |query: String| format!("/api/v1/locations/closet/statistics{}", query);
+        let body: Value = call_and_read_body_json(
+            &app,
+            TestRequest::get()
+                .uri(&uri(format!("?from={}", today)))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(body["days"].as_array().unwrap().len(), 1);
+        assert_eq!(body["days"][0]["date"], today.to_string());
+        assert_eq!(body["records"]["highest_temperature"]["value"], 64.0);
+
+        let reversed = format!("?from={}&to={}", today, today - Duration::days(1));
+        let resp = call_service(&app, TestRequest::get().uri(&uri(reversed)).to_request()).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let resp = call_service(
+            &app,
+            TestRequest::get()
+                .uri("/api/v1/locations/attic/statistics")
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+}
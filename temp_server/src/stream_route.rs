@@ -0,0 +1,147 @@
+use crate::location::Location;
+use crate::reading::Reading;
+use crate::state::TemperatureServerState;
+use actix_web::http::header::CACHE_CONTROL;
+use actix_web::http::StatusCode;
+use actix_web::web::Bytes;
+use actix_web::{get, web, HttpResponseBuilder, Responder};
+use futures_util::stream;
+use serde::Deserialize;
+use std::convert::Infallible;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::Receiver;
+use tracing::{error, warn};
+use utoipa::IntoParams;
+
+// A comment is sent when there have been no readings for this long, so proxies keep the
+// connection open and we notice clients that went away
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct StreamQuery {
+    /// Only send readings from this location
+    location: Option<String>,
+}
+
+/// Waits for the next reading to send, as a server-sent event, or a keep alive comment.
+/// None once the server is shutting down.
+async fn next_event(
+    receiver: &mut Receiver<Reading>,
+    location: Option<&Location>,
+) -> Option<Bytes> {
+    loop {
+        let reading = match tokio::time::timeout(KEEP_ALIVE, receiver.recv()).await {
+            Err(_) => return Some(Bytes::from_static(b": keep-alive\n\n")),
+            Ok(Err(RecvError::Closed)) => return None,
+            Ok(Err(RecvError::Lagged(skipped))) => {
+                warn!(
+                    "Reading stream client fell behind, skipped {} readings",
+                    skipped
+                );
+                continue;
+            }
+            Ok(Ok(reading)) => reading,
+        };
+
+        if location.is_some_and(|location| *location != reading.location()) {
+            continue;
+        }
+
+        match serde_json::to_string(&reading) {
+            Ok(data) => return Some(Bytes::from(format!("event: reading\ndata: {}\n\n", data))),
+            Err(err) => error!("Error serializing reading for the stream: {}", err),
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "readings",
+    params(StreamQuery),
+    responses(
+        (status = 200, description = "Server-sent events, a `reading` event with a Reading as its data for every reading stored from now on", content_type = "text/event-stream", body = Reading),
+    )
+)]
+#[get("/api/v1/stream")]
+pub async fn reading_stream_handler(
+    query: web::Query<StreamQuery>,
+    state: web::Data<TemperatureServerState>,
+) -> impl Responder {
+    let location: Option<Location> = query.into_inner().location.map(Location::from);
+    let receiver = state.readings.subscribe();
+
+    let events = stream::unfold(
+        (receiver, location),
+        |(mut receiver, location)| async move {
+            next_event(&mut receiver, location.as_ref())
+                .await
+                .map(|event| (Ok::<_, Infallible>(event), (receiver, location)))
+        },
+    );
+
+    HttpResponseBuilder::new(StatusCode::OK)
+        .content_type("text/event-stream")
+        .insert_header((CACHE_CONTROL, "no-cache"))
+        .streaming(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reading_route::reading_handler;
+    use crate::test_support::{readings, TestFolder};
+    use actix_web::body::MessageBody;
+    use actix_web::http::header::CONTENT_TYPE;
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::App;
+    use chrono::Local;
+
+    #[actix_web::test]
+    async fn stored_readings_are_streamed_to_clients() {
+        let folder = TestFolder::new("stream");
+        folder.write_location("kitchen", &readings("kitchen", Local::now(), 1));
+        folder.write_location("garage", &readings("garage", Local::now(), 1));
+        let app = init_service(
+            App::new()
+                .app_data(folder.state())
+                .service(reading_stream_handler)
+                .service(reading_handler),
+        )
+        .await;
+
+        let resp = call_service(
+            &app,
+            TestRequest::get()
+                .uri("/api/v1/stream?location=kitchen")
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(CONTENT_TYPE).unwrap(),
+            "text/event-stream"
+        );
+        let mut body = Box::pin(resp.into_body());
+
+        for uri in ["/reading/garage/30.0/40.0", "/reading/kitchen/20.0/55.0"] {
+            let resp = call_service(&app, TestRequest::get().uri(uri).to_request()).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+        }
+
+        // only the kitchen reading is sent, in fahrenheit like it is stored
+        let event = std::future::poll_fn(|cx| body.as_mut().poll_next(cx))
+            .await
+            .unwrap()
+            .unwrap();
+        let event = std::str::from_utf8(&event).unwrap();
+        let data = event
+            .strip_prefix("event: reading\ndata: ")
+            .and_then(|event| event.strip_suffix("\n\n"))
+            .unwrap();
+        let reading: serde_json::Value = serde_json::from_str(data).unwrap();
+        assert_eq!(reading["location"], "kitchen");
+        assert_eq!(reading["temperature"], 68.0);
+        assert_eq!(reading["humidity"], 55.0);
+    }
+}
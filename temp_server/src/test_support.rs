@@ -0,0 +1,75 @@
+//! Helpers for tests that go through the route handlers with `actix_web::test`.
+
+use crate::location::Location;
+use crate::reading::Reading;
+use crate::state::TemperatureServerState;
+use actix_web::web;
+use chrono::{DateTime, Duration, Local, Timelike};
+use std::fs;
+use std::path::PathBuf;
+
+/// A log folder of its own for one test, removed again when the test is done.
+pub struct TestFolder {
+    path: PathBuf,
+}
+
+impl TestFolder {
+    /// `name` has to be unique among the tests, they run at the same time.
+    pub fn new(name: &str) -> Self {
+        let path =
+            std::env::temp_dir().join(format!("temp_server-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        Self { path }
+    }
+
+    /// Where the csv file for `location` goes in this folder.
+    pub fn location_path(&self, location: &str) -> PathBuf {
+        self.path.join(Location::from(location).path())
+    }
+
+    /// Writes a location csv file the way the reading route does.
+    pub fn write_location(&self, location: &str, readings: &[Reading]) {
+        let mut contents = "Date,Time,Temperature,Humidity\n".to_string();
+        for reading in readings {
+            contents.push_str(&reading.format_to_file());
+        }
+        fs::write(self.location_path(location), contents).unwrap();
+    }
+
+    /// The state the server would start with from this folder.
+    pub fn state(&self) -> web::Data<TemperatureServerState> {
+        web::Data::new(TemperatureServerState::load(&self.path))
+    }
+
+    /// Like [TestFolder::state], with the admin api turned on and expecting `token`.
+    pub fn admin_state(&self, token: &str) -> web::Data<TemperatureServerState> {
+        web::Data::new(TemperatureServerState {
+            admin_token: Some(token.to_string()),
+            ..TemperatureServerState::load(&self.path)
+        })
+    }
+}
+
+impl Drop for TestFolder {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// `count` readings a minute apart with the last at `end`, the temperature going up by a degree
+/// each time from 60 F and the humidity staying at 50 %.
+pub fn readings(location: &str, end: DateTime<Local>, count: usize) -> Vec<Reading> {
+    // the csv files only keep whole seconds
+    let end = end.with_nanosecond(0).unwrap_or(end);
+    (0..count)
+        .map(|idx| {
+            Reading::new(
+                location.into(),
+                60.0 + idx as f32,
+                50.0,
+                end - Duration::minutes((count - 1 - idx) as i64),
+            )
+        })
+        .collect()
+}
@@ -0,0 +1,100 @@
+use chrono::{DateTime, Duration, Local};
+
+// Longer durations are rejected, so adding one to a time can't run off the end of the calendar
+const MAX_DURATION: Duration = Duration::days(100 * 365);
+
+/// Parses a short duration such as "30s", "5m", "24h", "7d" or "2w".
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| format!("Duration is missing a unit: {}", s))?;
+    let (amount, unit) = s.split_at(split);
+    let amount: i64 = amount
+        .parse()
+        .map_err(|_| format!("Invalid duration: {}", s))?;
+
+    let duration = match unit {
+        "s" => Duration::try_seconds(amount),
+        "m" => Duration::try_minutes(amount),
+        "h" => Duration::try_hours(amount),
+        "d" => Duration::try_days(amount),
+        "w" => Duration::try_weeks(amount),
+        _ => return Err(format!("Unknown duration unit: {}", unit)),
+    }
+    .filter(|duration| *duration <= MAX_DURATION)
+    .ok_or_else(|| format!("Duration is too long: {}", s))?;
+
+    if duration <= Duration::zero() {
+        return Err(format!("Duration must be positive: {}", s));
+    }
+
+    Ok(duration)
+}
+
+/// Turns the `from`, `to` and `last` query parameters into a concrete range.
+/// `last` counts back from `to` (or now), and wins over `from` if both are given.
+/// With neither `from` nor `last` the range covers `default_last`.
+pub fn resolve_range(
+    from: Option<DateTime<Local>>,
+    to: Option<DateTime<Local>>,
+    last: Option<&str>,
+    default_last: Duration,
+) -> Result<(DateTime<Local>, DateTime<Local>), String> {
+    let to = to.unwrap_or_else(Local::now);
+    let from = match (last.map(parse_duration).transpose()?, from) {
+        (Some(last), _) => to.checked_sub_signed(last),
+        (None, Some(from)) => Some(from),
+        (None, None) => to.checked_sub_signed(default_last),
+    }
+    .ok_or_else(|| "The range starts too long ago".to_string())?;
+
+    if from >= to {
+        return Err("from must be before to".to_string());
+    }
+
+    Ok((from, to))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn durations_have_a_unit() {
+        assert_eq!(parse_duration("30s"), Ok(Duration::seconds(30)));
+        assert_eq!(parse_duration(" 5m "), Ok(Duration::minutes(5)));
+        assert_eq!(parse_duration("2w"), Ok(Duration::weeks(2)));
+
+        assert!(parse_duration("30").is_err());
+        assert!(parse_duration("30y").is_err());
+        assert!(parse_duration("5 m").is_err());
+        assert!(parse_duration("m").is_err());
+        assert!(parse_duration("0h").is_err());
+        assert!(parse_duration("-5m").is_err());
+    }
+
+    #[test]
+    fn huge_durations_are_errors() {
+        assert!(parse_duration("9223372036854775807s").is_err());
+        assert!(parse_duration("9223372036854775807w").is_err());
+        assert!(parse_duration("99999999999999999999d").is_err());
+        assert!(parse_duration("100000w").is_err());
+    }
+
+    #[test]
+    fn ranges_too_far_back_are_errors() {
+        let to = Local.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        assert_eq!(
+            resolve_range(None, Some(to), Some("1d"), Duration::days(1)),
+            Ok((to - Duration::days(1), to))
+        );
+        assert!(resolve_range(None, Some(to), Some("5200w"), Duration::days(1)).is_ok());
+
+        let start_of_time = DateTime::<Local>::MIN_UTC.with_timezone(&Local);
+        assert!(resolve_range(None, Some(start_of_time), Some("1d"), Duration::days(1)).is_err());
+        assert!(resolve_range(None, Some(start_of_time), None, Duration::days(1)).is_err());
+        assert!(resolve_range(Some(to), Some(to), None, Duration::days(1)).is_err());
+    }
+}
@@ -0,0 +1,352 @@
+use crate::notifier::{Notification, NotificationKind, Retries};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashSet};
+use std::time::Duration;
+use ureq::Agent;
+
+// Give up on a receiver that takes longer than this to answer
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A receiver that notifications are posted to as json, from the config file, such as
+///
+/// ```toml
+/// [[webhooks]]
+/// name = "slack"
+/// url = "https://hooks.slack.com/services/..."
+/// events = ["alert"]
+/// template = { text = "{message}" }
+/// ```
+///
+/// Without a template the notification itself is posted. Every `{field}` in the strings of a
+/// template is replaced with that field of the notification, see [`Notification`].
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookConfig {
+    /// Identifies the webhook in the delivery log, so the url (which is often a secret) is not logged
+    pub name: String,
+    pub url: String,
+    /// Which notifications are sent, every kind when empty
+    #[serde(default)]
+    pub events: Vec<NotificationKind>,
+    pub template: Option<Value>,
+    /// Extra request headers, such as an Authorization header
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// How many times a notification is sent before giving up, defaults to 5
+    pub max_attempts: Option<u32>,
+    /// Seconds before the first retry, doubling after every failed attempt, defaults to 5
+    pub retry_after_seconds: Option<u64>,
+}
+
+impl WebhookConfig {
+    pub fn wants(&self, notification: &Notification) -> bool {
+        self.events.is_empty() || self.events.contains(&notification.kind())
+    }
+
+    pub fn retries(&self) -> Retries {
+        Retries::new(self.max_attempts, self.retry_after_seconds)
+    }
+
+    /// The json body posted for a notification.
+    pub fn body(&self, notification: &Notification) -> Value {
+        let notification = serde_json::to_value(notification).unwrap_or_default();
+        match (&self.template, notification.as_object()) {
+            (Some(template), Some(fields)) => render(template, fields),
+            _ => notification,
+        }
+    }
+}
+
+/// Checks webhook names are unique and urls look like urls.
+pub fn validate_webhooks(webhooks: &[WebhookConfig]) -> Result<(), String> {
+    let mut names = HashSet::new();
+
+    for webhook in webhooks {
+        if webhook.name.trim().is_empty() {
+            return Err("Every webhook needs a name".to_string());
+        }
+        if !names.insert(webhook.name.as_str()) {
+            return Err(format!("Duplicate webhook name: {}", webhook.name));
+        }
+        if webhook.events.contains(&NotificationKind::Digest) {
+            return Err(format!(
+                "Webhook {} can't send the digest, it is only sent by email",
+                webhook.name
+            ));
+        }
+        if !webhook.url.starts_with("http://") && !webhook.url.starts_with("https://") {
+            return Err(format!(
+                "Webhook {} needs an http:// or https:// url",
+                webhook.name
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Replaces `{field}` in every string of the template with that field of the notification.
+fn render(template: &Value, fields: &Map<String, Value>) -> Value {
+    match template {
+        Value::String(text) => {
+            Value::String(fields.iter().fold(text.clone(), |text, (name, value)| {
+                let value = match value {
+                    Value::String(value) => value.clone(),
+                    Value::Null => String::new(),
+                    value => value.to_string(),
+                };
+                text.replace(&format!("{{{}}}", name), &value)
+            }))
+        }
+        Value::Array(values) => {
+            Value::Array(values.iter().map(|value| render(value, fields)).collect())
+        }
+        Value::Object(object) => Value::Object(
+            object
+                .iter()
+                .map(|(key, value)| (key.clone(), render(value, fields)))
+                .collect(),
+        ),
+        value => value.clone(),
+    }
+}
+
+pub fn agent() -> Agent {
+    Agent::config_builder()
+        .timeout_global(Some(REQUEST_TIMEOUT))
+        .build()
+        .into()
+}
+
+/// Posts a body to the webhook once, this blocks until the receiver answers.
+pub fn send(agent: &Agent, webhook: &WebhookConfig, body: &Value) -> Result<(), ureq::Error> {
+    let mut request = agent.post(&webhook.url);
+    for (name, value) in &webhook.headers {
+        request = request.header(name, value);
+    }
+    request.send_json(body).map(|_| ())
+}
+
+/// Whether sending again could work, a receiver that rejects the request will keep rejecting it.
+pub fn worth_retrying(err: &ureq::Error) -> bool {
+    match err {
+        ureq::Error::StatusCode(status) => *status == 429 || *status >= 500,
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alerts::{AlertEvent, AlertState};
+    use crate::metric::Metric;
+    use crate::notifier::{run_webhook, MAX_RETRY_DELAY};
+    use crate::test_support::TestFolder;
+    use chrono::Local;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread::{self, JoinHandle};
+
+    fn webhook(toml: &str) -> WebhookConfig {
+        toml::from_str(toml).unwrap()
+    }
+
+    fn alert() -> Notification {
+        Notification::Alert(AlertEvent {
+            rule: "humid".to_string(),
+            location: "closet".into(),
+            state: AlertState::Firing,
+            time: Local::now(),
+            metric: Metric::Humidity,
+            value: 65.0,
+            message: "humid: \"closet\" is 65.0 %".to_string(),
+        })
+    }
+
+    #[test]
+    fn templates_fill_in_notification_fields() {
+        let slack = webhook(
+            "name = 'slack'\nurl = 'http://localhost'\ntemplate = { text = '{message}', tags = ['{location}', '{value}'] }",
+        );
+        assert_eq!(
+            slack.body(&alert()),
+            serde_json::json!({"text": "humid: \"closet\" is 65.0 %", "tags": ["closet", "65.0"]})
+        );
+
+        let plain = webhook("name = 'plain'\nurl = 'http://localhost'\nevents = ['health']");
+        assert_eq!(plain.body(&alert())["kind"], "alert");
+        assert!(!plain.wants(&alert()));
+        assert_eq!(plain.retries().delay(3), Duration::from_secs(20));
+    }
+
+    /// Answers a request with each of `statuses` in turn, returning the port it listens on and
+    /// the authorization header and body of every request.
+    fn receiver(statuses: Vec<&'static str>) -> (u16, JoinHandle<Vec<(String, String)>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let receiver = thread::spawn(move || {
+            let mut bodies = vec![];
+            for status in statuses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut content_length = 0;
+                let mut authorization = String::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim_end().to_lowercase();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if let Some(length) = line.strip_prefix("content-length: ") {
+                        content_length = length.parse().unwrap();
+                    }
+                    if let Some(value) = line.strip_prefix("authorization: ") {
+                        authorization = value.to_string();
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                bodies.push((authorization, String::from_utf8(body).unwrap()));
+                write!(
+                    reader.get_mut(),
+                    "HTTP/1.1 {}\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                    status
+                )
+                .unwrap();
+            }
+            bodies
+        });
+        (port, receiver)
+    }
+
+    #[test]
+    fn posts_to_a_local_receiver() {
+        let (port, receiver) = receiver(vec!["503 Service Unavailable", "200 OK"]);
+
+        let ntfy = webhook(&format!(
+            "name = 'ntfy'\nurl = 'http://127.0.0.1:{}/alerts'\nheaders = {{ Authorization = 'Bearer abc' }}\ntemplate = {{ message = '{{message}}' }}",
+            port
+        ));
+        let agent = agent();
+        let body = ntfy.body(&alert());
+
+        let err = send(&agent, &ntfy, &body).unwrap_err();
+        assert!(worth_retrying(&err));
+        send(&agent, &ntfy, &body).unwrap();
+
+        let bodies = receiver.join().unwrap();
+        assert_eq!(bodies[1].0, "bearer abc");
+        assert_eq!(
+            serde_json::from_str::<Value>(&bodies[1].1).unwrap(),
+            serde_json::json!({"message": "humid: \"closet\" is 65.0 %"})
+        );
+    }
+
+    #[actix_web::test]
+    async fn retries_until_delivered_or_given_up() {
+        let retried = |statuses: &'static [&'static str], max_attempts: u32| async move {
+            let (port, receiver) = receiver(statuses.to_vec());
+            let hook = webhook(&format!(
+                "name = 'hook'\nurl = 'http://127.0.0.1:{}/'\nmax_attempts = {}\nretry_after_seconds = 0",
+                port, max_attempts
+            ));
+            let (agent, body) = (agent(), hook.body(&alert()));
+            let delivery = hook
+                .retries()
+                .send(
+                    &hook.name,
+                    NotificationKind::Alert,
+                    "humid",
+                    {
+                        let hook = hook.clone();
+                        move || send(&agent, &hook, &body)
+                    },
+                    worth_retrying,
+                )
+                .await;
+            assert_eq!(receiver.join().unwrap().len(), statuses.len());
+            delivery
+        };
+
+        let delivery = retried(&["503 Service Unavailable", "502 Bad Gateway", "200 OK"], 5).await;
+        assert!(delivery.delivered);
+        assert_eq!(delivery.attempts, 3);
+        assert_eq!(delivery.error, None);
+
+        let delivery = retried(&["503 Service Unavailable", "503 Service Unavailable"], 2).await;
+        assert!(!delivery.delivered);
+        assert_eq!(delivery.attempts, 2);
+        assert!(delivery.error.is_some());
+
+        // sending it again won't fix a bad request
+        let delivery = retried(&["400 Bad Request"], 5).await;
+        assert!(!delivery.delivered);
+        assert_eq!(delivery.attempts, 1);
+
+        let retries = Retries::new(Some(5), Some(5));
+        assert_eq!(retries.delay(1), Duration::from_secs(5));
+        assert_eq!(retries.delay(12), MAX_RETRY_DELAY);
+        assert_eq!(retries.delay(u32::MAX), MAX_RETRY_DELAY);
+    }
+
+    #[actix_web::test]
+    async fn notifications_wait_for_a_failing_receiver() {
+        // more than the notification channel holds
+        const COUNT: usize = 300;
+        let mut statuses = vec!["503 Service Unavailable"];
+        statuses.extend(["200 OK"; COUNT]);
+        let (port, receiver) = receiver(statuses);
+
+        let folder = TestFolder::new("webhook-queue");
+        let state = folder.state();
+        let hook = webhook(&format!(
+            "name = 'hook'\nurl = 'http://127.0.0.1:{}/'\nretry_after_seconds = 1\ntemplate = {{ text = '{{message}}' }}",
+            port
+        ));
+        actix_web::rt::spawn(run_webhook(hook, state.clone()));
+        while state.notifications.receiver_count() == 0 {
+            actix_web::rt::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        // sent while the webhook waits to retry the first one
+        for idx in 0..COUNT {
+            let Notification::Alert(event) = alert() else {
+                unreachable!()
+            };
+            let message = format!("alert {}", idx);
+            state
+                .notifications
+                .send(Notification::Alert(AlertEvent { message, ..event }))
+                .unwrap();
+            actix_web::rt::task::yield_now().await;
+        }
+
+        // the last delivery is recorded after the receiver has answered it
+        let mut deliveries = vec![];
+        for _ in 0..500 {
+            deliveries = state.deliveries.lock().await.recent(COUNT * 2);
+            if deliveries.len() == COUNT {
+                break;
+            }
+            actix_web::rt::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(deliveries.len(), COUNT);
+        assert!(deliveries.iter().all(|delivery| delivery.delivered));
+        assert_eq!(deliveries.last().unwrap().attempts, 2);
+
+        let texts = receiver
+            .join()
+            .unwrap()
+            .iter()
+            .map(|(_, body)| serde_json::from_str::<Value>(body).unwrap()["text"].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(texts.len(), COUNT + 1);
+        assert_eq!(texts[0], texts[1]);
+        for (idx, text) in texts[1..].iter().enumerate() {
+            assert_eq!(*text, format!("alert {}", idx));
+        }
+    }
+}
@@ -0,0 +1,476 @@
+// Interactive chart of one location, drawn on a canvas from the aggregate api.
+// The location is the second segment of the page path, /location/{location}/chart.
+// New readings from the /api/v1/stream server-sent events keep a view of the present up to date.
+"use strict";
+
+(function () {
+    // Same colours and axes as the server side plots
+    const METRICS = [
+        { key: "temperature", name: "Temperature", unit: "F", color: "#ff0000", secondary: false, shown: true },
+        { key: "humidity", name: "Humidity", unit: "%", color: "#00ff00", secondary: true, shown: true },
+        { key: "dew_point", name: "Dew Point", unit: "F", color: "#0000ff", secondary: false, shown: false },
+        { key: "absolute_humidity", name: "Absolute Humidity", unit: "g/m³", color: "#00ffff", secondary: true, shown: false },
+        { key: "heat_index", name: "Heat Index", unit: "F", color: "#ff8c00", secondary: false, shown: false },
+        { key: "humidex", name: "Humidex", unit: "", color: "#ff00ff", secondary: false, shown: false },
+    ];
+
+    const MINUTE = 60 * 1000;
+    const HOUR = 60 * MINUTE;
+    const DAY = 24 * HOUR;
+
+    const RANGES = [["24h", DAY], ["7d", 7 * DAY], ["30d", 30 * DAY], ["365d", 365 * DAY]];
+
+    // Roughly how many buckets are fetched for the visible range, about one per pixel
+    const TARGET_POINTS = 1000;
+    const MIN_BUCKET_SECONDS = 60;
+    const MIN_SPAN = 10 * MINUTE;
+    const MAX_SPAN = 5 * 365 * DAY;
+
+    // Candidate spacings of the time axis ticks
+    const TICK_STEPS = [MINUTE, 5 * MINUTE, 15 * MINUTE, 30 * MINUTE, HOUR, 3 * HOUR, 6 * HOUR, 12 * HOUR, DAY, 2 * DAY, 7 * DAY, 14 * DAY, 30 * DAY, 91 * DAY, 365 * DAY];
+
+    const MARGIN = { top: 20, right: 70, bottom: 40, left: 70 };
+
+    const location = decodeURIComponent(window.location.pathname.split("/")[2] || "");
+    const canvas = document.getElementById("chart");
+    const tooltip = document.getElementById("tooltip");
+    const context = canvas.getContext("2d");
+
+    const state = {
+        // the range picked with the range buttons, double click returns here
+        rangeFrom: 0,
+        rangeTo: 0,
+        // the visible part of the range
+        viewFrom: 0,
+        viewTo: 0,
+        // fetched buckets, and the range and bucket width they were fetched for
+        rows: [],
+        fetchedFrom: 0,
+        fetchedTo: 0,
+        bucketMillis: 0,
+        request: 0,
+        drag: null,
+        hoverX: null,
+    };
+
+    function bucketSecondsFor(span) {
+        return Math.max(MIN_BUCKET_SECONDS, Math.ceil(span / 1000 / TARGET_POINTS));
+    }
+
+    function fetchRows() {
+        const span = state.viewTo - state.viewFrom;
+        // fetch a view either side as well so panning has something to show straight away
+        const from = state.viewFrom - span;
+        const to = state.viewTo + span;
+        const bucketSeconds = bucketSecondsFor(span);
+        const request = ++state.request;
+
+        const query = new URLSearchParams({
+            from: new Date(from).toISOString(),
+            to: new Date(to).toISOString(),
+            bucket: bucketSeconds + "s",
+            aggregates: "mean",
+            metrics: METRICS.map((metric) => metric.key).join(","),
+        });
+        const url = "/api/v1/locations/" + encodeURIComponent(location) + "/aggregate?" + query;
+
+        fetch(url)
+            .then((response) => {
+                if (!response.ok) {
+                    throw new Error(response.status + " " + response.statusText);
+                }
+                return response.json();
+            })
+            .then((body) => {
+                // a newer request was sent while this one was running
+                if (request !== state.request) {
+                    return;
+                }
+                state.rows = body.rows.map((row) => ({
+                    time: Date.parse(row.bucket_start) + body.bucket_seconds * 500,
+                    values: row.values,
+                }));
+                state.fetchedFrom = from;
+                state.fetchedTo = to;
+                state.bucketMillis = body.bucket_seconds * 1000;
+                draw();
+            })
+            .catch((err) => {
+                document.getElementById("title").textContent = location + " (" + err.message + ")";
+            });
+    }
+
+    let fetchTimer = null;
+
+    // Fetches again once the view stops moving, if it has left the fetched data or is zoomed in
+    // far enough to show more detail
+    function scheduleFetch() {
+        clearTimeout(fetchTimer);
+        fetchTimer = setTimeout(() => {
+            const span = state.viewTo - state.viewFrom;
+            const outside = state.viewFrom < state.fetchedFrom || state.viewTo > state.fetchedTo;
+            const tooCoarse = bucketSecondsFor(span) * 1000 * 2 <= state.bucketMillis;
+            const tooFine = bucketSecondsFor(span) * 1000 >= state.bucketMillis * 2;
+            if (outside || tooCoarse || tooFine) {
+                fetchRows();
+            }
+        }, 300);
+    }
+
+    function setRange(span) {
+        state.rangeTo = Date.now();
+        state.rangeFrom = state.rangeTo - span;
+        setView(state.rangeFrom, state.rangeTo);
+        fetchRows();
+    }
+
+    function setView(from, to) {
+        let span = Math.min(MAX_SPAN, Math.max(MIN_SPAN, to - from));
+        const middle = (from + to) / 2;
+        state.viewFrom = middle - span / 2;
+        state.viewTo = middle + span / 2;
+        draw();
+    }
+
+    function plotArea() {
+        return {
+            left: MARGIN.left,
+            top: MARGIN.top,
+            width: canvas.clientWidth - MARGIN.left - MARGIN.right,
+            height: canvas.clientHeight - MARGIN.top - MARGIN.bottom,
+        };
+    }
+
+    function xFor(time, area) {
+        return area.left + ((time - state.viewFrom) / (state.viewTo - state.viewFrom)) * area.width;
+    }
+
+    function timeFor(x, area) {
+        return state.viewFrom + ((x - area.left) / area.width) * (state.viewTo - state.viewFrom);
+    }
+
+    // Fits every visible value with a little room above and below, like the server plots
+    function axisRange(values) {
+        if (values.length === 0) {
+            return [0, 100];
+        }
+        const lowest = Math.min(...values);
+        const highest = Math.max(...values);
+        const padding = Math.max((highest - lowest) * 0.1, 1);
+        return [lowest - padding, highest + padding];
+    }
+
+    function niceStep(span, ticks) {
+        const rough = span / ticks;
+        const magnitude = Math.pow(10, Math.floor(Math.log10(rough)));
+        const normalised = rough / magnitude;
+        const nice = normalised < 1.5 ? 1 : normalised < 3 ? 2 : normalised < 7 ? 5 : 10;
+        return nice * magnitude;
+    }
+
+    function pad(number) {
+        return String(number).padStart(2, "0");
+    }
+
+    function formatTime(time, span) {
+        const date = new Date(time);
+        const hours = date.getHours() % 12 === 0 ? 12 : date.getHours() % 12;
+        const clock = hours + ":" + pad(date.getMinutes()) + (date.getHours() < 12 ? " AM" : " PM");
+        const day = pad(date.getMonth() + 1) + "/" + pad(date.getDate());
+        if (span <= DAY) {
+            return clock;
+        } else if (span <= 7 * DAY) {
+            return day + " " + clock;
+        } else if (span <= 365 * DAY) {
+            return day;
+        }
+        return pad(date.getMonth() + 1) + "/" + date.getFullYear();
+    }
+
+    function axisDescription(metrics) {
+        return metrics.map((metric) => (metric.unit ? metric.name + " (" + metric.unit + ")" : metric.name)).join(" / ");
+    }
+
+    function draw() {
+        const ratio = window.devicePixelRatio || 1;
+        canvas.width = canvas.clientWidth * ratio;
+        canvas.height = canvas.clientHeight * ratio;
+        context.setTransform(ratio, 0, 0, ratio, 0, 0);
+        context.clearRect(0, 0, canvas.clientWidth, canvas.clientHeight);
+
+        const area = plotArea();
+        const span = state.viewTo - state.viewFrom;
+        const shown = METRICS.filter((metric) => metric.shown);
+
+        // only split the metrics over two axes when there is something to put on each of them
+        const dualAxes = shown.some((metric) => metric.secondary) && shown.some((metric) => !metric.secondary);
+        const isSecondary = (metric) => dualAxes && metric.secondary;
+
+        // one bucket either side of the view so lines run off the edges
+        const visible = state.rows.filter(
+            (row) => row.time >= state.viewFrom - state.bucketMillis && row.time <= state.viewTo + state.bucketMillis
+        );
+
+        const valuesFor = (secondary) =>
+            shown
+                .filter((metric) => isSecondary(metric) === secondary)
+                .flatMap((metric) => visible.map((row) => row.values[metric.key] && row.values[metric.key].mean))
+                .filter((value) => value !== undefined);
+        const axes = {
+            primary: { range: axisRange(valuesFor(false)), metrics: shown.filter((metric) => !isSecondary(metric)) },
+            secondary: { range: axisRange(valuesFor(true)), metrics: shown.filter(isSecondary) },
+        };
+        const yFor = (value, range) => area.top + area.height - ((value - range[0]) / (range[1] - range[0])) * area.height;
+
+        context.font = "12px sans-serif";
+        context.lineWidth = 1;
+
+        // horizontal grid and the left axis labels
+        const primaryStep = niceStep(axes.primary.range[1] - axes.primary.range[0], 8);
+        context.textAlign = "right";
+        context.textBaseline = "middle";
+        for (let value = Math.ceil(axes.primary.range[0] / primaryStep) * primaryStep; value <= axes.primary.range[1]; value += primaryStep) {
+            const y = yFor(value, axes.primary.range);
+            context.strokeStyle = "#e0e0e0";
+            context.beginPath();
+            context.moveTo(area.left, y);
+            context.lineTo(area.left + area.width, y);
+            context.stroke();
+            context.fillStyle = "black";
+            context.fillText(value.toFixed(primaryStep < 1 ? 1 : 0), area.left - 6, y);
+        }
+
+        if (dualAxes) {
+            const secondaryStep = niceStep(axes.secondary.range[1] - axes.secondary.range[0], 8);
+            context.textAlign = "left";
+            for (let value = Math.ceil(axes.secondary.range[0] / secondaryStep) * secondaryStep; value <= axes.secondary.range[1]; value += secondaryStep) {
+                context.fillText(value.toFixed(secondaryStep < 1 ? 1 : 0), area.left + area.width + 6, yFor(value, axes.secondary.range));
+            }
+        }
+
+        // vertical grid and the time labels
+        const tickStep = TICK_STEPS.find((step) => span / step <= 10) || TICK_STEPS[TICK_STEPS.length - 1];
+        const offset = new Date().getTimezoneOffset() * MINUTE;
+        context.textAlign = "center";
+        context.textBaseline = "top";
+        // ticks land on local midnights and whole hours rather than utc ones
+        for (let time = Math.ceil((state.viewFrom - offset) / tickStep) * tickStep + offset; time <= state.viewTo; time += tickStep) {
+            const x = xFor(time, area);
+            context.strokeStyle = "#e0e0e0";
+            context.beginPath();
+            context.moveTo(x, area.top);
+            context.lineTo(x, area.top + area.height);
+            context.stroke();
+            context.fillStyle = "black";
+            context.fillText(formatTime(time, span), x, area.top + area.height + 6);
+        }
+
+        // axis descriptions
+        context.save();
+        context.textAlign = "center";
+        context.textBaseline = "top";
+        context.translate(8, area.top + area.height / 2);
+        context.rotate(-Math.PI / 2);
+        context.fillText(axisDescription(axes.primary.metrics), 0, 0);
+        context.restore();
+        if (dualAxes) {
+            context.save();
+            context.textAlign = "center";
+            context.textBaseline = "bottom";
+            context.translate(canvas.clientWidth - 8, area.top + area.height / 2);
+            context.rotate(-Math.PI / 2);
+            context.fillText(axisDescription(axes.secondary.metrics), 0, 0);
+            context.restore();
+        }
+
+        context.strokeStyle = "black";
+        context.strokeRect(area.left, area.top, area.width, area.height);
+
+        // the series, broken wherever a bucket has no readings
+        context.save();
+        context.beginPath();
+        context.rect(area.left, area.top, area.width, area.height);
+        context.clip();
+        context.lineWidth = 2;
+        shown.forEach((metric) => {
+            const range = isSecondary(metric) ? axes.secondary.range : axes.primary.range;
+            context.strokeStyle = metric.color;
+            context.beginPath();
+            let previous = null;
+            visible.forEach((row) => {
+                const value = row.values[metric.key] && row.values[metric.key].mean;
+                if (value === undefined) {
+                    previous = null;
+                    return;
+                }
+                const x = xFor(row.time, area);
+                const y = yFor(value, range);
+                if (previous === null || row.time - previous > state.bucketMillis * 1.5) {
+                    context.moveTo(x, y);
+                } else {
+                    context.lineTo(x, y);
+                }
+                previous = row.time;
+            });
+            context.stroke();
+        });
+        context.restore();
+
+        drawHover(area, shown, visible, axes, isSecondary, yFor);
+    }
+
+    function drawHover(area, shown, visible, axes, isSecondary, yFor) {
+        if (state.hoverX === null || state.drag !== null || visible.length === 0) {
+            tooltip.style.display = "none";
+            return;
+        }
+
+        const time = timeFor(state.hoverX, area);
+        const nearest = visible.reduce((best, row) => (Math.abs(row.time - time) < Math.abs(best.time - time) ? row : best));
+        const x = xFor(nearest.time, area);
+        if (x < area.left || x > area.left + area.width) {
+            tooltip.style.display = "none";
+            return;
+        }
+
+        context.strokeStyle = "#808080";
+        context.lineWidth = 1;
+        context.beginPath();
+        context.moveTo(x, area.top);
+        context.lineTo(x, area.top + area.height);
+        context.stroke();
+
+        tooltip.replaceChildren();
+        const heading = document.createElement("b");
+        heading.textContent = formatTime(nearest.time, 7 * DAY);
+        tooltip.appendChild(heading);
+
+        shown.forEach((metric) => {
+            const value = nearest.values[metric.key] && nearest.values[metric.key].mean;
+            if (value === undefined) {
+                return;
+            }
+            const range = isSecondary(metric) ? axes.secondary.range : axes.primary.range;
+            context.fillStyle = metric.color;
+            context.beginPath();
+            context.arc(x, yFor(value, range), 4, 0, 2 * Math.PI);
+            context.fill();
+
+            const line = document.createElement("div");
+            line.style.color = metric.color === "#00ff00" || metric.color === "#00ffff" ? "black" : metric.color;
+            line.textContent = metric.name + ": " + value.toFixed(1) + " " + metric.unit;
+            tooltip.appendChild(line);
+        });
+
+        tooltip.style.display = "block";
+        // keep the tooltip on the side of the cursor with more room
+        const left = x > area.left + area.width / 2 ? x - tooltip.offsetWidth - 12 : x + 12;
+        tooltip.style.left = left + "px";
+        tooltip.style.top = area.top + 10 + "px";
+    }
+
+    function setUpControls() {
+        const ranges = document.getElementById("ranges");
+        RANGES.forEach(([label, span]) => {
+            const button = document.createElement("button");
+            button.textContent = label;
+            button.addEventListener("click", () => setRange(span));
+            ranges.appendChild(button);
+        });
+
+        const series = document.getElementById("series");
+        METRICS.forEach((metric) => {
+            const label = document.createElement("label");
+            label.style.marginRight = "10px";
+            const checkbox = document.createElement("input");
+            checkbox.type = "checkbox";
+            checkbox.checked = metric.shown;
+            checkbox.addEventListener("change", () => {
+                metric.shown = checkbox.checked;
+                draw();
+            });
+            label.appendChild(checkbox);
+            label.appendChild(document.createTextNode(metric.name));
+            series.appendChild(label);
+        });
+
+        canvas.addEventListener("wheel", (event) => {
+            event.preventDefault();
+            const area = plotArea();
+            // zoom around the time under the cursor
+            const anchor = timeFor(event.offsetX, area);
+            const factor = event.deltaY > 0 ? 1.25 : 0.8;
+            const span = Math.min(MAX_SPAN, Math.max(MIN_SPAN, (state.viewTo - state.viewFrom) * factor));
+            const before = (anchor - state.viewFrom) / (state.viewTo - state.viewFrom);
+            state.viewFrom = anchor - span * before;
+            state.viewTo = state.viewFrom + span;
+            draw();
+            scheduleFetch();
+        });
+
+        canvas.addEventListener("mousedown", (event) => {
+            state.drag = { x: event.offsetX, from: state.viewFrom, to: state.viewTo };
+            canvas.style.cursor = "grabbing";
+        });
+
+        canvas.addEventListener("mousemove", (event) => {
+            state.hoverX = event.offsetX;
+            if (state.drag !== null) {
+                const area = plotArea();
+                const shift = ((event.offsetX - state.drag.x) / area.width) * (state.drag.to - state.drag.from);
+                state.viewFrom = state.drag.from - shift;
+                state.viewTo = state.drag.to - shift;
+            }
+            draw();
+        });
+
+        const endDrag = () => {
+            if (state.drag !== null) {
+                state.drag = null;
+                canvas.style.cursor = "grab";
+                draw();
+                scheduleFetch();
+            }
+        };
+        canvas.addEventListener("mouseup", endDrag);
+        canvas.addEventListener("mouseleave", () => {
+            state.hoverX = null;
+            endDrag();
+            draw();
+        });
+
+        canvas.addEventListener("dblclick", () => {
+            setView(state.rangeFrom, state.rangeTo);
+            scheduleFetch();
+        });
+
+        window.addEventListener("resize", draw);
+    }
+
+    let lastLiveFetch = 0;
+
+    // While the view reaches the present it moves along with new readings, like a strip chart
+    function followReadings() {
+        const source = new EventSource("/api/v1/stream?" + new URLSearchParams({ location: location }));
+        source.addEventListener("reading", () => {
+            if (state.viewTo < state.rangeTo - state.bucketMillis) {
+                return;
+            }
+            const now = Date.now();
+            const moved = now - state.rangeTo;
+            state.rangeFrom += moved;
+            state.rangeTo = now;
+            setView(state.viewFrom + moved, state.viewTo + moved);
+
+            // one fetch per bucket is enough for the new reading to show up
+            if (now - lastLiveFetch >= Math.max(state.bucketMillis, MINUTE)) {
+                lastLiveFetch = now;
+                fetchRows();
+            }
+        });
+    }
+
+    setUpControls();
+    setRange(DAY);
+    followReadings();
+})();
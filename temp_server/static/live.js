@@ -0,0 +1,128 @@
+// Keeps the dashboard and location pages up to date with readings from the /api/v1/stream
+// server-sent events. Every element with a data-location attribute is a sensor, whose
+// .temperature, .humidity, .last-modified and .health children are updated in place, showing
+// temperatures in the unit of its data-unit attribute.
+"use strict";
+
+(function () {
+    // How often health is worked out again, sensors go late without sending anything
+    const HEALTH_CHECK_MILLIS = 10 * 1000;
+    // Plot images are drawn on the server, so they are reloaded at most this often
+    const PLOT_REFRESH_MILLIS = 60 * 1000;
+
+    const sensors = Array.from(document.querySelectorAll("[data-location]"));
+    const notices = document.getElementById("health-notices");
+    const plots = Array.from(document.querySelectorAll("img.live-plot"));
+
+    // Same format as the server, 01/31/2025 01:02:03 PM
+    function formatTime(date) {
+        const pad = (value) => String(value).padStart(2, "0");
+        const hours = date.getHours() % 12 === 0 ? 12 : date.getHours() % 12;
+        return pad(date.getMonth() + 1) + "/" + pad(date.getDate()) + "/" + date.getFullYear() + " " +
+            pad(hours) + ":" + pad(date.getMinutes()) + ":" + pad(date.getSeconds()) + " " +
+            (date.getHours() < 12 ? "AM" : "PM");
+    }
+
+    // Readings are always in Fahrenheit
+    function formatTemperature(sensor, fahrenheit) {
+        if (sensor.dataset.unit === "C") {
+            return ((fahrenheit - 32) / 1.8).toFixed(1) + " C";
+        }
+        return fahrenheit.toFixed(1) + " F";
+    }
+
+    function setText(sensor, selector, text) {
+        const element = sensor.querySelector(selector);
+        if (element) {
+            element.textContent = text;
+        }
+    }
+
+    // Same rules as SensorHealth on the server
+    function healthOf(sensor, now) {
+        const lastSeen = Date.parse(sensor.dataset.lastSeen);
+        if (Number.isNaN(lastSeen)) {
+            return "missing";
+        }
+        const ageSeconds = (now - lastSeen) / 1000;
+        if (ageSeconds > Number(sensor.dataset.missingAfter)) {
+            return "missing";
+        }
+        if (ageSeconds > Number(sensor.dataset.lateAfter)) {
+            return "late";
+        }
+        return "ok";
+    }
+
+    function updateHealth() {
+        const now = Date.now();
+        sensors.forEach((sensor) => {
+            const element = sensor.querySelector(".health");
+            if (element) {
+                const health = healthOf(sensor, now);
+                element.className = "health " + health;
+                element.textContent = health;
+            }
+        });
+
+        if (notices) {
+            notices.replaceChildren();
+            sensors.forEach((sensor) => {
+                const health = healthOf(sensor, now);
+                if (health === "ok") {
+                    return;
+                }
+                const notice = document.createElement("b");
+                notice.className = health;
+                notice.textContent = (health === "missing" ? "MIA Sensor: " : "Late Sensor: ") +
+                    (sensor.dataset.name || sensor.dataset.location) + ", Last modified: " +
+                    sensor.querySelector(".last-modified").textContent;
+                notices.append(notice, document.createElement("br"));
+            });
+        }
+    }
+
+    let lastPlotRefresh = Date.now();
+
+    function refreshPlots() {
+        const now = Date.now();
+        if (now - lastPlotRefresh < PLOT_REFRESH_MILLIS) {
+            return;
+        }
+        lastPlotRefresh = now;
+        plots.forEach((plot) => {
+            const url = new URL(plot.src);
+            url.searchParams.set("refresh", now);
+            plot.src = url.toString();
+        });
+    }
+
+    function onReading(event) {
+        const reading = JSON.parse(event.data);
+        const matching = sensors.filter((sensor) => sensor.dataset.location === reading.location);
+
+        // a location that reported for the first time, the dashboard needs a new row
+        if (matching.length === 0 && document.getElementById("sensors")) {
+            window.location.reload();
+            return;
+        }
+
+        matching.forEach((sensor) => {
+            sensor.dataset.lastSeen = reading.reading_time;
+            setText(sensor, ".temperature", formatTemperature(sensor, reading.temperature));
+            setText(sensor, ".humidity", reading.humidity.toFixed(1) + " %");
+            setText(sensor, ".last-modified", formatTime(new Date(reading.reading_time)));
+        });
+        updateHealth();
+        refreshPlots();
+    }
+
+    // a location page only needs its own readings
+    const query = sensors.length === 1 && !document.getElementById("sensors")
+        ? "?" + new URLSearchParams({ location: sensors[0].dataset.location })
+        : "";
+    const source = new EventSource("/api/v1/stream" + query);
+    source.addEventListener("reading", onReading);
+
+    setInterval(updateHealth, HEALTH_CHECK_MILLIS);
+})();